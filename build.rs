@@ -0,0 +1,19 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    grpc();
+}
+
+/// Generate the nordvpnd client (and the server used by the tests) from
+/// the vendored protobuf definitions, with a bundled `protoc`.
+#[cfg(feature = "grpc")]
+fn grpc() {
+    let protoc = protoc_bin_vendored::protoc_bin_path().expect("bundled protoc is available");
+    // SAFETY: build scripts are single threaded
+    unsafe { std::env::set_var("PROTOC", protoc) };
+    // The daemon has a `Connect` call, which clashes with the generated
+    // `DaemonClient::connect` constructor; channels are built by hand anyway
+    tonic_prost_build::configure()
+        .build_transport(false)
+        .compile_protos(&["proto/daemon.proto"], &["proto"])
+        .expect("daemon.proto compiles");
+}
@@ -0,0 +1,486 @@
+//! Stand-in for the `nordvpn` CLI, used by the end-to-end tests.
+//!
+//! Behaviour is driven by a TOML state file, `nordvpn-state.toml` next to
+//! the path the binary was invoked as (or `$FAKE_NORDVPN_STATE`). Tests
+//! symlink this binary into a temporary directory as `nordvpn`, write the
+//! state they want and point nordvpn-tui at the symlink. Every invocation
+//! is appended to `nordvpn-calls.log` in the same directory.
+//!
+//! Output mimics CLI version 3.19, including the spinner it prints first.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+const SPINNER: &str = "\r-\r  \r\r-\r  \r";
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+struct State {
+    /// `connected`, `connecting` or `disconnected`
+    status: String,
+    country: String,
+    city: String,
+    hostname: String,
+    ip: String,
+    /// Printed as the `Transfer` line while connected, if set
+    transfer: String,
+    countries: Vec<String>,
+    groups: Vec<String>,
+    /// Cities per country; countries not listed have none
+    cities: BTreeMap<String, Vec<String>>,
+    /// Country of each server that can be connected to by hostname
+    servers: BTreeMap<String, String>,
+    settings: BTreeMap<String, String>,
+    /// Delay before every command answers
+    latency_ms: u64,
+    /// Bytes written to stderr before every command answers
+    stderr_bytes: usize,
+    /// Commands that fail with `fail_message`, either the first argument
+    /// (`set`) or the first words (`set obfuscate`)
+    fail: Vec<String>,
+    fail_message: String,
+    /// When false every command fails as if nordvpnd were not running
+    daemon: bool,
+    logged_in: bool,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect();
+        Self {
+            status: "disconnected".to_string(),
+            country: String::new(),
+            city: String::new(),
+            hostname: String::new(),
+            ip: String::new(),
+            transfer: String::new(),
+            countries: strings(&["Germany", "Netherlands", "Sweden", "United_States"]),
+            groups: strings(&["Double_VPN", "Europe", "P2P", "The_Americas"]),
+            cities: BTreeMap::new(),
+            servers: [
+                ("de1045", "Germany"),
+                ("nl812", "Netherlands"),
+                ("se512", "Sweden"),
+            ]
+            .into_iter()
+            .map(|(server, country)| (server.to_string(), country.to_string()))
+            .collect(),
+            settings: [
+                ("Technology", "NORDLYNX"),
+                ("Firewall", "enabled"),
+                ("Kill Switch", "disabled"),
+                ("Threat Protection Lite", "disabled"),
+                ("Auto-connect", "disabled"),
+                ("Meshnet", "disabled"),
+                ("Obfuscate", "disabled"),
+                ("DNS", "disabled"),
+                ("Post-quantum VPN", "disabled"),
+            ]
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect(),
+            latency_ms: 0,
+            stderr_bytes: 0,
+            fail: vec![],
+            fail_message: "Whoops! Something went wrong.".to_string(),
+            daemon: true,
+            logged_in: true,
+        }
+    }
+}
+
+fn state_path(argv0: &str) -> PathBuf {
+    std::env::var_os("FAKE_NORDVPN_STATE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(argv0).with_file_name("nordvpn-state.toml"))
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    let path = state_path(&args[0]);
+    let mut state: State = fs::read_to_string(&path)
+        .ok()
+        .and_then(|contents| toml::from_str(&contents).ok())
+        .unwrap_or_default();
+    let args = &args[1..];
+
+    if let Ok(mut log) = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path.with_file_name("nordvpn-calls.log"))
+    {
+        let _ = writeln!(log, "{}", args.join(" "));
+    }
+
+    std::thread::sleep(Duration::from_millis(state.latency_ms));
+    eprint!("{}", ".".repeat(state.stderr_bytes));
+    print!("{}", SPINNER);
+
+    let command = args.first().map(String::as_str).unwrap_or("");
+    if !state.daemon {
+        return fail("Cannot reach System Daemon.");
+    }
+    let command_line = args.join(" ");
+    if state
+        .fail
+        .iter()
+        .any(|f| f == command || command_line.starts_with(&format!("{} ", f)))
+    {
+        let message = state.fail_message.clone();
+        return fail(&message);
+    }
+
+    match command {
+        "--version" => println!("NordVPN Version 3.19.0"),
+        "status" => print_status(&state),
+        "countries" => println!("{}", state.countries.join("\n")),
+        "groups" => println!("{}", state.groups.join("\n")),
+        "cities" => {
+            let Some(country) = args.get(1).and_then(|c| find(&state.countries, c)) else {
+                return fail("The specified country does not exist.");
+            };
+            let cities = state.cities.get(&country).cloned().unwrap_or_default();
+            println!("{}", cities.join("\n"));
+        }
+        "settings" => {
+            for (key, value) in &state.settings {
+                // Allowlist entries are listed one per line
+                if key.starts_with("Allowlisted") {
+                    println!("{}:", key);
+                    for entry in value.split(", ") {
+                        println!("\t{}", entry);
+                    }
+                } else {
+                    println!("{}: {}", key, value);
+                }
+            }
+        }
+        "account" => {
+            if !state.logged_in {
+                return fail("You are not logged in.");
+            }
+            println!("Account Information:");
+            println!("Email Address: user@example.com");
+            println!("VPN Service: Active (Expires on Mar 14th, 2027)");
+        }
+        "connect" | "c" => {
+            if !state.logged_in {
+                return fail("You are not logged in.");
+            }
+            let (country, server) = match args.get(1) {
+                Some(target) => match state.servers.get(&target.to_lowercase()) {
+                    Some(country) => (country.clone(), target.to_lowercase()),
+                    None => match find(&state.countries, target) {
+                        Some(country) => {
+                            let server = format!("{}1045", code(&country));
+                            (country, server)
+                        }
+                        // Groups pick the last country, so tests can tell
+                        // them from a bare connect
+                        None if find(&state.groups, target).is_some() => {
+                            match state.countries.last() {
+                                Some(country) => {
+                                    (country.clone(), format!("{}1045", code(country)))
+                                }
+                                None => return fail("No servers are available."),
+                            }
+                        }
+                        None => return fail("The specified country does not exist."),
+                    },
+                },
+                None => match state.countries.first() {
+                    Some(country) => (country.clone(), format!("{}1045", code(country))),
+                    None => return fail("No servers are available."),
+                },
+            };
+            let cities = state.cities.get(&country).cloned().unwrap_or_default();
+            let city = match args.get(2) {
+                Some(wanted) => match find(&cities, wanted) {
+                    Some(city) => city,
+                    None => return fail("The specified city does not exist."),
+                },
+                None => cities.first().cloned().unwrap_or_default(),
+            };
+            let number = server.trim_start_matches(|c: char| !c.is_ascii_digit());
+            state.status = "connected".to_string();
+            state.country = country.replace('_', " ");
+            state.city = city.replace('_', " ");
+            state.hostname = format!("{}.nordvpn.com", server);
+            state.ip = "185.130.184.86".to_string();
+            save(&path, &state);
+            let server = format!("{} #{} ({})", state.country, number, state.hostname);
+            println!("Connecting to {}", server);
+            println!("You are connected to {}!", server);
+        }
+        "disconnect" | "d" => {
+            if state.status == "disconnected" {
+                println!("You are not connected to NordVPN.");
+            } else {
+                state.status = "disconnected".to_string();
+                save(&path, &state);
+                println!("You are disconnected from NordVPN.");
+            }
+        }
+        "allowlist" => {
+            let usage = "Usage: nordvpn allowlist add|remove port|ports|subnet <value> \
+                         [protocol TCP|UDP]";
+            let (Some(action), Some(kind)) = (args.get(1), args.get(2)) else {
+                return fail(usage);
+            };
+            let (values, protocol) = match args[3..].iter().position(|arg| arg == "protocol") {
+                Some(i) => (&args[3..3 + i], args.get(4 + i).map(|p| p.to_uppercase())),
+                None => (&args[3..], None),
+            };
+            let protocol = protocol.unwrap_or_else(|| "UDP|TCP".to_string());
+            let (key, entry) = match (kind.as_str(), values) {
+                ("subnet", [subnet]) => ("Allowlisted subnets", subnet.clone()),
+                ("port", [port]) => ("Allowlisted ports", format!("{} ({})", port, protocol)),
+                ("ports", [start, end]) => (
+                    "Allowlisted ports",
+                    format!("{} - {} ({})", start, end, protocol),
+                ),
+                ("subnet" | "port" | "ports", _) => return fail(usage),
+                _ => return fail(&format!("Command '{}' doesn't exist.", kind)),
+            };
+            let value = values.join(" ");
+            let mut entries: Vec<String> = state
+                .settings
+                .get(key)
+                .map(|value| value.split(", ").map(str::to_string).collect())
+                .unwrap_or_default();
+            match action.as_str() {
+                "add" if entries.contains(&entry) => {
+                    return fail(&format!("{} {} is already allowlisted.", kind, value));
+                }
+                "add" => entries.push(entry),
+                "remove" if !entries.contains(&entry) => {
+                    return fail(&format!("{} {} is not allowlisted.", kind, value));
+                }
+                "remove" => entries.retain(|e| *e != entry),
+                _ => return fail(&format!("Command '{}' doesn't exist.", action)),
+            }
+            if entries.is_empty() {
+                state.settings.remove(key);
+            } else {
+                state.settings.insert(key.to_string(), entries.join(", "));
+            }
+            save(&path, &state);
+            println!(
+                "{} {} is {} the allowlist successfully.",
+                kind,
+                value,
+                if action == "add" {
+                    "added to"
+                } else {
+                    "removed from"
+                }
+            );
+        }
+        "set" => {
+            let (Some(key), Some(value)) = (args.get(1), args.get(2)) else {
+                return fail("Usage: nordvpn set <setting> <value>");
+            };
+            let normalize = |s: &str| s.replace([' ', '-', '_'], "").to_lowercase();
+            if normalize(key) == "protocol" {
+                if state.settings.get("Technology").map(String::as_str) != Some("OPENVPN") {
+                    return fail("Protocol setting is not available for this technology.");
+                }
+                let value = value.to_uppercase();
+                println!("Protocol is set to '{}' successfully.", value);
+                state.settings.insert("Protocol".to_string(), value);
+                save(&path, &state);
+                return ExitCode::SUCCESS;
+            }
+            if normalize(key) == "dns" {
+                let servers = &args[2..];
+                if matches!(value.as_str(), "off" | "false" | "disabled" | "0") {
+                    state
+                        .settings
+                        .insert("DNS".to_string(), "disabled".to_string());
+                    println!("DNS is set to 'disabled' successfully.");
+                } else {
+                    if servers.len() > 3 {
+                        return fail("More than 3 DNS addresses provided.");
+                    }
+                    if let Some(invalid) = servers
+                        .iter()
+                        .find(|s| s.parse::<std::net::IpAddr>().is_err())
+                    {
+                        return fail(&format!("'{}' is not a valid IP address.", invalid));
+                    }
+                    if state
+                        .settings
+                        .get("Threat Protection Lite")
+                        .map(String::as_str)
+                        == Some("enabled")
+                    {
+                        println!("Disabling Threat Protection Lite.");
+                        state
+                            .settings
+                            .insert("Threat Protection Lite".to_string(), "disabled".to_string());
+                    }
+                    let servers = servers.join(", ");
+                    println!("DNS is set to '{}' successfully.", servers);
+                    state.settings.insert("DNS".to_string(), servers);
+                }
+                save(&path, &state);
+                return ExitCode::SUCCESS;
+            }
+            if normalize(key) == "threatprotectionlite"
+                && matches!(value.as_str(), "on" | "true" | "enabled" | "1")
+                && state.settings.get("DNS").map(String::as_str) != Some("disabled")
+            {
+                println!("Disabling custom DNS.");
+                state
+                    .settings
+                    .insert("DNS".to_string(), "disabled".to_string());
+            }
+            let key = match normalize(key).as_str() {
+                "pq" | "postquantum" => "Post-quantum VPN".to_string(),
+                _ => key.clone(),
+            };
+            let Some(name) = state
+                .settings
+                .keys()
+                .find(|name| normalize(name) == normalize(&key))
+                .cloned()
+            else {
+                return fail(&format!("Command '{}' doesn't exist.", key));
+            };
+            let value = match value.as_str() {
+                "on" | "true" | "enabled" | "1" => "enabled".to_string(),
+                "off" | "false" | "disabled" | "0" => "disabled".to_string(),
+                other => other.to_uppercase(),
+            };
+            if let Some(conflict) = conflict(&state, &name, &value) {
+                return fail(conflict);
+            }
+            println!("{} is set to '{}' successfully.", name, value);
+            if name == "Technology" {
+                // Only OpenVPN has a protocol choice, UDP unless set
+                if value == "OPENVPN" {
+                    state
+                        .settings
+                        .insert("Protocol".to_string(), "UDP".to_string());
+                } else {
+                    state.settings.remove("Protocol");
+                }
+            }
+            state.settings.insert(name, value);
+            save(&path, &state);
+        }
+        _ => return fail(&format!("Command '{}' doesn't exist.", command)),
+    }
+    ExitCode::SUCCESS
+}
+
+fn print_status(state: &State) {
+    match state.status.as_str() {
+        "connected" => {
+            println!("Status: Connected");
+            let number: String = state
+                .hostname
+                .chars()
+                .filter(|c| c.is_ascii_digit())
+                .collect();
+            println!("Server: {} #{}", state.country, number);
+            println!("Hostname: {}", state.hostname);
+            println!("IP: {}", state.ip);
+            println!("Country: {}", state.country);
+            if !state.city.is_empty() {
+                println!("City: {}", state.city);
+            }
+            println!(
+                "Current technology: {}",
+                state.settings.get("Technology").map_or("NORDLYNX", |t| t)
+            );
+            println!(
+                "Current protocol: {}",
+                state.settings.get("Protocol").map_or("UDP", |p| p)
+            );
+            if !state.transfer.is_empty() {
+                println!("Transfer: {}", state.transfer);
+            }
+            println!("Uptime: 12 seconds");
+        }
+        "connecting" => println!("Status: Connecting"),
+        _ => println!("Status: Disconnected"),
+    }
+}
+
+/// The daemon refuses settings that do not work together, with errors that
+/// do not say what to change instead
+fn conflict(state: &State, name: &str, value: &str) -> Option<&'static str> {
+    let setting = |key: &str| state.settings.get(key).map(String::as_str);
+    let enabled = value == "enabled";
+    match name {
+        "Obfuscate" if enabled && setting("Technology") != Some("OPENVPN") => {
+            Some("Obfuscation is not available with the current technology.")
+        }
+        "Post-quantum VPN" if enabled && setting("Technology") != Some("NORDLYNX") => {
+            Some("Post-quantum encryption is not compatible with the current technology.")
+        }
+        "Post-quantum VPN" if enabled && setting("Meshnet") == Some("enabled") => {
+            Some("Post-quantum encryption is not compatible with Meshnet.")
+        }
+        "Meshnet" if enabled && setting("Post-quantum VPN") == Some("enabled") => {
+            Some("Meshnet is not compatible with post-quantum encryption.")
+        }
+        "Technology" if value != "OPENVPN" && setting("Obfuscate") == Some("enabled") => {
+            Some("This setting is not compatible with obfuscation.")
+        }
+        "Technology" if value != "NORDLYNX" && setting("Post-quantum VPN") == Some("enabled") => {
+            Some("This setting is not compatible with post-quantum encryption.")
+        }
+        _ => None,
+    }
+}
+
+/// Two-letter prefix used for a country's server hostnames, as NordVPN
+/// names them (which is "uk" rather than "gb")
+fn code(country: &str) -> String {
+    let code = match country {
+        "Australia" => "au",
+        "Austria" => "at",
+        "Belgium" => "be",
+        "Canada" => "ca",
+        "France" => "fr",
+        "Germany" => "de",
+        "Italy" => "it",
+        "Japan" => "jp",
+        "Netherlands" => "nl",
+        "Norway" => "no",
+        "Poland" => "pl",
+        "Spain" => "es",
+        "Sweden" => "se",
+        "Switzerland" => "ch",
+        "United_Kingdom" => "uk",
+        "United_States" => "us",
+        _ => return country.chars().take(2).collect::<String>().to_lowercase(),
+    };
+    code.to_string()
+}
+
+/// Case-insensitive lookup that accepts spaces for underscores
+fn find(names: &[String], wanted: &str) -> Option<String> {
+    let wanted = wanted.replace(' ', "_");
+    names
+        .iter()
+        .find(|name| name.eq_ignore_ascii_case(&wanted))
+        .cloned()
+}
+
+fn save(path: &Path, state: &State) {
+    let contents = toml::to_string(state).expect("state serializes");
+    fs::write(path, contents).expect("state file is writable");
+}
+
+fn fail(message: &str) -> ExitCode {
+    eprintln!("{}", message);
+    ExitCode::FAILURE
+}
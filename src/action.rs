@@ -0,0 +1,235 @@
+use crate::app::View;
+use crate::types::{Target, Toggle};
+use std::fmt;
+
+/// Something the user can do, reachable from the command palette
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Connect(Target),
+    SearchServer,
+    SwitchTechnology,
+    Disconnect,
+    RefreshStatus,
+    ToggleFavorite,
+    ToggleWatchdog,
+    /// Turn a daemon setting such as the kill switch on or off
+    Toggle(Toggle),
+    LeakTest,
+    ShowView(View),
+    ApplyProfile(String),
+    RunDiagnostics,
+    CopyReport,
+    ShowHelp,
+    Quit,
+}
+
+impl Action {
+    /// Key that triggers this action directly, if any
+    pub fn key_hint(&self) -> Option<&'static str> {
+        match self {
+            Action::Connect(Target::Quick) => Some("Ctrl+K"),
+            Action::Connect(_) => None,
+            Action::SearchServer => Some("Ctrl+S"),
+            Action::SwitchTechnology => Some("Ctrl+T"),
+            Action::Disconnect => Some("Ctrl+D"),
+            Action::RefreshStatus => Some("Ctrl+R"),
+            Action::ToggleFavorite => Some("Ctrl+F"),
+            Action::ToggleWatchdog => Some("Ctrl+W"),
+            Action::Toggle(_) => None,
+            Action::LeakTest => None,
+            Action::ShowView(_) => Some("Tab"),
+            Action::ApplyProfile(_) => None,
+            Action::RunDiagnostics => None,
+            Action::CopyReport => None,
+            Action::ShowHelp => Some("?"),
+            Action::Quit => Some("q"),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Connect(Target::Quick) => write!(f, "Quick connect to fastest server"),
+            Action::Connect(Target::Group(group)) => {
+                write!(f, "Quick connect within {}", group.replace('_', " "))
+            }
+            Action::Connect(target) => write!(f, "Connect to {}", target),
+            Action::SearchServer => write!(f, "Connect to a server by hostname"),
+            Action::SwitchTechnology => write!(f, "Switch technology, obfuscation or post-quantum"),
+            Action::Disconnect => write!(f, "Disconnect"),
+            Action::RefreshStatus => write!(f, "Refresh status"),
+            Action::ToggleFavorite => write!(f, "Toggle favorite for selected country"),
+            Action::ToggleWatchdog => write!(f, "Toggle auto-reconnect watchdog"),
+            Action::Toggle(toggle) => write!(f, "Toggle {}", toggle),
+            Action::LeakTest => write!(f, "Run leak test on current connection"),
+            Action::ShowView(view) => write!(f, "Go to {}", view.title()),
+            Action::ApplyProfile(name) => write!(f, "Apply profile {}", name),
+            Action::RunDiagnostics => write!(f, "Run diagnostics"),
+            Action::CopyReport => write!(f, "Copy diagnostics report to clipboard"),
+            Action::ShowHelp => write!(f, "Show key bindings"),
+            Action::Quit => write!(f, "Quit"),
+        }
+    }
+}
+
+/// Key bindings shown in the help overlay, grouped by where they apply
+pub const KEY_BINDINGS: &[(&str, &[(&str, &str)])] = &[
+    (
+        "Everywhere",
+        &[
+            ("Tab / Shift+Tab", "next / previous view"),
+            ("Ctrl+K", "quick connect to fastest server"),
+            ("Ctrl+D", "disconnect"),
+            ("Ctrl+W", "toggle watchdog"),
+            ("Ctrl+S", "connect to a server by hostname"),
+            ("Ctrl+T", "technology, obfuscation, post-quantum"),
+            (": / Ctrl+P", "command palette"),
+            ("?", "this help"),
+            ("Esc / q / Ctrl+C", "quit"),
+        ],
+    ),
+    (
+        "Countries",
+        &[
+            ("↑/↓ j/k", "navigate"),
+            ("Enter", "connect to selected country"),
+            ("Ctrl+F", "toggle favorite"),
+            ("Ctrl+R", "refresh status"),
+            ("/ or type", "filter countries"),
+            ("Backspace", "edit filter"),
+        ],
+    ),
+    (
+        "Filter",
+        &[
+            ("type", "narrow the list"),
+            ("↑/↓", "leave filter and navigate"),
+            ("Enter / Esc", "leave filter mode"),
+        ],
+    ),
+    (
+        "Server search",
+        &[
+            ("type", "hostname, e.g. uk2345"),
+            ("↑/↓", "select completion"),
+            ("Tab", "complete"),
+            ("Enter", "connect"),
+            ("Ctrl+F", "toggle favorite"),
+            ("Esc", "close"),
+        ],
+    ),
+    (
+        "Technology",
+        &[
+            ("↑/↓", "select"),
+            ("r", "toggle reconnect"),
+            ("Enter / Space", "apply or toggle"),
+            ("Esc", "close"),
+        ],
+    ),
+    (
+        "DNS",
+        &[
+            ("e / Enter", "edit custom DNS servers"),
+            ("x", "reset to NordVPN's DNS"),
+            ("t", "toggle Threat Protection Lite"),
+            ("Ctrl+R", "reload settings"),
+        ],
+    ),
+    (
+        "Profiles",
+        &[
+            ("↑/↓ j/k", "select"),
+            ("Enter", "apply settings and connect"),
+        ],
+    ),
+    (
+        "Settings",
+        &[
+            ("e", "export settings to a file"),
+            ("i", "import a file to compare"),
+            ("a / Enter", "apply the imported file"),
+            ("Ctrl+R", "reload settings"),
+        ],
+    ),
+    (
+        "Diagnostics",
+        &[("Ctrl+R", "re-run checks"), ("c", "copy report")],
+    ),
+    (
+        "Command palette",
+        &[
+            ("type", "fuzzy search actions"),
+            ("↑/↓", "select"),
+            ("Enter", "run"),
+            ("Esc", "close"),
+        ],
+    ),
+];
+
+/// Score how well `query` fuzzily matches `candidate`.
+///
+/// Every query character must appear in order (case-insensitive). Matches
+/// at word starts and consecutive runs score higher; `None` means no match.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut last_match: Option<usize> = None;
+
+    for q in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = (pos..candidate.len()).find(|&i| candidate[i] == q)?;
+        score += 1;
+        if found == 0 || !candidate[found - 1].is_alphanumeric() {
+            score += 8;
+        }
+        if last_match.is_some_and(|last| last + 1 == found) {
+            score += 5;
+        }
+        // Prefer matches that start early and have few gaps
+        score -= (found - pos) as i64;
+        last_match = Some(found);
+        pos = found + 1;
+    }
+
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_score_ranks_matches() {
+        // Prefix and word-start runs beat the same letters scattered
+        let prefix = fuzzy_score("dis", "Disconnect").unwrap();
+        let scattered = fuzzy_score("dis", "Add to favorites").unwrap();
+        assert!(prefix > scattered, "{prefix} <= {scattered}");
+        let word_start = fuzzy_score("dns", "Open DNS settings").unwrap();
+        let inside = fuzzy_score("dns", "Show windows sensors").unwrap();
+        assert!(word_start > inside, "{word_start} <= {inside}");
+        // Earlier matches win among equals
+        assert!(fuzzy_score("con", "Connect") > fuzzy_score("con", "Quick connect"));
+    }
+
+    #[test]
+    fn fuzzy_score_ignores_case_and_spaces() {
+        assert_eq!(
+            fuzzy_score("QUICK", "quick connect"),
+            fuzzy_score("quick", "Quick Connect")
+        );
+        assert_eq!(
+            fuzzy_score("q c", "Quick connect"),
+            fuzzy_score("qc", "Quick connect")
+        );
+        assert_eq!(fuzzy_score("", "Quick connect"), Some(0));
+    }
+
+    #[test]
+    fn fuzzy_score_requires_every_character_in_order() {
+        assert_eq!(fuzzy_score("xyz", "Disconnect"), None);
+        assert_eq!(fuzzy_score("tcennocsid", "Disconnect"), None);
+        assert_eq!(fuzzy_score("disconnects", "Disconnect"), None);
+    }
+}
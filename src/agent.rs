@@ -0,0 +1,655 @@
+//! Auto-connect agent: watches the network and applies the `[agent]` rules
+//! from the config file whenever it changes.
+//!
+//! Route changes arrive as netlink events, the Wi-Fi name comes from an
+//! [`SsidProvider`], and the decisions are made by [`Engine`], which does no
+//! I/O of its own.
+
+use crate::config::{AgentConfig, Config};
+use crate::nordvpn::NordVPN;
+use crate::profile::{self, Step};
+use crate::types::{ConnectionStatus, Target};
+use serde::Deserialize;
+use std::fmt;
+use std::process::Command;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+/// Route events tend to come in bursts; wait for the network to settle
+const SETTLE: Duration = Duration::from_secs(2);
+/// Interfaces created by the VPN itself, which never count as the network
+const TUNNEL_PREFIXES: &[&str] = &["nordlynx", "nordtun", "tun", "tap", "wg"];
+
+/// One `[[agent.rules]]` entry; the first rule matching the network wins.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Wi-Fi networks the rule applies to
+    #[serde(default)]
+    pub ssid: Vec<String>,
+    /// Kind of network the rule applies to
+    #[serde(default)]
+    pub network: Option<NetworkKind>,
+    pub action: RuleAction,
+    /// Profile to apply when connecting; the fastest server without one
+    #[serde(default)]
+    pub profile: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NetworkKind {
+    /// Any network with a default route
+    Any,
+    Wifi,
+    /// Wi-Fi listed in `trusted_ssids`
+    TrustedWifi,
+    /// Wi-Fi not listed in `trusted_ssids`
+    UntrustedWifi,
+    /// A network without an SSID
+    Wired,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    Connect,
+    Disconnect,
+}
+
+impl Rule {
+    /// Check everything that does not depend on the rest of the config
+    pub fn validate(&self) -> Result<(), String> {
+        if self.ssid.is_empty() && self.network.is_none() {
+            return Err("set ssid or network".to_string());
+        }
+        if self.action == RuleAction::Disconnect && self.profile.is_some() {
+            return Err("profile only applies to connect".to_string());
+        }
+        Ok(())
+    }
+
+    fn matches(&self, network: &NetworkState, trusted_ssids: &[String]) -> bool {
+        if network.interface.is_none() {
+            return false;
+        }
+        let ssid = network.ssid.as_ref();
+        let kind = match self.network {
+            None | Some(NetworkKind::Any) => true,
+            Some(NetworkKind::Wifi) => ssid.is_some(),
+            Some(NetworkKind::TrustedWifi) => ssid.is_some_and(|s| trusted_ssids.contains(s)),
+            Some(NetworkKind::UntrustedWifi) => ssid.is_some_and(|s| !trusted_ssids.contains(s)),
+            Some(NetworkKind::Wired) => ssid.is_none(),
+        };
+        kind && (self.ssid.is_empty() || ssid.is_some_and(|s| self.ssid.contains(s)))
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let network = match self.network {
+            None | Some(NetworkKind::Any) if !self.ssid.is_empty() => "Wi-Fi",
+            None | Some(NetworkKind::Any) => "any network",
+            Some(NetworkKind::Wifi) => "Wi-Fi",
+            Some(NetworkKind::TrustedWifi) => "trusted Wi-Fi",
+            Some(NetworkKind::UntrustedWifi) => "untrusted Wi-Fi",
+            Some(NetworkKind::Wired) => "wired network",
+        };
+        write!(f, "on {}", network)?;
+        if !self.ssid.is_empty() {
+            let names: Vec<String> = self.ssid.iter().map(|s| format!("{:?}", s)).collect();
+            write!(f, " {}", names.join(" or "))?;
+        }
+        match (self.action, &self.profile) {
+            (RuleAction::Connect, Some(profile)) => write!(f, ", apply profile {}", profile),
+            (RuleAction::Connect, None) => write!(f, ", connect to the fastest server"),
+            (RuleAction::Disconnect, _) => write!(f, ", disconnect"),
+        }
+    }
+}
+
+/// The network the machine is on, ignoring VPN tunnels
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetworkState {
+    /// Interface holding the default route; `None` when offline
+    pub interface: Option<String>,
+    /// Name of the connected Wi-Fi network
+    pub ssid: Option<String>,
+}
+
+impl NetworkState {
+    /// Read the default route and ask `ssid` for the Wi-Fi name
+    pub fn current(ssid: &dyn SsidProvider) -> Self {
+        let read = |path| std::fs::read_to_string(path).unwrap_or_default();
+        let interface = default_interface(&read("/proc/net/route"), &read("/proc/net/ipv6_route"));
+        Self {
+            ssid: interface.as_ref().and_then(|_| ssid.ssid()),
+            interface,
+        }
+    }
+}
+
+impl fmt::Display for NetworkState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.interface, &self.ssid) {
+            (None, _) => write!(f, "offline"),
+            (Some(interface), Some(ssid)) => write!(f, "Wi-Fi {:?} on {}", ssid, interface),
+            (Some(interface), None) => write!(f, "{}", interface),
+        }
+    }
+}
+
+/// What the engine decided after the network changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// No rule matches the new network
+    NoMatch,
+    /// The matching rule (by index) is already in effect
+    Satisfied(usize),
+    /// Run the matching rule's action
+    Run(usize),
+}
+
+/// Evaluates the rules against each network sample.
+///
+/// Rules only fire when the network changes, so a manual connect or
+/// disconnect is left alone until the machine moves to another network.
+#[derive(Debug)]
+pub struct Engine {
+    rules: Vec<Rule>,
+    trusted_ssids: Vec<String>,
+    last: Option<NetworkState>,
+}
+
+impl Engine {
+    pub fn new(config: &AgentConfig) -> Self {
+        Self {
+            rules: config.rules.clone(),
+            trusted_ssids: config.trusted_ssids.clone(),
+            last: None,
+        }
+    }
+
+    pub fn rule(&self, index: usize) -> &Rule {
+        &self.rules[index]
+    }
+
+    /// Decide what to do about a network sample; `None` if it is the same
+    /// network as last time. `connected` is only asked when a rule matches.
+    pub fn on_network(
+        &mut self,
+        network: NetworkState,
+        connected: impl FnOnce() -> bool,
+    ) -> Option<Decision> {
+        if self.last.as_ref() == Some(&network) {
+            return None;
+        }
+        let matching = self
+            .rules
+            .iter()
+            .position(|rule| rule.matches(&network, &self.trusted_ssids));
+        self.last = Some(network);
+        let Some(index) = matching else {
+            return Some(Decision::NoMatch);
+        };
+        let wants_connected = self.rules[index].action == RuleAction::Connect;
+        Some(if connected() == wants_connected {
+            Decision::Satisfied(index)
+        } else {
+            Decision::Run(index)
+        })
+    }
+
+    /// Forget the last network so the next sample is evaluated again, e.g.
+    /// after the action failed
+    pub fn retry(&mut self) {
+        self.last = None;
+    }
+}
+
+/// Source of the connected Wi-Fi network's name
+pub trait SsidProvider {
+    /// `None` when not on Wi-Fi or the name cannot be read
+    fn ssid(&self) -> Option<String>;
+}
+
+/// Asks NetworkManager
+pub struct Nmcli;
+
+impl SsidProvider for Nmcli {
+    fn ssid(&self) -> Option<String> {
+        let output = Command::new("nmcli")
+            .args(["-t", "-f", "ACTIVE,SSID", "device", "wifi", "list"])
+            .args(["--rescan", "no"])
+            .output()
+            .ok()?;
+        output
+            .status
+            .success()
+            .then(|| nmcli_ssid(&String::from_utf8_lossy(&output.stdout)))
+            .flatten()
+    }
+}
+
+/// Runs a user-supplied command that prints the SSID, e.g. `iwgetid -r`
+pub struct SsidCommand(pub Vec<String>);
+
+impl SsidProvider for SsidCommand {
+    fn ssid(&self) -> Option<String> {
+        let (program, args) = self.0.split_first()?;
+        let output = Command::new(program).args(args).output().ok()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let ssid = stdout.trim();
+        (output.status.success() && !ssid.is_empty()).then(|| ssid.to_string())
+    }
+}
+
+/// The provider configured by `ssid_command`, falling back to nmcli
+pub fn ssid_provider(config: &AgentConfig) -> Box<dyn SsidProvider> {
+    match &config.ssid_command {
+        Some(command) => Box::new(SsidCommand(command.clone())),
+        None => Box::new(Nmcli),
+    }
+}
+
+/// Active SSID from `nmcli -t -f ACTIVE,SSID device wifi list` output, which
+/// escapes `:` and `\` in names with a backslash
+pub fn nmcli_ssid(output: &str) -> Option<String> {
+    let escaped = output.lines().find_map(|line| line.strip_prefix("yes:"))?;
+    let mut ssid = String::new();
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => ssid.extend(chars.next()),
+            c => ssid.push(c),
+        }
+    }
+    (!ssid.is_empty()).then_some(ssid)
+}
+
+/// Interface of the preferred default route in `/proc/net/route`, or in
+/// `/proc/net/ipv6_route` on IPv6-only networks
+pub fn default_interface(route: &str, ipv6_route: &str) -> Option<String> {
+    const RTF_UP: u32 = 0x1;
+    let ipv4 = route.lines().skip(1).filter_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [interface, destination, _, flags, _, _, metric, mask, ..] = fields[..] else {
+            return None;
+        };
+        let flags = u32::from_str_radix(flags, 16).ok()?;
+        (destination == "00000000" && mask == "00000000" && flags & RTF_UP != 0)
+            .then(|| (metric.parse::<u32>().unwrap_or(u32::MAX), interface))
+    });
+    let ipv6 = ipv6_route.lines().filter_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [destination, prefix, _, _, _, metric, _, _, _, interface] = fields[..] else {
+            return None;
+        };
+        (destination.bytes().all(|b| b == b'0') && prefix == "00" && interface != "lo").then(|| {
+            (
+                u32::from_str_radix(metric, 16).unwrap_or(u32::MAX),
+                interface,
+            )
+        })
+    });
+    best_route(ipv4).or_else(|| best_route(ipv6))
+}
+
+/// Interface of the lowest-metric route that is not a VPN tunnel
+fn best_route<'a>(routes: impl Iterator<Item = (u32, &'a str)>) -> Option<String> {
+    routes
+        .filter(|(_, interface)| !is_tunnel(interface))
+        .min_by_key(|(metric, _)| *metric)
+        .map(|(_, interface)| interface.to_string())
+}
+
+fn is_tunnel(interface: &str) -> bool {
+    TUNNEL_PREFIXES
+        .iter()
+        .any(|prefix| interface.starts_with(prefix))
+}
+
+/// Whether a batch of netlink messages adds or removes a default route in
+/// the main table
+pub fn default_route_changed(buf: &[u8]) -> bool {
+    const RTM_NEWROUTE: u16 = 24;
+    const RTM_DELROUTE: u16 = 25;
+    const RT_TABLE_MAIN: u8 = 254;
+    const HEADER: usize = 16;
+
+    let mut rest = buf;
+    while rest.len() >= HEADER {
+        let len = u32::from_ne_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        let kind = u16::from_ne_bytes([rest[4], rest[5]]);
+        if len < HEADER || len > rest.len() {
+            break;
+        }
+        // rtmsg: family, dst_len, src_len, tos, table, ...
+        let rtmsg = &rest[HEADER..len];
+        if matches!(kind, RTM_NEWROUTE | RTM_DELROUTE)
+            && rtmsg.len() >= 5
+            && rtmsg[1] == 0
+            && rtmsg[4] == RT_TABLE_MAIN
+        {
+            return true;
+        }
+        // The last message may be shorter than its padding
+        rest = rest.get((len + 3) & !3..).unwrap_or(&[]);
+    }
+    false
+}
+
+/// Listen for route changes on a netlink socket, sending a message for each
+/// batch that touches the default route
+#[cfg(target_os = "linux")]
+fn spawn_route_watcher(tx: Sender<()>) -> Result<(), String> {
+    use std::fs::File;
+    use std::io::Read;
+    use std::os::fd::FromRawFd;
+
+    // SAFETY: plain socket syscalls on a zeroed sockaddr_nl; the descriptor
+    // is handed to a File right away so it is closed when the thread ends
+    let mut socket = unsafe {
+        let fd = libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            libc::NETLINK_ROUTE,
+        );
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error().to_string());
+        }
+        let socket = File::from_raw_fd(fd);
+        let mut addr: libc::sockaddr_nl = std::mem::zeroed();
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = (libc::RTMGRP_IPV4_ROUTE | libc::RTMGRP_IPV6_ROUTE) as u32;
+        let bound = libc::bind(
+            fd,
+            &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        );
+        if bound < 0 {
+            return Err(std::io::Error::last_os_error().to_string());
+        }
+        socket
+    };
+
+    thread::spawn(move || {
+        let mut buf = vec![0; 16 * 1024];
+        while let Ok(n) = socket.read(&mut buf) {
+            if n > 0 && default_route_changed(&buf[..n]) && tx.send(()).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn spawn_route_watcher(_tx: Sender<()>) -> Result<(), String> {
+    Err("route events are only supported on Linux".to_string())
+}
+
+/// Run the agent until killed, or evaluate the rules once with `once`.
+///
+/// Returns the exit code.
+pub fn run(config: &Config, once: bool) -> i32 {
+    let agent = &config.agent;
+    if agent.rules.is_empty() {
+        eprintln!("error: no [[agent.rules]] in the config file");
+        return 2;
+    }
+    let ssid = ssid_provider(agent);
+    let mut engine = Engine::new(agent);
+    let poll = Duration::from_secs(agent.poll_secs.max(1));
+
+    let (tx, rx) = mpsc::channel();
+    if !once && let Err(e) = spawn_route_watcher(tx.clone()) {
+        eprintln!(
+            "warning: cannot watch route changes ({}), checking every {}s",
+            e,
+            poll.as_secs()
+        );
+    }
+
+    loop {
+        let network = NetworkState::current(ssid.as_ref());
+        let decision = engine.on_network(network.clone(), || {
+            matches!(
+                NordVPN::get_status(),
+                Ok(ConnectionStatus::Connected { .. })
+            )
+        });
+        let ok = match decision {
+            None => true,
+            Some(decision) => {
+                println!("Network: {}", network);
+                match decision {
+                    Decision::NoMatch => println!("No rule matches"),
+                    Decision::Satisfied(i) => {
+                        println!("Rule {} ({}): nothing to do", i + 1, engine.rule(i))
+                    }
+                    Decision::Run(i) => println!("Rule {}: {}", i + 1, engine.rule(i)),
+                }
+                match decision {
+                    Decision::Run(i) => run_action(config, engine.rule(i)),
+                    _ => true,
+                }
+            }
+        };
+        if once {
+            return if ok { 0 } else { 1 };
+        }
+        if !ok {
+            engine.retry();
+        }
+
+        match rx.recv_timeout(poll) {
+            Ok(()) => {
+                thread::sleep(SETTLE);
+                while rx.try_recv().is_ok() {}
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            // The sender is kept alive above
+            Err(RecvTimeoutError::Disconnected) => unreachable!(),
+        }
+    }
+}
+
+/// Carry out a rule through the backend, printing the outcome
+fn run_action(config: &Config, rule: &Rule) -> bool {
+    match (rule.action, &rule.profile) {
+        (RuleAction::Connect, Some(name)) => match config.profiles.get(name) {
+            Some(profile) => profile::apply_printing(name, profile),
+            // Checked when the config is loaded
+            None => false,
+        },
+        (RuleAction::Connect, None) => {
+            profile::apply_steps_printing(&[Step::Connect(Target::Quick)])
+        }
+        (RuleAction::Disconnect, _) => match NordVPN::disconnect() {
+            Ok(()) => {
+                println!("✓ disconnect");
+                true
+            }
+            Err(e) => {
+                println!("✗ disconnect: {}", e);
+                false
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agent(toml: &str) -> AgentConfig {
+        Config::parse(toml).unwrap().agent
+    }
+
+    fn wifi(ssid: &str) -> NetworkState {
+        NetworkState {
+            interface: Some("wlan0".to_string()),
+            ssid: Some(ssid.to_string()),
+        }
+    }
+
+    fn wired() -> NetworkState {
+        NetworkState {
+            interface: Some("eth0".to_string()),
+            ssid: None,
+        }
+    }
+
+    const RULES: &str = r#"
+        [profiles.secure]
+        country = "Sweden"
+
+        [agent]
+        trusted_ssids = ["Home", "Office"]
+
+        [[agent.rules]]
+        ssid = ["Office"]
+        action = "disconnect"
+
+        [[agent.rules]]
+        network = "untrusted-wifi"
+        action = "connect"
+        profile = "secure"
+
+        [[agent.rules]]
+        network = "wired"
+        action = "connect"
+    "#;
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let mut engine = Engine::new(&agent(RULES));
+        assert_eq!(
+            engine.on_network(wifi("Office"), || true),
+            Some(Decision::Run(0))
+        );
+        assert_eq!(
+            engine.on_network(wifi("Cafe"), || false),
+            Some(Decision::Run(1))
+        );
+        assert_eq!(engine.on_network(wired(), || false), Some(Decision::Run(2)));
+        assert_eq!(
+            engine.on_network(wifi("Home"), || panic!("no rule, no status")),
+            Some(Decision::NoMatch)
+        );
+        assert_eq!(
+            engine.on_network(NetworkState::default(), || panic!("offline")),
+            Some(Decision::NoMatch)
+        );
+    }
+
+    #[test]
+    fn only_acts_when_the_network_changes() {
+        let mut engine = Engine::new(&agent(RULES));
+        assert_eq!(
+            engine.on_network(wifi("Cafe"), || true),
+            Some(Decision::Satisfied(1))
+        );
+        // The user disconnecting by hand is respected
+        assert_eq!(engine.on_network(wifi("Cafe"), || false), None);
+        assert_eq!(
+            engine.on_network(wifi("Airport"), || false),
+            Some(Decision::Run(1))
+        );
+        engine.retry();
+        assert_eq!(
+            engine.on_network(wifi("Airport"), || false),
+            Some(Decision::Run(1))
+        );
+    }
+
+    #[test]
+    fn validates_rules_with_the_config() {
+        for (toml, error) in [
+            (
+                "[[agent.rules]]\naction = \"connect\"",
+                "set ssid or network",
+            ),
+            (
+                "[[agent.rules]]\nnetwork = \"any\"\naction = \"disconnect\"\nprofile = \"x\"",
+                "only applies to connect",
+            ),
+            (
+                "[[agent.rules]]\nnetwork = \"wifi\"\naction = \"connect\"\nprofile = \"x\"",
+                "no profile named \"x\"",
+            ),
+        ] {
+            let e = format!("{:#}", Config::parse(toml).unwrap_err());
+            assert!(e.contains(error), "{toml:?} gave {e:?}");
+        }
+        assert_eq!(
+            agent(RULES).rules[1].to_string(),
+            "on untrusted Wi-Fi, apply profile secure"
+        );
+    }
+
+    #[test]
+    fn reads_default_route_ignoring_tunnels() {
+        let route = "\
+Iface\tDestination\tGateway\tFlags\tRefCnt\tUse\tMetric\tMask\tMTU\tWindow\tIRTT
+tun0\t00000000\t0100080A\t0003\t0\t0\t0\t00000000\t0\t0\t0
+wlan0\t00000000\t0101A8C0\t0003\t0\t0\t600\t00000000\t0\t0\t0
+eth0\t00000000\t0100A8C0\t0003\t0\t0\t100\t00000000\t0\t0\t0
+eth0\t0000A8C0\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0
+";
+        assert_eq!(default_interface(route, ""), Some("eth0".to_string()));
+
+        let ipv6 = "\
+00000000000000000000000000000000 00 00000000000000000000000000000000 00 00000000000000000000000000000000 ffffffff 00000001 00000000 00200200       lo
+fe800000000000000000000000000000 40 00000000000000000000000000000000 00 00000000000000000000000000000000 00000100 00000001 00000000 00000001    wlan0
+00000000000000000000000000000000 00 00000000000000000000000000000000 00 fe800000000000000000000000000001 00000258 00000001 00000000 00000003    wlan0
+";
+        let header = route.lines().next().unwrap();
+        assert_eq!(default_interface(header, ipv6), Some("wlan0".to_string()));
+        assert_eq!(default_interface(header, ""), None);
+    }
+
+    #[test]
+    fn parses_nmcli_ssid() {
+        assert_eq!(
+            nmcli_ssid("no:Neighbour\nyes:Cafe\\: Guest\nno:Other\n"),
+            Some("Cafe: Guest".to_string())
+        );
+        assert_eq!(nmcli_ssid("no:Neighbour\n"), None);
+    }
+
+    #[test]
+    fn detects_default_route_messages() {
+        fn message(kind: u16, dst_len: u8, table: u8) -> Vec<u8> {
+            let mut buf = Vec::new();
+            buf.extend(28u32.to_ne_bytes());
+            buf.extend(kind.to_ne_bytes());
+            buf.extend([0; 10]);
+            // AF_INET
+            buf.extend([2, dst_len, 0, 0, table, 0, 0, 0]);
+            buf.extend([0; 4]);
+            buf
+        }
+
+        let subnet = message(24, 24, 254);
+        let policy_table = message(24, 0, 205);
+        let link = message(16, 0, 254);
+        assert!(!default_route_changed(
+            &[subnet.clone(), policy_table.clone(), link].concat()
+        ));
+        assert!(default_route_changed(
+            &[subnet, policy_table, message(25, 0, 254)].concat()
+        ));
+        assert!(!default_route_changed(&[0; 8]));
+
+        // A trailing message whose length is not a multiple of four
+        let mut unpadded = message(24, 24, 254);
+        unpadded[..4].copy_from_slice(&29u32.to_ne_bytes());
+        unpadded.push(0);
+        assert!(!default_route_changed(
+            &[message(16, 0, 254), unpadded].concat()
+        ));
+    }
+}
@@ -1,24 +1,209 @@
-use crate::nordvpn::NordVPN;
-use crate::types::{ConnectionStatus, Country};
+use crate::action::{self, Action};
+use crate::config::Config;
+use crate::doctor::{self, Check};
+use crate::error::BackendError;
+use crate::event::{
+    AppEvent, RETRY_INITIAL, spawn_connect, spawn_daemon_retry, spawn_diagnostics, spawn_leak_test,
+    spawn_recommendations, spawn_reconnect, spawn_status_poller,
+};
+use crate::hooks::{self, HookEvent, HookJob, HookRun, HookRunner};
+use crate::ipc::{self, ControlServer, Request};
+use crate::leak::LeakReport;
+use crate::nordvpn::{ChangeSetError, ConnectCancel, NordVPN};
+use crate::notify::{Notifier, NotifyEvent};
+use crate::profile::Step;
+use crate::servers::{self, ServerCache};
+use crate::snapshot::SettingsSnapshot;
+use crate::state::{HistoryKind, State};
+use crate::types::{
+    self, ChangeSet, ConnectionStatus, Country, DnsChange, DnsSettings, SettingChange, Target,
+    Technology, Toggle, TunnelSettings,
+};
+use crate::watchdog::{Failure, Watchdog};
 use color_eyre::Result;
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent,
+    MouseEventKind,
+};
 use ratatui::DefaultTerminal;
+use ratatui::layout::{Position, Rect};
+use serde_json::{Value, json};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+/// How long to wait for terminal input before handling background events
+const TICK_RATE: Duration = Duration::from_millis(250);
+
+/// Number of status poll samples kept for the activity graph
+const STATUS_SAMPLES: usize = 120;
+/// Finished hooks kept for the hook log
+const HOOK_LOG_LIMIT: usize = 50;
+
+/// Two clicks on the same row within this window count as a double-click
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+/// Whether the nordvpn daemon can currently be reached
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DaemonState {
+    Available,
+    Unavailable {
+        /// The last error returned while trying to reach it
+        error: BackendError,
+        /// Number of background retries so far
+        attempts: u32,
+        /// When the next retry is due
+        next_retry: Instant,
+    },
+}
+
+/// State of the post-connect leak test
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LeakCheck {
+    /// Not run for the current connection
+    Idle,
+    Running,
+    Done(LeakReport),
+    Failed(String),
+}
+
+/// The top-level views, switched with Tab
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum View {
+    Countries,
+    History,
+    Dns,
+    Profiles,
+    Settings,
+    Diagnostics,
+}
+
+impl View {
+    pub const ALL: [View; 6] = [
+        View::Countries,
+        View::History,
+        View::Dns,
+        View::Profiles,
+        View::Settings,
+        View::Diagnostics,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            View::Countries => "Countries",
+            View::History => "History",
+            View::Dns => "DNS",
+            View::Profiles => "Profiles",
+            View::Settings => "Settings",
+            View::Diagnostics => "Diagnostics",
+        }
+    }
+
+    fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|v| *v == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    fn previous(self) -> Self {
+        let index = Self::ALL.iter().position(|v| *v == self).unwrap_or(0);
+        Self::ALL[(index + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+}
+
+/// How far a step of the last applied profile got
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepOutcome {
+    Pending,
+    Done,
+    Failed(String),
+    /// Not run because an earlier step failed
+    Skipped,
+}
+
+/// The profile applied last and the outcome of each of its steps
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileRun {
+    pub name: String,
+    pub steps: Vec<(Step, StepOutcome)>,
+}
+
+/// What the path typed in the Settings view is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsFileAction {
+    Export,
+    Import,
+}
+
+/// Clickable buttons in the status bar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    Disconnect,
+    Refresh,
+}
+
+/// Screen areas recorded while rendering, used to hit-test mouse clicks
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HitAreas {
+    pub tabs: Vec<(View, Rect)>,
+    /// Country list including its border
+    pub country_list: Option<Rect>,
+    pub buttons: Vec<(Button, Rect)>,
+}
+
+/// Command palette input and selection
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Palette {
+    pub query: String,
+    pub selected: usize,
+}
+
+/// Server search input and selected completion
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerSearch {
+    pub query: String,
+    pub selected: usize,
+}
+
+/// Technology picker rows: every technology, then the obfuscation and
+/// post-quantum toggles
+pub const TECHNOLOGY_PICKER_ROWS: usize = Technology::ALL.len() + 2;
+
+/// Technology picker selection
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TechnologyPicker {
+    /// Index into [`Technology::ALL`], continuing with the toggles
+    pub selected: usize,
+    /// Reconnect to the current target once switched
+    pub reconnect: bool,
+    /// Change set waiting for confirmation because it changes other
+    /// settings too
+    pub pending: Option<ChangeSet>,
+}
 
 pub struct App {
     /// Is the application running?
     pub running: bool,
     /// List of all available countries
     pub countries: Vec<Country>,
+    /// Server groups offered for quick connect, e.g. "P2P"
+    pub groups: Vec<String>,
     /// Filtered list of countries based on search
     pub filtered_countries: Vec<Country>,
     /// Currently selected index in the filtered list
     pub selected_index: usize,
     /// Current connection status
     pub status: ConnectionStatus,
+    /// When `status` was last changed by something other than a poll
+    status_updated_at: Instant,
+    /// Whether each recent status poll found the tunnel up, oldest first
+    pub status_samples: VecDeque<bool>,
     /// Search/filter text
     pub filter: String,
     /// Error message to display (if any)
     pub error_message: Option<String>,
+    /// Remediation hint for the current error (if any)
+    pub error_hint: Option<&'static str>,
     /// Success message to display (if any)
     pub success_message: Option<String>,
     /// Whether we're in filter mode
@@ -27,13 +212,172 @@ pub struct App {
     pub scroll_state: ratatui::widgets::ScrollbarState,
     /// List state for scrolling
     pub list_state: ratatui::widgets::ListState,
+    /// Daemon reachability
+    pub daemon: DaemonState,
+    /// Currently shown view
+    pub view: View,
+    /// Results of the last diagnostics run (`None` while running)
+    pub diagnostics: Option<Vec<Check>>,
+    /// Leak test for the current connection
+    pub leak_check: LeakCheck,
+    /// User configuration
+    pub config: Config,
+    /// Favorites and history, saved between runs
+    pub state: State,
+    /// Target of the current connection, cleared when the user disconnects
+    pub last_target: Option<Target>,
+    /// Target of the connect running in the background, if any
+    pub connecting: Option<Target>,
+    /// Id of the last connect started, so a cancelled one is ignored
+    connect_id: u64,
+    /// Stops the connect or watchdog reconnect running in the background
+    connect_cancel: Option<ConnectCancel>,
+    /// Progress printed by the current or last connect, oldest first
+    pub connect_log: Vec<String>,
+    /// Reconnects after unexpected drops
+    pub watchdog: Watchdog,
+    /// Whether the key binding overlay is shown
+    pub show_help: bool,
+    /// Open command palette, if any
+    pub palette: Option<Palette>,
+    /// Open server search, if any
+    pub server_search: Option<ServerSearch>,
+    /// Open technology picker, if any
+    pub technology_picker: Option<TechnologyPicker>,
+    /// Technology in use or configured, as last read from the CLI
+    pub technology: Option<Technology>,
+    /// Technology, obfuscation, post-quantum and Meshnet settings, read
+    /// when the technology picker opens
+    pub tunnel: Option<TunnelSettings>,
+    /// DNS settings as last read from the CLI (`None` until the DNS view
+    /// is opened or if reading them failed)
+    pub dns: Option<DnsSettings>,
+    /// DNS servers being typed, while the DNS form is open
+    pub dns_input: Option<String>,
+    /// DNS change waiting for the user to confirm a conflict warning
+    pub dns_confirm: Option<DnsChange>,
+    /// Selected row in the Profiles view
+    pub profile_selected: usize,
+    /// Last applied profile, shown in the Profiles view
+    pub profile_run: Option<ProfileRun>,
+    /// Current settings as a snapshot (`None` until the Settings view is
+    /// opened or if reading them failed)
+    pub settings: Option<SettingsSnapshot>,
+    /// Settings file imported for comparison
+    pub settings_file: Option<(PathBuf, SettingsSnapshot)>,
+    /// Path being typed, while the Settings view asks for one
+    pub settings_path_input: Option<(SettingsFileAction, String)>,
+    /// Steps to apply the imported file, waiting for the user to confirm
+    pub settings_plan: Option<Vec<Step>>,
+    /// Recommended servers the server search completes from
+    pub servers: ServerCache,
+    /// Whether the recommended servers are being fetched
+    pub servers_refreshing: bool,
+    /// Clickable areas from the last draw
+    pub hit_areas: HitAreas,
+    /// Country list row and time of the last left click
+    last_click: Option<(usize, Instant)>,
+    /// Desktop notifications about connection changes
+    pub notifier: Notifier,
+    /// Runs hook commands, started with the first hook
+    hooks: Option<HookRunner>,
+    /// Hook events recorded since the last update, with their target
+    pending_hooks: Vec<(HookEvent, Option<Target>)>,
+    /// Server of the connection last seen, to notice server changes
+    hook_server: Option<String>,
+    /// Finished hooks, oldest first
+    pub hook_log: VecDeque<HookRun>,
+    /// Control socket, while listening
+    pub control: Option<ControlServer>,
+    /// Control socket clients that asked for status events
+    subscribers: Vec<Sender<String>>,
+    /// Status last sent to the subscribers
+    published: Option<Value>,
+    /// Sender handed to background workers
+    event_tx: Sender<AppEvent>,
+    /// Events coming back from background workers
+    event_rx: Receiver<AppEvent>,
 }
 
 impl App {
     /// Construct a new instance of [`App`].
-    pub fn new() -> Result<Self> {
-        let countries = NordVPN::get_countries()?;
-        let status = NordVPN::get_status().unwrap_or(ConnectionStatus::Disconnected);
+    ///
+    /// If the daemon cannot be reached the app starts in a degraded state
+    /// and retries in the background until the countries can be loaded.
+    pub fn new(config: Config, state: State, servers: ServerCache) -> Self {
+        let (countries, daemon) = match NordVPN::get_countries() {
+            Ok(countries) => (countries, DaemonState::Available),
+            Err(error) => {
+                let daemon = DaemonState::Unavailable {
+                    error,
+                    attempts: 0,
+                    next_retry: Instant::now() + RETRY_INITIAL,
+                };
+                (Vec::new(), daemon)
+            }
+        };
+
+        let status = match daemon {
+            DaemonState::Available => {
+                NordVPN::get_status().unwrap_or(ConnectionStatus::Disconnected)
+            }
+            DaemonState::Unavailable { .. } => ConnectionStatus::Disconnected,
+        };
+
+        let poll_secs = config.status_poll_secs;
+        let mut app = Self::with_data(config, countries, status, state);
+        app.servers = servers;
+        if daemon == DaemonState::Available {
+            app.groups = NordVPN::get_groups().unwrap_or_default();
+        }
+
+        if let DaemonState::Unavailable { .. } = daemon {
+            spawn_daemon_retry(app.event_tx.clone());
+        }
+        app.daemon = daemon;
+
+        if poll_secs > 0 {
+            spawn_status_poller(app.event_tx.clone(), Duration::from_secs(poll_secs));
+        }
+
+        if app.config.desktop_notifications.enabled
+            && let Err(e) = app.notifier.start(None, app.event_tx.clone())
+        {
+            app.error_message = Some(format!("Desktop notifications off: {}", e));
+        }
+
+        if app.config.control.enabled {
+            match app.config.control.socket_path() {
+                Some(path) => app.start_control(&path),
+                None => {
+                    app.error_message =
+                        Some("No location for the control socket; set control.socket".to_string())
+                }
+            }
+        }
+
+        app
+    }
+
+    /// Build an app from already loaded data.
+    ///
+    /// This does not talk to the CLI or start any background workers, so it
+    /// is also how tests construct an app from fixtures.
+    pub fn with_data(
+        config: Config,
+        countries: Vec<Country>,
+        status: ConnectionStatus,
+        state: State,
+    ) -> Self {
+        let (event_tx, event_rx) = mpsc::channel();
+
+        // Let the watchdog guard a connection made before the app started
+        let last_target = match &status {
+            ConnectionStatus::Connected { country, .. } => {
+                Some(Target::Country(country.replace(' ', "_")))
+            }
+            _ => None,
+        };
 
         let filtered_countries = countries.clone();
 
@@ -43,32 +387,821 @@ impl App {
         let mut list_state = ratatui::widgets::ListState::default();
         list_state.select(Some(0));
 
-        Ok(Self {
+        Self {
             running: true,
             countries,
+            groups: Vec::new(),
             filtered_countries,
             selected_index: 0,
             status,
+            status_updated_at: Instant::now(),
+            status_samples: VecDeque::new(),
             filter: String::new(),
             error_message: None,
+            error_hint: None,
             success_message: None,
             filter_mode: false,
             scroll_state,
             list_state,
-        })
+            daemon: DaemonState::Available,
+            view: View::Countries,
+            diagnostics: None,
+            leak_check: LeakCheck::Idle,
+            watchdog: Watchdog::new(config.watchdog.clone()),
+            notifier: Notifier::new(config.desktop_notifications.clone()),
+            hooks: None,
+            pending_hooks: Vec::new(),
+            hook_server: None,
+            hook_log: VecDeque::new(),
+            config,
+            state,
+            last_target,
+            connecting: None,
+            connect_id: 0,
+            connect_cancel: None,
+            connect_log: Vec::new(),
+            show_help: false,
+            palette: None,
+            server_search: None,
+            technology_picker: None,
+            technology: None,
+            tunnel: None,
+            dns: None,
+            dns_input: None,
+            dns_confirm: None,
+            profile_selected: 0,
+            profile_run: None,
+            settings: None,
+            settings_file: None,
+            settings_path_input: None,
+            settings_plan: None,
+            servers: ServerCache::default(),
+            servers_refreshing: false,
+            hit_areas: HitAreas::default(),
+            last_click: None,
+            control: None,
+            subscribers: Vec::new(),
+            published: None,
+            event_tx,
+            event_rx,
+        }
     }
 
     /// Run the application's main loop.
     pub fn run(mut self, mut terminal: DefaultTerminal) -> Result<()> {
         while self.running {
-            terminal.draw(|frame| crate::ui::render(&mut self, frame))?;
+            terminal.draw(|frame| crate::ui::render(&mut self, frame, Instant::now()))?;
             self.handle_crossterm_events()?;
+            self.update();
         }
         Ok(())
     }
 
+    /// Apply background results and do periodic work, once per loop
+    pub fn update(&mut self) {
+        self.handle_app_events();
+        self.on_tick();
+        self.run_hooks();
+        self.publish_status();
+    }
+
+    /// Periodic work that does not depend on input
+    fn on_tick(&mut self) {
+        if let Some((target, attempt, id)) = self.watchdog.poll(Instant::now()) {
+            let detail = format!("attempt {}/{}", attempt, self.watchdog.max_attempts());
+            self.success_message = Some(format!("Reconnecting to {} ({})...", target, detail));
+            self.record(HistoryKind::ReconnectAttempt, Some(target.clone()), detail);
+            let pre_connect = self.pre_connect_hooks(&target);
+            self.set_status(ConnectionStatus::Connecting);
+            self.connect_log.clear();
+            let cancel = ConnectCancel::default();
+            self.connect_cancel = Some(cancel.clone());
+            spawn_reconnect(
+                self.event_tx.clone(),
+                target,
+                attempt,
+                id,
+                cancel,
+                pre_connect,
+            );
+        }
+    }
+
+    /// Drain events sent by background workers
+    fn handle_app_events(&mut self) {
+        while let Ok(event) = self.event_rx.try_recv() {
+            self.on_app_event(event);
+        }
+    }
+
+    /// Handle a single background event
+    fn on_app_event(&mut self, event: AppEvent) {
+        match event {
+            AppEvent::DaemonRetry {
+                attempt,
+                result,
+                next_retry,
+            } => match result {
+                Ok(ready) => {
+                    self.countries = ready.countries;
+                    self.groups = ready.groups;
+                    self.update_filter();
+                    self.daemon = DaemonState::Available;
+                    self.set_status(ready.status);
+                    self.success_message = Some("NordVPN daemon is now available".to_string());
+                }
+                Err(error) => {
+                    self.daemon = DaemonState::Unavailable {
+                        error,
+                        attempts: attempt,
+                        next_retry: Instant::now() + next_retry,
+                    };
+                }
+            },
+            AppEvent::Diagnostics(checks) => self.diagnostics = Some(checks),
+            AppEvent::LeakTest(result) => {
+                // Ignore results that arrive after the connection went away
+                if self.leak_check == LeakCheck::Running {
+                    self.leak_check = match result {
+                        Ok(report) => LeakCheck::Done(report),
+                        Err(e) => {
+                            self.error_message = Some(format!("Leak test failed: {}", e));
+                            LeakCheck::Failed(e)
+                        }
+                    };
+                }
+            }
+            AppEvent::StatusPolled { sampled_at, result } => {
+                // Polling errors are transient; the next poll will tell
+                if let Ok(status) = result {
+                    self.status_samples
+                        .push_back(matches!(status, ConnectionStatus::Connected { .. }));
+                    if self.status_samples.len() > STATUS_SAMPLES {
+                        self.status_samples.pop_front();
+                    }
+                    self.on_status_polled(sampled_at, status);
+                }
+            }
+            AppEvent::ConnectProgress(line) => self.connect_log.push(line),
+            AppEvent::Recommendations(result) => {
+                self.servers_refreshing = false;
+                match result {
+                    Ok(servers) => {
+                        self.servers.update(servers);
+                        if let Err(e) = self.servers.save() {
+                            self.error_message = Some(format!("Failed to cache servers: {}", e));
+                        }
+                    }
+                    // Completions fall back to the last known servers
+                    Err(e) => self.error_message = Some(e),
+                }
+            }
+            AppEvent::Connected { target, id, result } => {
+                // The user disconnected meanwhile
+                if self.connecting.is_some() && id == self.connect_id {
+                    self.on_connect_result(target, result);
+                }
+            }
+            AppEvent::Reconnect {
+                target,
+                attempt,
+                id,
+                result,
+            } => {
+                // The user took over since; the connection is theirs now
+                if self.watchdog.is_in_flight(id) {
+                    self.on_reconnect_result(target, attempt, result);
+                }
+            }
+            AppEvent::Control { request, reply } => self.on_control(request, reply),
+            AppEvent::HookFinished(run) => {
+                if !run.succeeded() {
+                    self.error_message = Some(format!(
+                        "{} hook `{}` {}",
+                        run.event, run.command, run.outcome
+                    ));
+                }
+                self.hook_log.push_back(run);
+                if self.hook_log.len() > HOOK_LOG_LIMIT {
+                    self.hook_log.pop_front();
+                }
+            }
+            AppEvent::NotificationFailed(e) => {
+                self.error_message = Some(format!("Desktop notification failed: {}", e))
+            }
+        }
+    }
+
+    /// Listen for control socket clients
+    pub fn start_control(&mut self, path: &std::path::Path) {
+        match ipc::listen(path, self.event_tx.clone()) {
+            Ok(server) => self.control = Some(server),
+            Err(e) => self.error_message = Some(format!("Control socket not started: {}", e)),
+        }
+    }
+
+    /// Answer a control socket request
+    fn on_control(&mut self, request: std::result::Result<Request, String>, reply: Sender<String>) {
+        let request = match request {
+            Ok(request) => request,
+            Err(e) => {
+                let _ = reply.send(ipc::reply(Err(e)));
+                return;
+            }
+        };
+        let result = match request {
+            Request::Status => Ok(json!({ "status": self.status_json() })),
+            Request::Subscribe => {
+                // Start the stream with the current status, which the
+                // other subscribers have then seen too
+                self.publish_status();
+                let _ = reply.send(ipc::reply(Ok(json!({}))));
+                let _ = reply.send(ipc::status_event(&self.status_json()));
+                self.published = Some(self.status_json());
+                self.subscribers.push(reply);
+                return;
+            }
+            Request::Connect { target } => match self.connect_blocked() {
+                Some(message) => Err(message),
+                None => {
+                    let target = target.unwrap_or(Target::Quick);
+                    self.connect(target.clone());
+                    Ok(json!({ "connecting": target }))
+                }
+            },
+            Request::Disconnect => match self.try_disconnect() {
+                Ok(()) => Ok(json!({})),
+                Err(e) => {
+                    let message = e.to_string();
+                    self.report_error("Failed to disconnect", e);
+                    Err(message)
+                }
+            },
+            Request::Favorites => Ok(json!({ "favorites": self.state.favorites })),
+            Request::History { limit } => {
+                let history = &self.state.history;
+                let skip = history.len().saturating_sub(limit.unwrap_or(history.len()));
+                Ok(json!({ "history": history[skip..] }))
+            }
+        };
+        let _ = reply.send(ipc::reply(result));
+    }
+
+    /// The current status as sent to control socket clients
+    fn status_json(&self) -> Value {
+        let target = self.connecting.as_ref().or(self.last_target.as_ref());
+        ipc::status_json(&self.status, target)
+    }
+
+    /// Send the status to subscribers if it changed since last time
+    fn publish_status(&mut self) {
+        if self.subscribers.is_empty() {
+            return;
+        }
+        let status = self.status_json();
+        if self.published.as_ref() == Some(&status) {
+            return;
+        }
+        let line = ipc::status_event(&status);
+        // Clients that went away are dropped
+        self.subscribers
+            .retain(|subscriber| subscriber.send(line.clone()).is_ok());
+        self.published = Some(status);
+    }
+
+    /// Apply a polled status, detecting drops the user did not ask for
+    fn on_status_polled(&mut self, sampled_at: Instant, status: ConnectionStatus) {
+        if self.watchdog.is_active()
+            || self.connecting.is_some()
+            || sampled_at < self.status_updated_at
+        {
+            // The watchdog or a running connect owns the status, and a poll
+            // started before a local change is already stale
+            return;
+        }
+
+        let was_connected = matches!(self.status, ConnectionStatus::Connected { .. });
+        let dropped = was_connected && status == ConnectionStatus::Disconnected;
+        self.status = status;
+
+        if !dropped {
+            return;
+        }
+        self.leak_check = LeakCheck::Idle;
+        let Some(target) = self.last_target.clone() else {
+            return;
+        };
+
+        self.record(HistoryKind::Dropped, Some(target.clone()), "");
+        if self.watchdog.enabled {
+            self.watchdog.on_drop(target.clone(), Instant::now());
+            self.error_message = Some(format!(
+                "Connection to {} dropped, watchdog will reconnect",
+                target
+            ));
+        } else {
+            self.error_message = Some(format!("Connection to {} dropped", target));
+        }
+    }
+
+    /// Handle the outcome of a watchdog reconnect attempt
+    fn on_reconnect_result(
+        &mut self,
+        target: Target,
+        attempt: u32,
+        result: std::result::Result<ConnectionStatus, BackendError>,
+    ) {
+        match result {
+            Ok(status) => {
+                self.watchdog.on_success();
+                self.last_target = Some(target.clone());
+                self.record(
+                    HistoryKind::Reconnected,
+                    Some(target.clone()),
+                    format!("attempt {}", attempt),
+                );
+                self.set_status(status);
+                self.success_message = Some(format!("Reconnected to {}", target));
+                if self.config.leak_test.enabled {
+                    self.start_leak_test();
+                }
+            }
+            Err(e) => {
+                self.set_status(ConnectionStatus::Disconnected);
+                self.record(
+                    HistoryKind::ReconnectFailed,
+                    Some(target.clone()),
+                    e.to_string(),
+                );
+                match self
+                    .watchdog
+                    .on_failure(Instant::now(), &self.state.favorites)
+                {
+                    None => {}
+                    Some(Failure::Retry { attempt, delay }) => {
+                        self.error_message = Some(format!(
+                            "Reconnect to {} failed, retrying in {}s (attempt {}/{})",
+                            target,
+                            delay.as_secs(),
+                            attempt,
+                            self.watchdog.max_attempts()
+                        ));
+                    }
+                    Some(Failure::Fallback(next)) => {
+                        self.error_message = Some(format!(
+                            "Giving up on {}, falling back to favorite {}",
+                            target, next
+                        ));
+                    }
+                    Some(Failure::GaveUp) => {
+                        self.last_target = None;
+                        self.record(HistoryKind::GaveUp, Some(target.clone()), "");
+                        self.report_error("Watchdog gave up reconnecting", e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Hook commands for `event`, told about the current status
+    fn hook_jobs(
+        &self,
+        event: HookEvent,
+        target: Option<&Target>,
+        previous_server: Option<&str>,
+    ) -> Vec<HookJob> {
+        let env = hooks::env(event, &self.status, target, previous_server);
+        let timeout = Duration::from_secs(self.config.hooks.timeout_secs);
+        event
+            .commands(&self.config.hooks)
+            .iter()
+            .map(|command| HookJob {
+                event,
+                command: command.clone(),
+                env: env.clone(),
+                timeout,
+            })
+            .collect()
+    }
+
+    fn hook_runner(&mut self) -> HookRunner {
+        self.hooks
+            .get_or_insert_with(|| HookRunner::spawn(self.event_tx.clone()))
+            .clone()
+    }
+
+    /// Pre-connect hooks for the connect thread to run and wait for
+    fn pre_connect_hooks(&mut self, target: &Target) -> Option<(HookRunner, Vec<HookJob>)> {
+        let jobs = self.hook_jobs(HookEvent::PreConnect, Some(target), None);
+        if jobs.is_empty() {
+            return None;
+        }
+        Some((self.hook_runner(), jobs))
+    }
+
+    /// Queue the hooks for what was recorded since the last update and for
+    /// a server change, now that the status is up to date
+    fn run_hooks(&mut self) {
+        let mut jobs = Vec::new();
+        for (event, target) in std::mem::take(&mut self.pending_hooks) {
+            jobs.extend(self.hook_jobs(event, target.as_ref(), None));
+        }
+        match &self.status {
+            ConnectionStatus::Connected {
+                server: Some(server),
+                ..
+            } => {
+                if let Some(previous) = &self.hook_server
+                    && previous != server
+                {
+                    jobs.extend(self.hook_jobs(
+                        HookEvent::ServerChange,
+                        self.last_target.as_ref(),
+                        Some(previous),
+                    ));
+                }
+                self.hook_server = Some(server.clone());
+            }
+            // Connecting elsewhere while connected still counts as a change
+            ConnectionStatus::Connecting => {}
+            _ => self.hook_server = None,
+        }
+        if !jobs.is_empty() {
+            self.hook_runner().queue(jobs);
+        }
+    }
+
+    /// Replace the status after a change made by this app
+    fn set_status(&mut self, status: ConnectionStatus) {
+        self.status = status;
+        self.status_updated_at = Instant::now();
+    }
+
+    /// Add a history entry and persist it, notifying the desktop about it
+    fn record(&mut self, kind: HistoryKind, target: Option<Target>, detail: impl Into<String>) {
+        let detail = detail.into();
+        if let Some(event) = NotifyEvent::from_history(kind) {
+            let body = match (&target, detail.as_str()) {
+                (Some(target), "") => target.to_string(),
+                (Some(target), detail) => format!("{}: {}", target, detail),
+                (None, detail) => detail.to_string(),
+            };
+            self.notifier.notify(event, body);
+        }
+        let hook = match kind {
+            HistoryKind::Connected | HistoryKind::Reconnected => Some(HookEvent::PostConnect),
+            HistoryKind::Disconnected => Some(HookEvent::Disconnect),
+            HistoryKind::Dropped => Some(HookEvent::UnexpectedDrop),
+            _ => None,
+        };
+        if let Some(event) = hook {
+            self.pending_hooks.push((event, target.clone()));
+        }
+        self.state.record(kind, target, detail);
+        self.save_state();
+    }
+
+    /// Persist favorites and history
+    fn save_state(&mut self) {
+        if let Err(e) = self.state.save() {
+            self.error_message = Some(format!("Failed to save state: {}", e));
+        }
+    }
+
+    /// Arm or disarm the reconnect watchdog
+    fn toggle_watchdog(&mut self) {
+        self.watchdog.enabled = !self.watchdog.enabled;
+        if self.watchdog.enabled {
+            self.success_message = Some("Watchdog enabled".to_string());
+        } else {
+            self.watchdog.cancel();
+            self.success_message = Some("Watchdog disabled".to_string());
+        }
+    }
+
+    /// Add or remove the selected country from the favorites
+    fn toggle_favorite(&mut self) {
+        let Some(country) = self.filtered_countries.get(self.selected_index) else {
+            return;
+        };
+        self.toggle_favorite_target(Target::Country(country.name.clone()));
+    }
+
+    fn toggle_favorite_target(&mut self, target: Target) {
+        let message = if self.state.toggle_favorite(target.clone()) {
+            format!("Added {} to favorites", target)
+        } else {
+            format!("Removed {} from favorites", target)
+        };
+        self.success_message = Some(message);
+        self.save_state();
+    }
+
+    /// Switch to the next view, running diagnostics when they are first shown
+    fn next_view(&mut self) {
+        self.show_view(self.view.next());
+    }
+
+    fn previous_view(&mut self) {
+        self.show_view(self.view.previous());
+    }
+
+    fn show_view(&mut self, view: View) {
+        self.view = view;
+        if self.view == View::Diagnostics && self.diagnostics.is_none() {
+            self.run_diagnostics();
+        }
+        if self.view == View::Dns && self.dns.is_none() {
+            self.load_dns();
+        }
+        if self.view == View::Settings && self.settings.is_none() {
+            self.load_settings();
+        }
+    }
+
+    /// Re-read all settings from the CLI
+    fn load_settings(&mut self) {
+        match NordVPN::get_settings() {
+            Ok(settings) => self.settings = Some(SettingsSnapshot::from_settings(&settings)),
+            Err(e) => {
+                self.settings = None;
+                self.report_error("Failed to read settings", e);
+            }
+        }
+    }
+
+    /// Ask for the file to export to or import from, starting from the last
+    /// one used
+    fn ask_settings_path(&mut self, action: SettingsFileAction) {
+        let path = self
+            .settings_file
+            .as_ref()
+            .map(|(path, _)| path.clone())
+            .or_else(|| Config::path().map(|path| path.with_file_name("settings.toml")))
+            .map(|path| path.display().to_string())
+            .unwrap_or_default();
+        self.settings_path_input = Some((action, path));
+    }
+
+    /// Handle keys while the Settings view asks for a path
+    fn on_settings_path_key(&mut self, key: KeyEvent) {
+        let Some((action, input)) = self.settings_path_input.as_mut() else {
+            return;
+        };
+        match (key.modifiers, key.code) {
+            (_, KeyCode::Esc) | (KeyModifiers::CONTROL, KeyCode::Char('c')) => {
+                self.settings_path_input = None;
+            }
+            (_, KeyCode::Backspace) => {
+                input.pop();
+            }
+            (KeyModifiers::CONTROL, KeyCode::Char('u')) => input.clear(),
+            (_, KeyCode::Enter) if !input.trim().is_empty() => {
+                let action = *action;
+                let path = PathBuf::from(input.trim());
+                self.settings_path_input = None;
+                match action {
+                    SettingsFileAction::Export => self.export_settings(&path),
+                    SettingsFileAction::Import => self.import_settings(path),
+                }
+            }
+            (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => input.push(c),
+            _ => {}
+        }
+    }
+
+    fn export_settings(&mut self, path: &std::path::Path) {
+        self.load_settings();
+        let Some(settings) = &self.settings else {
+            return;
+        };
+        match settings.save(path) {
+            Ok(()) => self.success_message = Some(format!("Saved settings to {}", path.display())),
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
+    /// Load a settings file to compare against the current settings
+    fn import_settings(&mut self, path: PathBuf) {
+        match SettingsSnapshot::load(&path) {
+            Ok(snapshot) => {
+                if self.settings.is_none() {
+                    self.load_settings();
+                }
+                let differences = self
+                    .settings
+                    .as_ref()
+                    .map(|current| snapshot.diff(current).len());
+                self.settings_file = Some((path.clone(), snapshot));
+                // Without the current settings there is nothing to compare
+                // with; loading them reported why
+                let Some(differences) = differences else {
+                    return;
+                };
+                self.success_message = Some(match differences {
+                    0 => format!("Settings match {}", path.display()),
+                    n => format!("{} settings differ, a: apply", n),
+                });
+            }
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
+    /// Plan applying the imported file and ask before doing it
+    fn request_settings_import(&mut self) {
+        let Some((_, wanted)) = &self.settings_file else {
+            self.error_message = Some("Import a settings file first (i)".to_string());
+            return;
+        };
+        let plan = NordVPN::get_settings()
+            .map_err(|e| e.to_string())
+            .and_then(|settings| wanted.plan(&settings));
+        match plan {
+            Ok(steps) if steps.is_empty() => {
+                self.success_message = Some("Settings already match".to_string());
+            }
+            Ok(steps) => self.settings_plan = Some(steps),
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
+    /// Apply confirmed steps, stopping at the first failure
+    fn apply_settings_plan(&mut self, steps: Vec<Step>) {
+        let mut steps: Vec<(Step, StepOutcome)> = steps
+            .into_iter()
+            .map(|step| (step, StepOutcome::Pending))
+            .collect();
+        let failed = run_steps(&mut steps);
+        self.after_settings_changed();
+        self.load_settings();
+        match failed {
+            Some((step, e)) => self.report_error(&format!("Failed to {}", step), e),
+            None => {
+                self.success_message = Some(format!("Applied {} changes", steps.len()));
+            }
+        }
+    }
+
+    /// Drop cached settings so views re-read what the daemon ended up with
+    fn after_settings_changed(&mut self) {
+        self.dns = None;
+        self.tunnel = None;
+        self.settings = None;
+        if let Ok(technology) = NordVPN::get_technology() {
+            self.technology = technology;
+        }
+    }
+
+    /// Re-read the DNS settings from the CLI
+    fn load_dns(&mut self) {
+        match NordVPN::get_dns() {
+            Ok(dns) => self.dns = Some(dns),
+            Err(e) => {
+                self.dns = None;
+                self.report_error("Failed to read DNS settings", e);
+            }
+        }
+    }
+
+    /// Open the DNS form with the current servers filled in
+    fn edit_dns(&mut self) {
+        let servers = self
+            .dns
+            .as_ref()
+            .map(|dns| types::join_addresses(&dns.servers))
+            .unwrap_or_default();
+        self.dns_input = Some(servers);
+    }
+
+    /// Handle keys while the DNS form is open
+    fn on_dns_input_key(&mut self, key: KeyEvent) {
+        let Some(input) = self.dns_input.as_mut() else {
+            return;
+        };
+        match (key.modifiers, key.code) {
+            (_, KeyCode::Esc) | (KeyModifiers::CONTROL, KeyCode::Char('c')) => {
+                self.dns_input = None;
+            }
+            (_, KeyCode::Backspace) => {
+                input.pop();
+            }
+            (_, KeyCode::Enter) => match types::parse_dns_servers(input) {
+                Ok(servers) => {
+                    self.dns_input = None;
+                    self.request_dns_change(DnsChange::Servers(servers));
+                }
+                Err(e) => self.error_message = Some(e),
+            },
+            (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => input.push(c),
+            _ => {}
+        }
+    }
+
+    /// Apply a DNS change, asking first if it undoes another setting
+    fn request_dns_change(&mut self, change: DnsChange) {
+        if change == DnsChange::Reset && self.dns.as_ref().is_some_and(|dns| dns.servers.is_empty())
+        {
+            self.success_message = Some("Already using NordVPN's DNS".to_string());
+            return;
+        }
+        if self
+            .dns
+            .as_ref()
+            .and_then(|dns| dns.conflict(&change))
+            .is_some()
+        {
+            self.dns_confirm = Some(change);
+            return;
+        }
+        self.apply_dns_change(change);
+    }
+
+    fn apply_dns_change(&mut self, change: DnsChange) {
+        let (result, context, done) = match &change {
+            DnsChange::Servers(servers) => (
+                NordVPN::set_dns(servers),
+                "Failed to set DNS",
+                format!("DNS set to {}", types::join_addresses(servers)),
+            ),
+            DnsChange::Reset => (
+                NordVPN::reset_dns(),
+                "Failed to reset DNS",
+                "DNS reset to NordVPN's".to_string(),
+            ),
+            DnsChange::ThreatProtection(enabled) => (
+                NordVPN::set_threat_protection(*enabled),
+                "Failed to set Threat Protection Lite",
+                format!(
+                    "Threat Protection Lite {}",
+                    if *enabled { "enabled" } else { "disabled" }
+                ),
+            ),
+        };
+        // Show what the daemon actually did, including settings it changed
+        // along the way
+        self.load_dns();
+        match result {
+            Ok(()) => self.success_message = Some(done),
+            Err(e) => self.report_error(context, e),
+        }
+    }
+
+    /// Toggle Threat Protection Lite from its last known state
+    fn toggle_threat_protection(&mut self) {
+        match self.dns.as_ref().and_then(|dns| dns.threat_protection) {
+            Some(enabled) => self.request_dns_change(DnsChange::ThreatProtection(!enabled)),
+            None => {
+                self.error_message =
+                    Some("Threat Protection Lite is not available in this version".to_string())
+            }
+        }
+    }
+
+    /// Flip an on/off setting, reading its current state from the daemon
+    fn toggle_setting(&mut self, toggle: Toggle) {
+        let enabled = match NordVPN::get_settings() {
+            Ok(settings) => settings.is_enabled(toggle.setting()),
+            Err(e) => return self.report_error("Failed to get settings", e),
+        };
+        let Some(enabled) = enabled else {
+            self.error_message = Some(format!("The daemon does not report its {}", toggle));
+            return;
+        };
+        match NordVPN::set_toggle(toggle, !enabled) {
+            Ok(()) => {
+                self.success_message = Some(format!(
+                    "{} {}",
+                    toggle.setting(),
+                    if enabled { "disabled" } else { "enabled" }
+                ))
+            }
+            Err(e) => self.report_error("Failed to change setting", e),
+        }
+        if self.settings.is_some() {
+            self.load_settings();
+        }
+    }
+
+    /// Re-run the diagnostics checks in the background
+    fn run_diagnostics(&mut self) {
+        self.diagnostics = None;
+        spawn_diagnostics(self.event_tx.clone(), self.config.daemon_socket.clone());
+    }
+
+    /// Copy the diagnostics report to the clipboard
+    fn copy_diagnostics(&mut self) {
+        let Some(checks) = &self.diagnostics else {
+            self.error_message = Some("Diagnostics are still running".to_string());
+            return;
+        };
+        match crate::clipboard::copy(&doctor::report(checks)) {
+            Ok(()) => self.success_message = Some("Report copied to clipboard".to_string()),
+            Err(e) => self.error_message = Some(format!("Failed to copy report: {}", e)),
+        }
+    }
+
     /// Update the filtered countries list based on current filter
-    fn update_filter(&mut self) {
+    pub fn update_filter(&mut self) {
         if self.filter.is_empty() {
             self.filtered_countries = self.countries.clone();
         } else {
@@ -99,13 +1232,22 @@ impl App {
     pub fn refresh_status(&mut self) {
         match NordVPN::get_status() {
             Ok(status) => {
-                self.status = status;
+                if !matches!(status, ConnectionStatus::Connected { .. }) {
+                    self.leak_check = LeakCheck::Idle;
+                }
+                self.set_status(status);
                 self.success_message = Some("Status refreshed".to_string());
             }
-            Err(e) => self.error_message = Some(format!("Failed to get status: {}", e)),
+            Err(e) => self.report_error("Failed to get status", e),
         }
     }
 
+    /// Show a backend error together with its remediation hint
+    fn report_error(&mut self, context: &str, err: BackendError) {
+        self.error_hint = err.hint();
+        self.error_message = Some(format!("{}: {}", context, err));
+    }
+
     /// Connect to the selected country
     fn connect_selected(&mut self) {
         if self.filtered_countries.is_empty() {
@@ -113,32 +1255,465 @@ impl App {
             return;
         }
 
-        let country = &self.filtered_countries[self.selected_index];
-        self.status = ConnectionStatus::Connecting;
+        let target = Target::Country(self.filtered_countries[self.selected_index].name.clone());
+        self.connect(target);
+    }
 
-        match NordVPN::connect(&country.name) {
-            Ok(_) => {
-                self.success_message = Some(format!("Connecting to {}...", country.display_name));
-                // Refresh status after a moment
-                self.refresh_status();
+    /// Start connecting to a target in the background
+    fn connect(&mut self, target: Target) {
+        if let Some(message) = self.connect_blocked() {
+            self.error_message = Some(message);
+            return;
+        }
+        // Before the status changes, so the hooks see what is replaced
+        let pre_connect = self.pre_connect_hooks(&target);
+        self.set_status(ConnectionStatus::Connecting);
+        self.watchdog.cancel();
+        self.connect_log.clear();
+        self.success_message = Some(format!("Connecting to {}...", target));
+        self.connecting = Some(target.clone());
+        self.connect_id += 1;
+        let cancel = ConnectCancel::default();
+        self.connect_cancel = Some(cancel.clone());
+        spawn_connect(
+            self.event_tx.clone(),
+            target,
+            self.connect_id,
+            cancel,
+            pre_connect,
+        );
+    }
+
+    /// Why a new connect cannot start now, if it cannot
+    fn connect_blocked(&self) -> Option<String> {
+        if let Some(pending) = &self.connecting {
+            Some(format!("Already connecting to {}", pending))
+        } else {
+            self.watchdog
+                .reconnecting()
+                .map(|target| format!("Already reconnecting to {}", target))
+        }
+    }
+
+    /// Handle the outcome of a connect started by the user
+    fn on_connect_result(
+        &mut self,
+        target: Target,
+        result: std::result::Result<ConnectionStatus, BackendError>,
+    ) {
+        self.connecting = None;
+        self.finish_profile_connect(
+            &target,
+            match &result {
+                Ok(_) => StepOutcome::Done,
+                Err(e) => StepOutcome::Failed(e.to_string()),
+            },
+        );
+        match result {
+            Ok(status) => {
+                self.success_message = Some(match (&target, &status) {
+                    // Say which server the daemon picked
+                    (
+                        Target::Quick | Target::Group(_),
+                        ConnectionStatus::Connected {
+                            country,
+                            server: Some(server),
+                            ..
+                        },
+                    ) => format!("Connected to {}: {} in {}", target, server, country),
+                    _ => format!("Connected to {}", target),
+                });
+                self.last_target = Some(target.clone());
+                self.record(HistoryKind::Connected, Some(target), "");
+                self.set_status(status);
+                if self.config.leak_test.enabled {
+                    self.start_leak_test();
+                }
             }
             Err(e) => {
-                self.error_message = Some(format!("Failed to connect: {}", e));
-                self.status = ConnectionStatus::Disconnected;
+                self.record(HistoryKind::ConnectFailed, Some(target), e.to_string());
+                self.report_error("Failed to connect", e);
+                self.set_status(ConnectionStatus::Disconnected);
             }
         }
     }
 
+    /// Record the outcome of the profile run's connect to `target`, if pending
+    fn finish_profile_connect(&mut self, target: &Target, result: StepOutcome) {
+        if let Some(run) = self.profile_run.as_mut()
+            && let Some((Step::Connect(pending), outcome)) = run.steps.last_mut()
+            && *outcome == StepOutcome::Pending
+            && pending == target
+        {
+            *outcome = result;
+        }
+    }
+
+    /// Verify the public IP and resolvers of the current connection
+    fn start_leak_test(&mut self) {
+        let ConnectionStatus::Connected { ip, .. } = &self.status else {
+            self.error_message = Some("Not connected".to_string());
+            return;
+        };
+        self.leak_check = LeakCheck::Running;
+        spawn_leak_test(
+            self.event_tx.clone(),
+            self.config.leak_test.clone(),
+            ip.clone(),
+        );
+    }
+
     /// Disconnect from VPN
     fn disconnect(&mut self) {
-        match NordVPN::disconnect() {
-            Ok(_) => {
-                self.success_message = Some("Disconnected".to_string());
-                self.status = ConnectionStatus::Disconnected;
+        if let Err(e) = self.try_disconnect() {
+            self.report_error("Failed to disconnect", e);
+        }
+    }
+
+    fn try_disconnect(&mut self) -> std::result::Result<(), BackendError> {
+        self.watchdog.cancel();
+        // Stop a connect still running first, or it could bring the tunnel
+        // back up after the disconnect
+        if let Some(cancel) = self.connect_cancel.take() {
+            cancel.cancel();
+        }
+        NordVPN::disconnect()?;
+        // Its result is ignored when it arrives
+        if let Some(target) = self.connecting.take() {
+            self.finish_profile_connect(&target, StepOutcome::Failed("Cancelled".to_string()));
+        }
+        self.success_message = Some("Disconnected".to_string());
+        self.set_status(ConnectionStatus::Disconnected);
+        self.leak_check = LeakCheck::Idle;
+        let target = self.last_target.take();
+        self.record(HistoryKind::Disconnected, target, "");
+        Ok(())
+    }
+
+    /// Open the technology picker on the technology currently in use
+    fn open_technology_picker(&mut self) {
+        match NordVPN::get_technology() {
+            Ok(technology) => self.technology = technology,
+            Err(e) => self.report_error("Failed to get technology", e),
+        }
+        match NordVPN::get_tunnel() {
+            Ok(tunnel) => self.tunnel = Some(tunnel),
+            Err(e) => {
+                self.tunnel = None;
+                self.report_error("Failed to read settings", e);
             }
+        }
+        let connected = matches!(self.status, ConnectionStatus::Connected { .. });
+        self.technology_picker = Some(TechnologyPicker {
+            selected: self
+                .technology
+                .and_then(|current| Technology::ALL.iter().position(|t| *t == current))
+                .unwrap_or(0),
+            reconnect: connected && self.last_target.is_some(),
+            pending: None,
+        });
+    }
+
+    /// Handle keys while the technology picker is open
+    fn on_technology_key(&mut self, key: KeyEvent) {
+        let Some(picker) = self.technology_picker.as_mut() else {
+            return;
+        };
+        if let Some(plan) = picker.pending.take() {
+            // Anything but confirming goes back to the picker
+            if matches!(key.code, KeyCode::Enter | KeyCode::Char('y')) {
+                let reconnect = picker.reconnect;
+                self.technology_picker = None;
+                self.apply_settings(plan, reconnect);
+            }
+            return;
+        }
+        match (key.modifiers, key.code) {
+            (_, KeyCode::Esc) | (KeyModifiers::CONTROL, KeyCode::Char('c')) => {
+                self.technology_picker = None;
+            }
+            (_, KeyCode::Up | KeyCode::Char('k')) => {
+                picker.selected = picker.selected.saturating_sub(1);
+            }
+            (_, KeyCode::Down | KeyCode::Char('j'))
+                if picker.selected + 1 < TECHNOLOGY_PICKER_ROWS =>
+            {
+                picker.selected += 1;
+            }
+            (_, KeyCode::Char('r')) => picker.reconnect = !picker.reconnect,
+            (_, KeyCode::Enter | KeyCode::Char(' ')) => {
+                let tunnel = self.tunnel.unwrap_or_default();
+                let change = match picker.selected.checked_sub(Technology::ALL.len()) {
+                    None => SettingChange::Technology(Technology::ALL[picker.selected]),
+                    Some(0) => SettingChange::Obfuscate(!tunnel.obfuscate.unwrap_or(false)),
+                    Some(_) => SettingChange::PostQuantum(!tunnel.post_quantum.unwrap_or(false)),
+                };
+                self.request_setting(change);
+            }
+            _ => {}
+        }
+    }
+
+    /// Name of the profile selected in the Profiles view
+    pub fn selected_profile(&self) -> Option<&String> {
+        self.config.profiles.keys().nth(self.profile_selected)
+    }
+
+    /// Apply a profile's settings one by one, then connect to its target.
+    ///
+    /// Settings are applied in the foreground like other setting changes;
+    /// the first failure stops the run and the connect is skipped.
+    fn apply_profile(&mut self, name: &str) {
+        let Some(profile) = self.config.profiles.get(name) else {
+            self.error_message = Some(format!("No profile named {}", name));
+            return;
+        };
+        if let Some(message) = self.connect_blocked() {
+            self.error_message = Some(message);
+            return;
+        }
+        let settings = match NordVPN::get_settings() {
+            Ok(settings) => settings,
+            Err(e) => {
+                self.report_error("Failed to read settings", e);
+                return;
+            }
+        };
+        let steps = match profile.plan(&settings) {
+            Ok(steps) => steps,
             Err(e) => {
-                self.error_message = Some(format!("Failed to disconnect: {}", e));
+                self.error_message = Some(format!("Profile {}: {}", name, e));
+                return;
             }
+        };
+
+        let mut run = ProfileRun {
+            name: name.to_string(),
+            steps: steps
+                .into_iter()
+                .map(|step| (step, StepOutcome::Pending))
+                .collect(),
+        };
+        let failed = run_steps(&mut run.steps);
+        let target = match run.steps.last() {
+            Some((Step::Connect(target), StepOutcome::Pending)) => Some(target.clone()),
+            _ => None,
+        };
+        self.profile_run = Some(run);
+        self.after_settings_changed();
+
+        match (failed, target) {
+            (Some((step, e)), _) => {
+                self.report_error(&format!("Profile {}: failed to {}", name, step), e)
+            }
+            (None, Some(target)) => self.connect(target),
+            (None, None) => {}
+        }
+    }
+
+    /// Plan a tunnel setting change, asking first if other settings have
+    /// to change with it
+    fn request_setting(&mut self, change: SettingChange) {
+        let Some(tunnel) = self.tunnel else {
+            self.error_message = Some("Settings could not be read".to_string());
+            return;
+        };
+        let plan = match tunnel.plan(change) {
+            Ok(plan) => plan,
+            Err(e) => {
+                self.error_message = Some(e);
+                return;
+            }
+        };
+        if plan.is_empty() {
+            self.success_message = Some(match change {
+                SettingChange::Technology(technology) => format!("Already using {}", technology),
+                SettingChange::Obfuscate(enabled)
+                | SettingChange::PostQuantum(enabled)
+                | SettingChange::Meshnet(enabled) => {
+                    format!("{} is already {}", change.name(), on_off(enabled))
+                }
+            });
+            return;
+        }
+        let Some(picker) = self.technology_picker.as_mut() else {
+            return;
+        };
+        if plan.required().is_empty() {
+            let reconnect = picker.reconnect;
+            self.technology_picker = None;
+            self.apply_settings(plan, reconnect);
+        } else {
+            picker.pending = Some(plan);
+        }
+    }
+
+    /// Apply a change set and, if asked, reconnect so it takes effect
+    fn apply_settings(&mut self, plan: ChangeSet, reconnect: bool) {
+        let Some(wanted) = plan.steps.last().map(|step| step.change) else {
+            return;
+        };
+        let result = NordVPN::apply(&plan);
+        // Show what the daemon ended up with, whether or not it all worked
+        self.tunnel = NordVPN::get_tunnel().ok();
+        if let Some(technology) = self.tunnel.and_then(|tunnel| tunnel.technology) {
+            self.technology = Some(technology);
+        }
+        if let Err(e) = result {
+            self.report_change_set_error(&plan, e);
+            return;
+        }
+
+        let done = match wanted {
+            SettingChange::Technology(technology) => format!("Switched to {}", technology),
+            SettingChange::Obfuscate(enabled)
+            | SettingChange::PostQuantum(enabled)
+            | SettingChange::Meshnet(enabled) => {
+                format!("{} turned {}", wanted.name(), on_off(enabled))
+            }
+        };
+        let connected = matches!(self.status, ConnectionStatus::Connected { .. });
+        match self.last_target.clone() {
+            Some(target) if connected && reconnect => self.connect(target),
+            _ if connected => {
+                self.success_message = Some(format!("{}, reconnect to start using it", done));
+            }
+            _ => self.success_message = Some(done),
+        }
+    }
+
+    fn report_change_set_error(&mut self, plan: &ChangeSet, e: ChangeSetError) {
+        let mut message = format!("Failed to {}: {}", e.change, e.error);
+        if !e.not_undone.is_empty() {
+            let not_undone: Vec<String> = e.not_undone.iter().map(ToString::to_string).collect();
+            message.push_str(&format!("; could not undo: {}", not_undone.join(", ")));
+        } else if plan
+            .steps
+            .first()
+            .is_some_and(|step| step.change != e.change)
+        {
+            message.push_str("; earlier changes were undone");
+        }
+        self.error_hint = e.error.hint();
+        self.error_message = Some(message);
+    }
+
+    /// Open the server search, refreshing stale completions in the background
+    fn open_server_search(&mut self) {
+        self.server_search = Some(ServerSearch::default());
+        if self.servers.is_stale() && !self.servers_refreshing {
+            self.servers_refreshing = true;
+            spawn_recommendations(
+                self.event_tx.clone(),
+                self.config.recommendations_url.clone(),
+            );
+        }
+    }
+
+    /// Servers matching the search query, best match first.
+    ///
+    /// Favorites come first, then servers from the history (most recent
+    /// first), then the cached recommendations.
+    pub fn server_completions(&self) -> Vec<String> {
+        let query = self
+            .server_search
+            .as_ref()
+            .map(|search| search.query.trim())
+            .unwrap_or_default();
+        let known = self
+            .state
+            .favorites
+            .iter()
+            .chain(
+                self.state
+                    .history
+                    .iter()
+                    .rev()
+                    .filter_map(|e| e.target.as_ref()),
+            )
+            .filter_map(|target| match target {
+                Target::Server(server) => Some(server.as_str()),
+                _ => None,
+            })
+            .chain(self.servers.servers.iter().map(|r| r.server()));
+
+        let mut scored: Vec<(i64, String)> = vec![];
+        for server in known {
+            if scored.iter().any(|(_, s)| s == server) {
+                continue;
+            }
+            if let Some(score) = action::fuzzy_score(query, server) {
+                scored.push((score, server.to_string()));
+            }
+        }
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored.into_iter().map(|(_, server)| server).collect()
+    }
+
+    /// The server the search would connect to: the selected completion, or
+    /// else the query itself if it is a valid hostname
+    pub fn server_search_target(&self) -> std::result::Result<Target, String> {
+        let search = self
+            .server_search
+            .as_ref()
+            .ok_or("Server search is closed")?;
+        match self.server_completions().into_iter().nth(search.selected) {
+            Some(server) => Ok(Target::Server(server)),
+            None => servers::parse_server(&search.query).map(Target::Server),
+        }
+    }
+
+    /// Handle keys while the server search is open
+    fn on_server_search_key(&mut self, key: KeyEvent) {
+        let completions = self.server_completions();
+        let Some(search) = self.server_search.as_mut() else {
+            return;
+        };
+        match (key.modifiers, key.code) {
+            (_, KeyCode::Esc) | (KeyModifiers::CONTROL, KeyCode::Char('c')) => {
+                self.server_search = None;
+            }
+            (_, KeyCode::Enter) => match self.server_search_target() {
+                Ok(target) => {
+                    self.server_search = None;
+                    self.connect(target);
+                }
+                Err(e) => self.error_message = Some(e),
+            },
+            (KeyModifiers::CONTROL, KeyCode::Char('f') | KeyCode::Char('F')) => {
+                match self.server_search_target() {
+                    Ok(target) => self.toggle_favorite_target(target),
+                    Err(e) => self.error_message = Some(e),
+                }
+            }
+            (_, KeyCode::Tab) => {
+                if let Some(server) = completions.get(search.selected) {
+                    search.query = server.clone();
+                    search.selected = 0;
+                }
+            }
+            (_, KeyCode::Up) => search.selected = search.selected.saturating_sub(1),
+            (_, KeyCode::Down) if search.selected + 1 < completions.len() => {
+                search.selected += 1;
+            }
+            (_, KeyCode::Backspace) => {
+                search.query.pop();
+                search.selected = 0;
+            }
+            (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => {
+                search.query.push(c);
+                search.selected = 0;
+            }
+            _ => {}
+        }
+    }
+
+    /// Select a row in the country list
+    pub fn select_index(&mut self, index: usize) {
+        if index < self.filtered_countries.len() {
+            self.selected_index = index;
+            self.scroll_state = self.scroll_state.position(index);
+            self.list_state.select(Some(index));
         }
     }
 
@@ -162,22 +1737,398 @@ impl App {
 
     /// Handle crossterm events
     fn handle_crossterm_events(&mut self) -> Result<()> {
+        if !event::poll(TICK_RATE)? {
+            return Ok(());
+        }
         match event::read()? {
             Event::Key(key) if key.kind == KeyEventKind::Press => self.on_key_event(key),
-            Event::Mouse(_) => {}
+            Event::Mouse(mouse) => self.on_mouse_event(mouse),
             Event::Resize(_, _) => {}
             _ => {}
         }
         Ok(())
     }
 
+    /// Handle mouse events by hit-testing the areas from the last draw
+    fn on_mouse_event(&mut self, mouse: MouseEvent) {
+        let position = Position::new(mouse.column, mouse.row);
+        if self.palette.is_some()
+            || self.server_search.is_some()
+            || self.technology_picker.is_some()
+            || self.dns_input.is_some()
+            || self.dns_confirm.is_some()
+            || self.settings_path_input.is_some()
+            || self.settings_plan.is_some()
+        {
+            return;
+        }
+
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                if self.show_help {
+                    self.show_help = false;
+                    return;
+                }
+                self.on_click(position);
+            }
+            MouseEventKind::ScrollUp if self.in_country_list(position) => self.move_up(),
+            MouseEventKind::ScrollDown if self.in_country_list(position) => self.move_down(),
+            _ => {}
+        }
+    }
+
+    fn in_country_list(&self, position: Position) -> bool {
+        self.view == View::Countries
+            && self
+                .hit_areas
+                .country_list
+                .is_some_and(|area| area.contains(position))
+    }
+
+    /// Handle a left click
+    fn on_click(&mut self, position: Position) {
+        if let Some(view) = self
+            .hit_areas
+            .tabs
+            .iter()
+            .find(|(_, area)| area.contains(position))
+            .map(|(view, _)| *view)
+        {
+            self.show_view(view);
+            return;
+        }
+
+        if let Some(button) = self
+            .hit_areas
+            .buttons
+            .iter()
+            .find(|(_, area)| area.contains(position))
+            .map(|(button, _)| *button)
+        {
+            self.error_message = None;
+            self.error_hint = None;
+            self.success_message = None;
+            match button {
+                Button::Disconnect => self.disconnect(),
+                Button::Refresh => self.refresh_status(),
+            }
+            return;
+        }
+
+        let Some(list) = self.hit_areas.country_list else {
+            return;
+        };
+        if !self.in_country_list(position) {
+            return;
+        }
+        // Rows inside the border map to items starting at the scroll offset
+        let inner_top = list.y + 1;
+        if position.y < inner_top || position.y >= list.bottom().saturating_sub(1) {
+            return;
+        }
+        let index = self.list_state.offset() + usize::from(position.y - inner_top);
+        if index >= self.filtered_countries.len() {
+            return;
+        }
+
+        self.filter_mode = false;
+        let now = Instant::now();
+        let double_click = self
+            .last_click
+            .is_some_and(|(last, at)| last == index && now.duration_since(at) <= DOUBLE_CLICK);
+        self.select_index(index);
+        if double_click {
+            self.last_click = None;
+            self.connect_selected();
+        } else {
+            self.last_click = Some((index, now));
+        }
+    }
+
+    /// Every action the command palette can offer right now
+    pub fn available_actions(&self) -> Vec<Action> {
+        let mut actions = vec![
+            Action::Connect(Target::Quick),
+            Action::Disconnect,
+            Action::RefreshStatus,
+            Action::ToggleWatchdog,
+            Action::LeakTest,
+            Action::SearchServer,
+            Action::SwitchTechnology,
+        ];
+        actions.extend(Toggle::ALL.map(Action::Toggle));
+        if self.view == View::Countries && !self.filtered_countries.is_empty() {
+            actions.push(Action::ToggleFavorite);
+        }
+        actions.extend(
+            View::ALL
+                .iter()
+                .filter(|view| **view != self.view)
+                .map(|view| Action::ShowView(*view)),
+        );
+        actions.extend(
+            self.config
+                .profiles
+                .keys()
+                .cloned()
+                .map(Action::ApplyProfile),
+        );
+        actions.extend([
+            Action::RunDiagnostics,
+            Action::CopyReport,
+            Action::ShowHelp,
+            Action::Quit,
+        ]);
+        actions.extend(
+            self.groups
+                .iter()
+                .map(|group| Action::Connect(Target::Group(group.clone()))),
+        );
+        // Favorites first so they win ties against other countries
+        actions.extend(self.state.favorites.iter().cloned().map(Action::Connect));
+        actions.extend(
+            self.countries
+                .iter()
+                .map(|c| Target::Country(c.name.clone()))
+                .filter(|target| !self.state.is_favorite(target))
+                .map(Action::Connect),
+        );
+        actions
+    }
+
+    /// Actions matching the palette query, best match first
+    pub fn palette_matches(&self) -> Vec<Action> {
+        let query = self
+            .palette
+            .as_ref()
+            .map(|p| p.query.as_str())
+            .unwrap_or_default();
+        let mut scored: Vec<(i64, Action)> = self
+            .available_actions()
+            .into_iter()
+            .filter_map(|action| {
+                action::fuzzy_score(query, &action.to_string()).map(|score| (score, action))
+            })
+            .collect();
+        // Stable sort keeps the natural order among equal scores
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored.into_iter().map(|(_, action)| action).collect()
+    }
+
+    /// Run an action picked from the palette
+    fn run_action(&mut self, action: Action) {
+        match action {
+            Action::Connect(target) => self.connect(target),
+            Action::SearchServer => self.open_server_search(),
+            Action::SwitchTechnology => self.open_technology_picker(),
+            Action::Disconnect => self.disconnect(),
+            Action::RefreshStatus => self.refresh_status(),
+            Action::ToggleFavorite => self.toggle_favorite(),
+            Action::ToggleWatchdog => self.toggle_watchdog(),
+            Action::Toggle(toggle) => self.toggle_setting(toggle),
+            Action::LeakTest => self.start_leak_test(),
+            Action::ShowView(view) => self.show_view(view),
+            Action::ApplyProfile(name) => self.apply_profile(&name),
+            Action::RunDiagnostics => {
+                self.show_view(View::Diagnostics);
+                self.run_diagnostics();
+            }
+            Action::CopyReport => self.copy_diagnostics(),
+            Action::ShowHelp => self.show_help = true,
+            Action::Quit => self.quit(),
+        }
+    }
+
+    /// Handle keys while the command palette is open
+    fn on_palette_key(&mut self, key: KeyEvent) {
+        let matches = self.palette_matches().len();
+        let Some(palette) = self.palette.as_mut() else {
+            return;
+        };
+        match (key.modifiers, key.code) {
+            (_, KeyCode::Esc) | (KeyModifiers::CONTROL, KeyCode::Char('c')) => {
+                self.palette = None;
+            }
+            (_, KeyCode::Enter) => {
+                let selected = palette.selected;
+                let action = self.palette_matches().into_iter().nth(selected);
+                self.palette = None;
+                if let Some(action) = action {
+                    self.run_action(action);
+                }
+            }
+            (_, KeyCode::Up) | (KeyModifiers::CONTROL, KeyCode::Char('p')) => {
+                palette.selected = palette.selected.saturating_sub(1);
+            }
+            (_, KeyCode::Down) | (KeyModifiers::CONTROL, KeyCode::Char('n'))
+                if palette.selected + 1 < matches =>
+            {
+                palette.selected += 1;
+            }
+            (_, KeyCode::Backspace) => {
+                palette.query.pop();
+                palette.selected = 0;
+            }
+            (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => {
+                palette.query.push(c);
+                palette.selected = 0;
+            }
+            _ => {}
+        }
+    }
+
     /// Handle key events
     fn on_key_event(&mut self, key: KeyEvent) {
         // Clear messages on any key press
         self.error_message = None;
+        self.error_hint = None;
         self.success_message = None;
 
-        if self.filter_mode {
+        if self.palette.is_some() {
+            self.on_palette_key(key);
+            return;
+        }
+        if self.server_search.is_some() {
+            self.on_server_search_key(key);
+            return;
+        }
+        if self.technology_picker.is_some() {
+            self.on_technology_key(key);
+            return;
+        }
+        if self.dns_input.is_some() {
+            self.on_dns_input_key(key);
+            return;
+        }
+        if self.settings_path_input.is_some() {
+            self.on_settings_path_key(key);
+            return;
+        }
+        if let Some(steps) = self.settings_plan.take() {
+            if key.code == KeyCode::Char('y') {
+                self.apply_settings_plan(steps);
+            } else {
+                self.success_message = Some("Settings left unchanged".to_string());
+            }
+            return;
+        }
+        if let Some(change) = self.dns_confirm.take() {
+            if key.code == KeyCode::Char('y') {
+                self.apply_dns_change(change);
+            } else {
+                self.success_message = Some("DNS left unchanged".to_string());
+            }
+            return;
+        }
+
+        if self.show_help {
+            // Any key dismisses the overlay
+            self.show_help = false;
+            return;
+        }
+
+        if !self.filter_mode {
+            match (key.modifiers, key.code) {
+                (_, KeyCode::Char('?')) => {
+                    self.show_help = true;
+                    return;
+                }
+                (_, KeyCode::Char(':'))
+                | (KeyModifiers::CONTROL, KeyCode::Char('p') | KeyCode::Char('P')) => {
+                    self.palette = Some(Palette::default());
+                    return;
+                }
+                (KeyModifiers::CONTROL, KeyCode::Char('s') | KeyCode::Char('S')) => {
+                    self.open_server_search();
+                    return;
+                }
+                (KeyModifiers::CONTROL, KeyCode::Char('k') | KeyCode::Char('K')) => {
+                    self.connect(Target::Quick);
+                    return;
+                }
+                (KeyModifiers::CONTROL, KeyCode::Char('t') | KeyCode::Char('T')) => {
+                    self.open_technology_picker();
+                    return;
+                }
+                (KeyModifiers::CONTROL, KeyCode::Char('d') | KeyCode::Char('D')) => {
+                    self.disconnect();
+                    return;
+                }
+                (KeyModifiers::CONTROL, KeyCode::Char('w') | KeyCode::Char('W')) => {
+                    self.toggle_watchdog();
+                    return;
+                }
+                _ => {}
+            }
+        }
+
+        if self.view == View::Diagnostics {
+            match (key.modifiers, key.code) {
+                (_, KeyCode::Esc | KeyCode::Char('q'))
+                | (KeyModifiers::CONTROL, KeyCode::Char('c') | KeyCode::Char('C')) => self.quit(),
+                (_, KeyCode::Tab) => self.next_view(),
+                (_, KeyCode::BackTab) => self.previous_view(),
+                (KeyModifiers::CONTROL, KeyCode::Char('r') | KeyCode::Char('R')) => {
+                    self.run_diagnostics()
+                }
+                (_, KeyCode::Char('c')) => self.copy_diagnostics(),
+                _ => {}
+            }
+        } else if self.view == View::Dns {
+            match (key.modifiers, key.code) {
+                (_, KeyCode::Esc | KeyCode::Char('q'))
+                | (KeyModifiers::CONTROL, KeyCode::Char('c') | KeyCode::Char('C')) => self.quit(),
+                (_, KeyCode::Tab) => self.next_view(),
+                (_, KeyCode::BackTab) => self.previous_view(),
+                (KeyModifiers::CONTROL, KeyCode::Char('r') | KeyCode::Char('R')) => self.load_dns(),
+                (_, KeyCode::Char('e') | KeyCode::Enter) => self.edit_dns(),
+                (_, KeyCode::Char('x')) => self.request_dns_change(DnsChange::Reset),
+                (_, KeyCode::Char('t')) => self.toggle_threat_protection(),
+                _ => {}
+            }
+        } else if self.view == View::Settings {
+            match (key.modifiers, key.code) {
+                (_, KeyCode::Esc | KeyCode::Char('q'))
+                | (KeyModifiers::CONTROL, KeyCode::Char('c') | KeyCode::Char('C')) => self.quit(),
+                (_, KeyCode::Tab) => self.next_view(),
+                (_, KeyCode::BackTab) => self.previous_view(),
+                (KeyModifiers::CONTROL, KeyCode::Char('r') | KeyCode::Char('R')) => {
+                    self.load_settings()
+                }
+                (_, KeyCode::Char('e')) => self.ask_settings_path(SettingsFileAction::Export),
+                (_, KeyCode::Char('i')) => self.ask_settings_path(SettingsFileAction::Import),
+                (_, KeyCode::Char('a') | KeyCode::Enter) => self.request_settings_import(),
+                _ => {}
+            }
+        } else if self.view == View::Profiles {
+            match (key.modifiers, key.code) {
+                (_, KeyCode::Esc | KeyCode::Char('q'))
+                | (KeyModifiers::CONTROL, KeyCode::Char('c') | KeyCode::Char('C')) => self.quit(),
+                (_, KeyCode::Tab) => self.next_view(),
+                (_, KeyCode::BackTab) => self.previous_view(),
+                (_, KeyCode::Up | KeyCode::Char('k')) => {
+                    self.profile_selected = self.profile_selected.saturating_sub(1);
+                }
+                (_, KeyCode::Down | KeyCode::Char('j'))
+                    if self.profile_selected + 1 < self.config.profiles.len() =>
+                {
+                    self.profile_selected += 1;
+                }
+                (_, KeyCode::Enter) => match self.selected_profile().cloned() {
+                    Some(name) => self.apply_profile(&name),
+                    None => self.error_message = Some("No profiles configured".to_string()),
+                },
+                _ => {}
+            }
+        } else if self.view == View::History {
+            match (key.modifiers, key.code) {
+                (_, KeyCode::Esc | KeyCode::Char('q'))
+                | (KeyModifiers::CONTROL, KeyCode::Char('c') | KeyCode::Char('C')) => self.quit(),
+                (_, KeyCode::Tab) => self.next_view(),
+                (_, KeyCode::BackTab) => self.previous_view(),
+                _ => {}
+            }
+        } else if self.filter_mode {
             match key.code {
                 KeyCode::Esc => {
                     self.filter_mode = false;
@@ -211,12 +2162,14 @@ impl App {
                 (_, KeyCode::Up | KeyCode::Char('k')) => self.move_up(),
                 (_, KeyCode::Down | KeyCode::Char('j')) => self.move_down(),
                 (_, KeyCode::Enter) => self.connect_selected(),
-                (KeyModifiers::CONTROL, KeyCode::Char('d') | KeyCode::Char('D')) => {
-                    self.disconnect()
-                }
+                (_, KeyCode::Tab) => self.next_view(),
+                (_, KeyCode::BackTab) => self.previous_view(),
                 (KeyModifiers::CONTROL, KeyCode::Char('r') | KeyCode::Char('R')) => {
                     self.refresh_status()
                 }
+                (KeyModifiers::CONTROL, KeyCode::Char('f') | KeyCode::Char('F')) => {
+                    self.toggle_favorite()
+                }
                 (_, KeyCode::Char('/')) => {
                     self.filter_mode = true;
                 }
@@ -226,13 +2179,11 @@ impl App {
                     self.filter.push(c);
                     self.update_filter();
                 }
-                (KeyModifiers::NONE, KeyCode::Backspace) => {
+                (KeyModifiers::NONE, KeyCode::Backspace) if !self.filter.is_empty() => {
                     // Backspace in normal mode enters filter mode and deletes
-                    if !self.filter.is_empty() {
-                        self.filter_mode = true;
-                        self.filter.pop();
-                        self.update_filter();
-                    }
+                    self.filter_mode = true;
+                    self.filter.pop();
+                    self.update_filter();
                 }
                 _ => {}
             }
@@ -244,3 +2195,1130 @@ impl App {
         self.running = false;
     }
 }
+
+/// Run settings steps in order, leaving connects pending and skipping
+/// everything after the first failure, which is returned
+fn run_steps(steps: &mut [(Step, StepOutcome)]) -> Option<(String, BackendError)> {
+    let mut failed = None;
+    for (step, outcome) in steps {
+        if failed.is_some() {
+            *outcome = StepOutcome::Skipped;
+            continue;
+        }
+        if matches!(step, Step::Connect(_)) {
+            continue;
+        }
+        match step.apply() {
+            Ok(()) => *outcome = StepOutcome::Done,
+            Err(e) => {
+                *outcome = StepOutcome::Failed(e.to_string());
+                failed = Some((step.to_string(), e));
+            }
+        }
+    }
+    failed
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled { "on" } else { "off" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{HooksConfig, WatchdogConfig};
+    use crate::testing::FakeNordVPN;
+    use crate::types::{SettingChange, TunnelSettings};
+    use std::os::unix::net::UnixStream;
+
+    const CONNECTED: &str = r#"
+        status = "connected"
+        country = "Germany"
+        city = "Frankfurt"
+        hostname = "de1045.nordvpn.com"
+        ip = "185.130.184.86"
+    "#;
+
+    fn config() -> Config {
+        Config {
+            status_poll_secs: 0,
+            ..Config::default()
+        }
+    }
+
+    fn press(app: &mut App, code: KeyCode, modifiers: KeyModifiers) {
+        app.on_key_event(KeyEvent::new(code, modifiers));
+    }
+
+    /// Run the event loop without a terminal until `done` or a timeout
+    fn update_until(app: &mut App, done: impl Fn(&App) -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !done(app) {
+            assert!(Instant::now() < deadline, "timed out waiting for the app");
+            app.update();
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    fn history(app: &App) -> Vec<HistoryKind> {
+        app.state.history.iter().map(|entry| entry.kind).collect()
+    }
+
+    #[test]
+    fn starts_with_countries_and_status_from_cli() {
+        let _fake = FakeNordVPN::new(CONNECTED);
+        let app = App::new(config(), State::default(), ServerCache::default());
+
+        assert_eq!(app.daemon, DaemonState::Available);
+        assert_eq!(app.countries.len(), 4);
+        assert_eq!(app.countries[3].display_name, "United States");
+        assert_eq!(
+            app.status,
+            ConnectionStatus::Connected {
+                country: "Germany".to_string(),
+                city: Some("Frankfurt".to_string()),
+                server: Some("de1045.nordvpn.com".to_string()),
+                ip: Some("185.130.184.86".to_string()),
+            }
+        );
+        assert_eq!(
+            app.last_target,
+            Some(Target::Country("Germany".to_string()))
+        );
+    }
+
+    #[test]
+    fn connects_and_disconnects_with_keys() {
+        let fake = FakeNordVPN::new("");
+        let mut app = App::new(config(), State::default(), ServerCache::default());
+        assert_eq!(app.status, ConnectionStatus::Disconnected);
+
+        press(&mut app, KeyCode::Down, KeyModifiers::NONE);
+        press(&mut app, KeyCode::Down, KeyModifiers::NONE);
+        press(&mut app, KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(app.status, ConnectionStatus::Connecting);
+        update_until(&mut app, |app| app.connecting.is_none());
+        assert!(
+            matches!(&app.status, ConnectionStatus::Connected { country, .. } if country == "Sweden")
+        );
+        assert_eq!(fake.state()["status"].as_str(), Some("connected"));
+
+        press(&mut app, KeyCode::Char('d'), KeyModifiers::CONTROL);
+        assert_eq!(app.status, ConnectionStatus::Disconnected);
+        assert_eq!(fake.state()["status"].as_str(), Some("disconnected"));
+
+        let calls = fake.calls();
+        assert!(calls.contains(&"connect Sweden".to_string()));
+        assert!(calls.contains(&"disconnect".to_string()));
+        assert_eq!(
+            history(&app),
+            [HistoryKind::Connected, HistoryKind::Disconnected]
+        );
+    }
+
+    #[test]
+    fn global_keys_work_in_every_view() {
+        let fake = FakeNordVPN::new(CONNECTED);
+        let mut app = App::new(config(), State::default(), ServerCache::default());
+        app.view = View::Settings;
+
+        press(&mut app, KeyCode::Char('w'), KeyModifiers::CONTROL);
+        assert!(app.watchdog.enabled);
+        press(&mut app, KeyCode::Char('d'), KeyModifiers::CONTROL);
+        assert_eq!(app.status, ConnectionStatus::Disconnected);
+        assert_eq!(fake.state()["status"].as_str(), Some("disconnected"));
+    }
+
+    #[test]
+    fn reports_connect_failure() {
+        let _fake = FakeNordVPN::new(
+            r#"
+            fail = ["connect"]
+            fail_message = "The specified server is not available at the moment or does not support your connection settings."
+            "#,
+        );
+        let mut app = App::new(config(), State::default(), ServerCache::default());
+        press(&mut app, KeyCode::Enter, KeyModifiers::NONE);
+        update_until(&mut app, |app| app.connecting.is_none());
+
+        assert_eq!(app.status, ConnectionStatus::Disconnected);
+        assert!(
+            app.error_message
+                .as_ref()
+                .unwrap()
+                .contains("not available")
+        );
+        assert_eq!(
+            app.error_hint,
+            BackendError::InvalidTarget(String::new()).hint()
+        );
+        assert_eq!(history(&app), [HistoryKind::ConnectFailed]);
+    }
+
+    #[test]
+    fn streams_connect_progress() {
+        let fake = FakeNordVPN::new("latency_ms = 200");
+        let mut app = App::new(config(), State::default(), ServerCache::default());
+        press(&mut app, KeyCode::Enter, KeyModifiers::NONE);
+
+        press(&mut app, KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(
+            app.error_message.as_deref(),
+            Some("Already connecting to Germany")
+        );
+
+        update_until(&mut app, |app| app.connecting.is_none());
+        assert_eq!(
+            app.connect_log,
+            [
+                "Connecting to Germany #1045 (de1045.nordvpn.com)",
+                "You are connected to Germany #1045 (de1045.nordvpn.com)!"
+            ]
+        );
+        // The status comes from the success line, without asking again
+        assert_eq!(
+            app.status,
+            ConnectionStatus::Connected {
+                country: "Germany".to_string(),
+                city: None,
+                server: Some("de1045.nordvpn.com".to_string()),
+                ip: None,
+            }
+        );
+        assert_eq!(
+            fake.calls().last().map(String::as_str),
+            Some("connect Germany")
+        );
+    }
+
+    #[test]
+    fn connects_while_cli_floods_stderr() {
+        // More than a pipe holds, so stderr must be read while streaming
+        let _fake = FakeNordVPN::new("stderr_bytes = 200000");
+        let mut app = App::new(config(), State::default(), ServerCache::default());
+
+        app.connect(Target::Country("Germany".to_string()));
+        update_until(&mut app, |app| app.connecting.is_none());
+        assert!(matches!(app.status, ConnectionStatus::Connected { .. }));
+    }
+
+    #[test]
+    fn connects_to_server_by_hostname() {
+        let fake = FakeNordVPN::new("");
+        let mut servers = ServerCache::default();
+        servers.update(vec![crate::servers::Recommendation {
+            hostname: "se512.nordvpn.com".to_string(),
+            name: "Sweden #512".to_string(),
+            load: 9,
+        }]);
+        let mut app = App::new(config(), State::default(), servers);
+        let type_text = |app: &mut App, text: &str| {
+            for c in text.chars() {
+                press(app, KeyCode::Char(c), KeyModifiers::NONE);
+            }
+        };
+
+        press(&mut app, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        type_text(&mut app, "zz");
+        press(&mut app, KeyCode::Enter, KeyModifiers::NONE);
+        assert!(
+            app.error_message
+                .as_ref()
+                .unwrap()
+                .contains("no server number")
+        );
+        assert!(app.server_search.is_some());
+
+        // Valid but unknown hostnames can be favorited without a completion
+        press(&mut app, KeyCode::Esc, KeyModifiers::NONE);
+        press(&mut app, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        type_text(&mut app, "NL812");
+        press(&mut app, KeyCode::Char('f'), KeyModifiers::CONTROL);
+        assert_eq!(app.state.favorites, [Target::Server("nl812".to_string())]);
+
+        // Completions include favorites and the cached recommendations
+        press(&mut app, KeyCode::Esc, KeyModifiers::NONE);
+        press(&mut app, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(app.server_completions(), ["nl812", "se512"]);
+        type_text(&mut app, "se");
+        press(&mut app, KeyCode::Enter, KeyModifiers::NONE);
+        assert!(app.server_search.is_none());
+        update_until(&mut app, |app| app.connecting.is_none());
+
+        assert!(matches!(
+            &app.status,
+            ConnectionStatus::Connected { country, server: Some(server), .. }
+                if country == "Sweden" && server == "se512.nordvpn.com"
+        ));
+        assert_eq!(
+            fake.calls().last().map(String::as_str),
+            Some("connect se512")
+        );
+        let target = Some(Target::Server("se512".to_string()));
+        assert_eq!(app.state.history.last().unwrap().target, target);
+        assert_eq!(app.last_target, target);
+    }
+
+    #[test]
+    fn quick_connects_and_reports_chosen_server() {
+        let fake = FakeNordVPN::new("");
+        let mut app = App::new(config(), State::default(), ServerCache::default());
+        assert_eq!(app.groups, ["Double_VPN", "Europe", "P2P", "The_Americas"]);
+
+        press(&mut app, KeyCode::Char('k'), KeyModifiers::CONTROL);
+        update_until(&mut app, |app| app.connecting.is_none());
+        assert_eq!(fake.calls().last().map(String::as_str), Some("connect"));
+        assert_eq!(
+            app.success_message.as_deref(),
+            Some("Connected to fastest server: de1045.nordvpn.com in Germany")
+        );
+        assert_eq!(app.last_target, Some(Target::Quick));
+
+        press(&mut app, KeyCode::Char(':'), KeyModifiers::NONE);
+        for c in "quick p2p".chars() {
+            press(&mut app, KeyCode::Char(c), KeyModifiers::NONE);
+        }
+        assert_eq!(
+            app.palette_matches().first(),
+            Some(&Action::Connect(Target::Group("P2P".to_string())))
+        );
+        press(&mut app, KeyCode::Enter, KeyModifiers::NONE);
+        update_until(&mut app, |app| app.connecting.is_none());
+        assert_eq!(fake.calls().last().map(String::as_str), Some("connect P2P"));
+        assert_eq!(
+            app.success_message.as_deref(),
+            Some("Connected to P2P group: us1045.nordvpn.com in United States")
+        );
+    }
+
+    #[test]
+    fn toggles_kill_switch_from_palette() {
+        let fake = FakeNordVPN::new(CONNECTED);
+        let mut app = App::new(config(), State::default(), ServerCache::default());
+
+        press(&mut app, KeyCode::Char(':'), KeyModifiers::NONE);
+        for c in "kill switch".chars() {
+            press(&mut app, KeyCode::Char(c), KeyModifiers::NONE);
+        }
+        assert_eq!(
+            app.palette_matches().first(),
+            Some(&Action::Toggle(Toggle::KillSwitch))
+        );
+        press(&mut app, KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(
+            fake.calls().last().map(String::as_str),
+            Some("set killswitch on")
+        );
+        assert_eq!(app.success_message.as_deref(), Some("Kill Switch enabled"));
+    }
+
+    #[test]
+    fn switches_technology_and_reconnects() {
+        let fake = FakeNordVPN::new(CONNECTED);
+        let mut app = App::new(config(), State::default(), ServerCache::default());
+
+        press(&mut app, KeyCode::Char('t'), KeyModifiers::CONTROL);
+        assert_eq!(app.technology, Some(Technology::NordLynx));
+        let picker = app.technology_picker.clone().unwrap();
+        assert_eq!(Technology::ALL[picker.selected], Technology::NordLynx);
+        assert!(picker.reconnect);
+
+        press(&mut app, KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(
+            app.success_message.as_deref(),
+            Some("Already using NordLynx")
+        );
+
+        press(&mut app, KeyCode::Char('t'), KeyModifiers::CONTROL);
+        press(&mut app, KeyCode::Down, KeyModifiers::NONE);
+        press(&mut app, KeyCode::Down, KeyModifiers::NONE);
+        press(&mut app, KeyCode::Enter, KeyModifiers::NONE);
+        update_until(&mut app, |app| app.connecting.is_none());
+        assert!(app.technology_picker.is_none());
+        assert_eq!(app.technology, Some(Technology::OpenVpnTcp));
+        let calls = fake.calls();
+        assert_eq!(
+            calls[calls.len() - 4..],
+            [
+                "set technology OPENVPN",
+                "set protocol TCP",
+                "settings",
+                "connect Germany"
+            ]
+        );
+
+        // Without reconnecting the switch only takes effect next time
+        press(&mut app, KeyCode::Char('t'), KeyModifiers::CONTROL);
+        press(&mut app, KeyCode::Up, KeyModifiers::NONE);
+        press(&mut app, KeyCode::Char('r'), KeyModifiers::NONE);
+        press(&mut app, KeyCode::Enter, KeyModifiers::NONE);
+        assert!(fake.calls().contains(&"set protocol UDP".to_string()));
+        assert!(app.connecting.is_none());
+        assert_eq!(
+            app.success_message.as_deref(),
+            Some("Switched to OpenVPN (UDP), reconnect to start using it")
+        );
+    }
+
+    #[test]
+    fn reports_failed_technology_switch() {
+        let _fake = FakeNordVPN::new(
+            r#"
+            fail = ["set"]
+            fail_message = "Protocol setting is not available for this technology."
+        "#,
+        );
+        let mut app = App::new(config(), State::default(), ServerCache::default());
+
+        press(&mut app, KeyCode::Char('t'), KeyModifiers::CONTROL);
+        press(&mut app, KeyCode::Down, KeyModifiers::NONE);
+        press(&mut app, KeyCode::Enter, KeyModifiers::NONE);
+        assert!(
+            app.error_message
+                .as_ref()
+                .unwrap()
+                .contains("Protocol setting is not available")
+        );
+        assert_eq!(app.technology, Some(Technology::NordLynx));
+    }
+
+    #[test]
+    fn turns_on_obfuscation_with_required_changes() {
+        let fake = FakeNordVPN::new(
+            r#"
+            [settings]
+            Technology = "NORDLYNX"
+            Obfuscate = "disabled"
+            "Post-quantum VPN" = "enabled"
+            Meshnet = "disabled"
+        "#,
+        );
+        let mut app = App::new(config(), State::default(), ServerCache::default());
+
+        press(&mut app, KeyCode::Char('t'), KeyModifiers::CONTROL);
+        for _ in 0..Technology::ALL.len() {
+            press(&mut app, KeyCode::Down, KeyModifiers::NONE);
+        }
+        press(&mut app, KeyCode::Enter, KeyModifiers::NONE);
+        let plan = app
+            .technology_picker
+            .as_ref()
+            .and_then(|picker| picker.pending.clone())
+            .unwrap();
+        assert_eq!(
+            plan.required()
+                .iter()
+                .map(|step| step.change)
+                .collect::<Vec<_>>(),
+            [
+                SettingChange::PostQuantum(false),
+                SettingChange::Technology(Technology::OpenVpnUdp),
+            ]
+        );
+
+        // Going back leaves everything as it was
+        press(&mut app, KeyCode::Esc, KeyModifiers::NONE);
+        assert!(app.technology_picker.as_ref().unwrap().pending.is_none());
+        assert!(!fake.calls().iter().any(|call| call.starts_with("set ")));
+
+        press(&mut app, KeyCode::Enter, KeyModifiers::NONE);
+        press(&mut app, KeyCode::Enter, KeyModifiers::NONE);
+        assert!(app.technology_picker.is_none());
+        let sets: Vec<String> = fake
+            .calls()
+            .into_iter()
+            .filter(|call| call.starts_with("set "))
+            .collect();
+        assert_eq!(
+            sets,
+            [
+                "set post-quantum off",
+                "set technology OPENVPN",
+                "set protocol UDP",
+                "set obfuscate on",
+            ]
+        );
+        assert_eq!(
+            app.success_message.as_deref(),
+            Some("Obfuscation turned on")
+        );
+        assert_eq!(
+            app.tunnel,
+            Some(TunnelSettings {
+                technology: Some(Technology::OpenVpnUdp),
+                obfuscate: Some(true),
+                post_quantum: Some(false),
+                meshnet: Some(false),
+            })
+        );
+    }
+
+    #[test]
+    fn rolls_back_failed_change_set() {
+        let fake = FakeNordVPN::new(
+            r#"
+            fail = ["set obfuscate"]
+            fail_message = "Obfuscation is not available."
+            [settings]
+            Technology = "NORDLYNX"
+            Obfuscate = "disabled"
+            "Post-quantum VPN" = "enabled"
+            Meshnet = "disabled"
+        "#,
+        );
+        let mut app = App::new(config(), State::default(), ServerCache::default());
+
+        press(&mut app, KeyCode::Char('t'), KeyModifiers::CONTROL);
+        let before = app.tunnel;
+        app.technology_picker.as_mut().unwrap().selected = Technology::ALL.len();
+        press(&mut app, KeyCode::Enter, KeyModifiers::NONE);
+        press(&mut app, KeyCode::Char('y'), KeyModifiers::NONE);
+
+        assert_eq!(app.tunnel, before);
+        assert!(fake.calls().ends_with(&[
+            "set technology NORDLYNX".to_string(),
+            "set post-quantum on".to_string(),
+            "settings".to_string()
+        ]));
+        let error = app.error_message.unwrap();
+        assert!(error.starts_with("Failed to turn on obfuscation: Obfuscation is not available."));
+        assert!(error.ends_with("; earlier changes were undone"));
+    }
+
+    #[test]
+    fn sets_dns_and_confirms_threat_protection_conflict() {
+        let fake = FakeNordVPN::new("");
+        let mut app = App::new(config(), State::default(), ServerCache::default());
+        let type_text = |app: &mut App, text: &str| {
+            for c in text.chars() {
+                press(app, KeyCode::Char(c), KeyModifiers::NONE);
+            }
+        };
+
+        press(&mut app, KeyCode::Tab, KeyModifiers::NONE);
+        press(&mut app, KeyCode::Tab, KeyModifiers::NONE);
+        assert_eq!(app.view, View::Dns);
+        assert_eq!(
+            app.dns,
+            Some(DnsSettings {
+                servers: vec![],
+                threat_protection: Some(false),
+            })
+        );
+
+        press(&mut app, KeyCode::Char('e'), KeyModifiers::NONE);
+        type_text(&mut app, "1.1.1.1, 9.9.9.9.9");
+        press(&mut app, KeyCode::Enter, KeyModifiers::NONE);
+        assert!(
+            app.error_message
+                .as_ref()
+                .unwrap()
+                .contains("not an IP address")
+        );
+        press(&mut app, KeyCode::Backspace, KeyModifiers::NONE);
+        press(&mut app, KeyCode::Backspace, KeyModifiers::NONE);
+        press(&mut app, KeyCode::Enter, KeyModifiers::NONE);
+        assert!(app.dns_input.is_none());
+        assert!(
+            fake.calls()
+                .contains(&"set dns 1.1.1.1 9.9.9.9".to_string())
+        );
+        assert_eq!(
+            app.success_message.as_deref(),
+            Some("DNS set to 1.1.1.1, 9.9.9.9")
+        );
+        assert_eq!(app.dns.as_ref().unwrap().servers.len(), 2);
+
+        // Threat Protection Lite drops the custom servers, so it asks first
+        press(&mut app, KeyCode::Char('t'), KeyModifiers::NONE);
+        assert_eq!(app.dns_confirm, Some(DnsChange::ThreatProtection(true)));
+        press(&mut app, KeyCode::Char('n'), KeyModifiers::NONE);
+        assert!(app.dns_confirm.is_none());
+        assert_eq!(app.dns.as_ref().unwrap().threat_protection, Some(false));
+
+        press(&mut app, KeyCode::Char('t'), KeyModifiers::NONE);
+        press(&mut app, KeyCode::Char('y'), KeyModifiers::NONE);
+        assert!(
+            fake.calls()
+                .contains(&"set threatprotectionlite on".to_string())
+        );
+        assert_eq!(
+            app.dns,
+            Some(DnsSettings {
+                servers: vec![],
+                threat_protection: Some(true),
+            })
+        );
+
+        press(&mut app, KeyCode::Char('x'), KeyModifiers::NONE);
+        assert_eq!(
+            app.success_message.as_deref(),
+            Some("Already using NordVPN's DNS")
+        );
+    }
+
+    #[test]
+    fn applies_profile_then_connects() {
+        let fake = FakeNordVPN::new(
+            r#"
+            fail = ["allowlist add port"]
+            [cities]
+            Germany = ["Berlin", "Frankfurt"]
+        "#,
+        );
+        let profiles = Config::parse(
+            r#"
+            [profiles.home]
+            allowlist = { ports = [22] }
+            [profiles.work]
+            country = "Germany"
+            city = "Frankfurt"
+            technology = "openvpn"
+            kill_switch = true
+            dns = ["1.1.1.1"]
+        "#,
+        )
+        .unwrap()
+        .profiles;
+        let mut app = App::new(
+            Config {
+                profiles,
+                ..config()
+            },
+            State::default(),
+            ServerCache::default(),
+        );
+
+        app.show_view(View::Profiles);
+        press(&mut app, KeyCode::Down, KeyModifiers::NONE);
+        assert_eq!(app.selected_profile().map(String::as_str), Some("work"));
+        press(&mut app, KeyCode::Enter, KeyModifiers::NONE);
+        update_until(&mut app, |app| app.connecting.is_none());
+
+        let calls = fake.calls();
+        let position = |call: &str| calls.iter().position(|c| c == call).unwrap();
+        assert!(position("set technology OPENVPN") < position("set killswitch on"));
+        assert!(position("set killswitch on") < position("set dns 1.1.1.1"));
+        assert!(position("set dns 1.1.1.1") < position("connect Germany Frankfurt"));
+        let run = app.profile_run.as_ref().unwrap();
+        assert!(
+            run.steps
+                .iter()
+                .all(|(_, outcome)| *outcome == StepOutcome::Done)
+        );
+        assert!(matches!(app.status, ConnectionStatus::Connected { .. }));
+        assert_eq!(fake.state()["city"].as_str(), Some("Frankfurt"));
+
+        // A failing step stops the profile before it connects
+        press(&mut app, KeyCode::Up, KeyModifiers::NONE);
+        press(&mut app, KeyCode::Enter, KeyModifiers::NONE);
+        let run = app.profile_run.as_ref().unwrap();
+        assert_eq!(run.name, "home");
+        assert!(matches!(run.steps[0].1, StepOutcome::Failed(_)));
+        assert_eq!(run.steps[1].1, StepOutcome::Skipped);
+        assert!(app.connecting.is_none());
+        assert!(
+            app.error_message
+                .as_ref()
+                .unwrap()
+                .starts_with("Profile home: failed to allowlist port 22")
+        );
+    }
+
+    #[test]
+    fn exports_and_imports_settings_file() {
+        let fake = FakeNordVPN::new("");
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("laptop.json");
+        let mut app = App::new(config(), State::default(), ServerCache::default());
+        let type_path = |app: &mut App| {
+            press(app, KeyCode::Char('u'), KeyModifiers::CONTROL);
+            for c in path.display().to_string().chars() {
+                press(app, KeyCode::Char(c), KeyModifiers::NONE);
+            }
+            press(app, KeyCode::Enter, KeyModifiers::NONE);
+        };
+
+        app.show_view(View::Settings);
+        assert_eq!(app.settings.as_ref().unwrap().kill_switch, Some(false));
+        press(&mut app, KeyCode::Char('e'), KeyModifiers::NONE);
+        type_path(&mut app);
+        let exported = std::fs::read_to_string(&path).unwrap();
+        assert!(exported.contains(r#""kill_switch": false"#));
+
+        // Another machine has the kill switch on and a custom DNS server
+        std::fs::write(
+            &path,
+            exported
+                .replace(r#""kill_switch": false"#, r#""kill_switch": true"#)
+                .replace(r#""dns": []"#, r#""dns": ["9.9.9.9"]"#),
+        )
+        .unwrap();
+        press(&mut app, KeyCode::Char('i'), KeyModifiers::NONE);
+        type_path(&mut app);
+        assert_eq!(
+            app.success_message.as_deref(),
+            Some("2 settings differ, a: apply")
+        );
+
+        press(&mut app, KeyCode::Char('a'), KeyModifiers::NONE);
+        assert_eq!(app.settings_plan.as_ref().map(Vec::len), Some(2));
+        press(&mut app, KeyCode::Char('n'), KeyModifiers::NONE);
+        assert!(!fake.calls().contains(&"set killswitch on".to_string()));
+
+        press(&mut app, KeyCode::Char('a'), KeyModifiers::NONE);
+        press(&mut app, KeyCode::Char('y'), KeyModifiers::NONE);
+        assert_eq!(app.success_message.as_deref(), Some("Applied 2 changes"));
+        let calls = fake.calls();
+        assert!(calls.contains(&"set killswitch on".to_string()));
+        assert!(calls.contains(&"set dns 9.9.9.9".to_string()));
+        let (_, file) = app.settings_file.as_ref().unwrap();
+        assert!(file.diff(app.settings.as_ref().unwrap()).is_empty());
+    }
+
+    #[test]
+    fn import_reports_unreadable_settings() {
+        let fake = FakeNordVPN::new("");
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("laptop.toml");
+        std::fs::write(&path, "kill_switch = true\n").unwrap();
+        let mut app = App::new(config(), State::default(), ServerCache::default());
+        fake.set_state("fail = [\"settings\"]");
+
+        app.import_settings(path);
+        assert_eq!(app.success_message, None);
+        assert!(
+            app.error_message
+                .as_deref()
+                .unwrap()
+                .starts_with("Failed to read settings")
+        );
+
+        // Compared once the settings can be read
+        fake.set_state("");
+        app.import_settings(app.settings_file.clone().unwrap().0);
+        assert_eq!(
+            app.success_message.as_deref(),
+            Some("1 settings differ, a: apply")
+        );
+    }
+
+    #[test]
+    fn recovers_once_daemon_starts() {
+        let fake = FakeNordVPN::new("daemon = false");
+        let mut app = App::new(config(), State::default(), ServerCache::default());
+        assert!(matches!(
+            app.daemon,
+            DaemonState::Unavailable {
+                error: BackendError::DaemonUnavailable(_),
+                ..
+            }
+        ));
+        assert!(app.countries.is_empty());
+
+        fake.set_state("");
+        update_until(&mut app, |app| app.daemon == DaemonState::Available);
+        assert_eq!(app.countries.len(), 4);
+    }
+
+    #[test]
+    fn watchdog_reconnects_after_drop() {
+        let fake = FakeNordVPN::new(CONNECTED);
+        let config = Config {
+            status_poll_secs: 1,
+            watchdog: WatchdogConfig {
+                enabled: true,
+                backoff_initial_secs: 0,
+                ..WatchdogConfig::default()
+            },
+            ..Config::default()
+        };
+        let mut app = App::new(config, State::default(), ServerCache::default());
+
+        fake.set_state("");
+        update_until(&mut app, |app| {
+            history(app).contains(&HistoryKind::Reconnected)
+        });
+
+        assert_eq!(
+            history(&app),
+            [
+                HistoryKind::Dropped,
+                HistoryKind::ReconnectAttempt,
+                HistoryKind::Reconnected
+            ]
+        );
+        assert!(fake.calls().contains(&"connect Germany".to_string()));
+        assert!(matches!(app.status, ConnectionStatus::Connected { .. }));
+    }
+
+    #[test]
+    fn runs_hooks_in_order_with_status() {
+        let _fake = FakeNordVPN::new("");
+        let echo = "echo \"$NORDVPN_EVENT $NORDVPN_STATE $NORDVPN_TARGET $NORDVPN_SERVER\"";
+        let config = Config {
+            hooks: HooksConfig {
+                pre_connect: vec![echo.to_string()],
+                post_connect: vec![echo.to_string(), "exit 4".to_string()],
+                disconnect: vec![echo.to_string()],
+                ..HooksConfig::default()
+            },
+            ..config()
+        };
+        let mut app = App::new(config, State::default(), ServerCache::default());
+
+        app.connect(Target::Country("Sweden".to_string()));
+        update_until(&mut app, |app| app.hook_log.len() == 3);
+        assert_eq!(
+            app.error_message.as_deref(),
+            Some("post-connect hook `exit 4` exited with 4")
+        );
+        press(&mut app, KeyCode::Char('d'), KeyModifiers::CONTROL);
+        update_until(&mut app, |app| app.hook_log.len() == 4);
+
+        let log: Vec<_> = app
+            .hook_log
+            .iter()
+            .map(|run| (run.event, run.output.join("\n"), run.succeeded()))
+            .collect();
+        assert_eq!(
+            log,
+            [
+                (
+                    HookEvent::PreConnect,
+                    "pre-connect disconnected Sweden ".to_string(),
+                    true
+                ),
+                (
+                    HookEvent::PostConnect,
+                    "post-connect connected Sweden se1045.nordvpn.com".to_string(),
+                    true
+                ),
+                (HookEvent::PostConnect, String::new(), false),
+                (
+                    HookEvent::Disconnect,
+                    "disconnect disconnected Sweden ".to_string(),
+                    true
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn runs_hook_when_server_changes() {
+        let _fake = FakeNordVPN::new(CONNECTED);
+        let config = Config {
+            hooks: HooksConfig {
+                server_change: vec![
+                    "echo \"$NORDVPN_PREVIOUS_SERVER $NORDVPN_SERVER\"".to_string(),
+                ],
+                ..HooksConfig::default()
+            },
+            ..config()
+        };
+        let mut app = App::new(config, State::default(), ServerCache::default());
+        app.update();
+
+        let ConnectionStatus::Connected {
+            country, city, ip, ..
+        } = app.status.clone()
+        else {
+            panic!("not connected: {:?}", app.status);
+        };
+        let moved = ConnectionStatus::Connected {
+            country,
+            city,
+            server: Some("de1046.nordvpn.com".to_string()),
+            ip,
+        };
+        app.on_app_event(AppEvent::StatusPolled {
+            sampled_at: Instant::now(),
+            result: Ok(moved),
+        });
+        update_until(&mut app, |app| !app.hook_log.is_empty());
+        assert_eq!(
+            app.hook_log[0].output,
+            ["de1045.nordvpn.com de1046.nordvpn.com"]
+        );
+    }
+
+    #[test]
+    fn ignores_reconnect_cancelled_by_user() {
+        let _fake = FakeNordVPN::new("");
+        let mut app = App::new(config(), State::default(), ServerCache::default());
+        app.watchdog.enabled = true;
+        let germany = Target::Country("Germany".to_string());
+        app.watchdog
+            .on_drop(germany, Instant::now() - Duration::from_secs(60));
+        let (target, attempt, id) = app.watchdog.poll(Instant::now()).unwrap();
+
+        press(&mut app, KeyCode::Char('d'), KeyModifiers::CONTROL);
+        for result in [
+            Err(BackendError::BinaryNotFound),
+            Ok(ConnectionStatus::Connected {
+                country: "Germany".to_string(),
+                city: None,
+                server: None,
+                ip: None,
+            }),
+        ] {
+            app.on_app_event(AppEvent::Reconnect {
+                target: target.clone(),
+                attempt,
+                id,
+                result,
+            });
+        }
+
+        assert_eq!(app.status, ConnectionStatus::Disconnected);
+        assert_eq!(app.last_target, None);
+        assert_eq!(app.error_message, None);
+        assert_eq!(history(&app), [HistoryKind::Disconnected]);
+    }
+
+    #[test]
+    fn disconnect_cancels_pending_connect() {
+        let _fake = FakeNordVPN::new("");
+        let mut app = App::new(config(), State::default(), ServerCache::default());
+        let germany = Target::Country("Germany".to_string());
+        // As if a connect were still running in the background
+        app.connecting = Some(germany.clone());
+        app.connect_id = 1;
+
+        press(&mut app, KeyCode::Char('d'), KeyModifiers::CONTROL);
+        assert_eq!(app.connecting, None);
+        app.on_app_event(AppEvent::Connected {
+            target: germany,
+            id: 1,
+            result: Ok(ConnectionStatus::Connected {
+                country: "Germany".to_string(),
+                city: None,
+                server: None,
+                ip: None,
+            }),
+        });
+
+        assert_eq!(app.status, ConnectionStatus::Disconnected);
+        assert_eq!(app.last_target, None);
+        assert_eq!(history(&app), [HistoryKind::Disconnected]);
+    }
+
+    #[test]
+    fn disconnect_kills_running_connect() {
+        let fake = FakeNordVPN::new(
+            r#"
+            status = "disconnected"
+            latency_ms = 300
+            "#,
+        );
+        let mut app = App::new(config(), State::default(), ServerCache::default());
+        app.connect(Target::Country("Germany".to_string()));
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !fake.calls().iter().any(|call| call == "connect Germany") {
+            assert!(Instant::now() < deadline, "connect never started");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        press(&mut app, KeyCode::Char('d'), KeyModifiers::CONTROL);
+        // Long enough for the connect to have finished had it survived
+        std::thread::sleep(Duration::from_millis(600));
+        app.update();
+
+        assert_eq!(fake.state()["status"].as_str(), Some("disconnected"));
+        assert_eq!(app.status, ConnectionStatus::Disconnected);
+        assert_eq!(app.connecting, None);
+    }
+
+    #[test]
+    fn refuses_connect_while_reconnecting() {
+        let fake = FakeNordVPN::new("");
+        let mut app = App::new(config(), State::default(), ServerCache::default());
+        app.watchdog.enabled = true;
+        app.watchdog.on_drop(
+            Target::Country("Germany".to_string()),
+            Instant::now() - Duration::from_secs(60),
+        );
+        app.watchdog.poll(Instant::now()).unwrap();
+
+        app.connect(Target::Country("Sweden".to_string()));
+        assert_eq!(
+            app.error_message.as_deref(),
+            Some("Already reconnecting to Germany")
+        );
+        assert_eq!(app.connecting, None);
+        assert!(!fake.calls().iter().any(|call| call.starts_with("connect")));
+    }
+
+    /// Render once so the hit areas are recorded, as the event loop does
+    fn draw(app: &mut App) {
+        let backend = ratatui::backend::TestBackend::new(80, 24);
+        let mut terminal = ratatui::Terminal::new(backend).unwrap();
+        terminal
+            .draw(|frame| crate::ui::render(app, frame, Instant::now()))
+            .unwrap();
+    }
+
+    fn mouse(app: &mut App, kind: MouseEventKind, area: Rect, row: u16) {
+        app.on_mouse_event(MouseEvent {
+            kind,
+            column: area.x + 2,
+            row: area.y + row,
+            modifiers: KeyModifiers::NONE,
+        });
+    }
+
+    fn click(app: &mut App, area: Rect, row: u16) {
+        mouse(app, MouseEventKind::Down(MouseButton::Left), area, row);
+    }
+
+    #[test]
+    fn handles_mouse_on_tabs_list_and_buttons() {
+        let fake = FakeNordVPN::new("");
+        let mut app = App::new(config(), State::default(), ServerCache::default());
+        draw(&mut app);
+
+        let tab = |app: &App, view| {
+            app.hit_areas
+                .tabs
+                .iter()
+                .find(|(tab, _)| *tab == view)
+                .map(|(_, area)| *area)
+                .unwrap()
+        };
+        let list = app.hit_areas.country_list.unwrap();
+        let history = tab(&app, View::History);
+        click(&mut app, history, 0);
+        assert_eq!(app.view, View::History);
+        draw(&mut app);
+        // The list is not shown, so where it was takes no scrolling
+        mouse(&mut app, MouseEventKind::ScrollDown, list, 2);
+        assert_eq!(app.selected_index, 0);
+        let countries = tab(&app, View::Countries);
+        click(&mut app, countries, 0);
+        assert_eq!(app.view, View::Countries);
+        draw(&mut app);
+
+        // Rows start inside the border; clicks on the border do nothing
+        click(&mut app, list, 0);
+        assert_eq!(app.selected_index, 0);
+        click(&mut app, list, 2);
+        assert_eq!(app.selected_index, 1);
+        assert_eq!(app.connecting, None);
+        mouse(&mut app, MouseEventKind::ScrollDown, list, 2);
+        assert_eq!(app.selected_index, 2);
+        mouse(&mut app, MouseEventKind::ScrollUp, list, 2);
+        assert_eq!(app.selected_index, 1);
+
+        // A second click on the same row connects
+        click(&mut app, list, 3);
+        assert_eq!(app.connecting, None);
+        click(&mut app, list, 3);
+        let target = Target::Country(app.filtered_countries[2].name.clone());
+        assert_eq!(app.connecting, Some(target.clone()));
+        update_until(&mut app, |app| app.connecting.is_none());
+        assert!(fake.calls().contains(&format!("connect {}", target)));
+
+        draw(&mut app);
+        let disconnect = app
+            .hit_areas
+            .buttons
+            .iter()
+            .find(|(button, _)| *button == Button::Disconnect)
+            .map(|(_, area)| *area)
+            .unwrap();
+        click(&mut app, disconnect, 0);
+        assert_eq!(app.status, ConnectionStatus::Disconnected);
+        assert!(fake.calls().contains(&"disconnect".to_string()));
+    }
+
+    /// Read the next line sent to a control client, running the event loop
+    /// meanwhile
+    fn read_line(app: &mut App, reader: &mut std::io::BufReader<UnixStream>) -> Value {
+        use std::io::BufRead;
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut line = String::new();
+        while !line.ends_with('\n') {
+            assert!(Instant::now() < deadline, "timed out waiting for a reply");
+            app.update();
+            let _ = reader.read_line(&mut line);
+        }
+        serde_json::from_str(&line).unwrap()
+    }
+
+    fn client(path: &std::path::Path) -> (UnixStream, std::io::BufReader<UnixStream>) {
+        let stream = UnixStream::connect(path).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_millis(20)))
+            .unwrap();
+        let reader = std::io::BufReader::new(stream.try_clone().unwrap());
+        (stream, reader)
+    }
+
+    #[test]
+    fn serves_control_socket_clients() {
+        use std::io::Write;
+        let fake = FakeNordVPN::new("");
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("control.sock");
+        let mut state = State::default();
+        state.favorites.push(Target::Group("P2P".to_string()));
+        let mut app = App::new(config(), state, ServerCache::default());
+        app.start_control(&path);
+
+        let (mut watcher, mut events) = client(&path);
+        writeln!(watcher, r#"{{"cmd":"subscribe"}}"#).unwrap();
+        assert_eq!(read_line(&mut app, &mut events), json!({ "ok": true }));
+        assert_eq!(
+            read_line(&mut app, &mut events)["status"]["state"],
+            "disconnected"
+        );
+
+        let (mut control, mut replies) = client(&path);
+        writeln!(control, r#"{{"cmd":"favorites"}}"#).unwrap();
+        assert_eq!(
+            read_line(&mut app, &mut replies),
+            json!({ "ok": true, "favorites": [{ "group": "P2P" }] })
+        );
+        writeln!(control, r#"{{"cmd":"reboot"}}"#).unwrap();
+        assert_eq!(read_line(&mut app, &mut replies)["ok"], false);
+
+        writeln!(
+            control,
+            r#"{{"cmd":"connect","target":{{"country":"Sweden"}}}}"#
+        )
+        .unwrap();
+        assert_eq!(
+            read_line(&mut app, &mut replies),
+            json!({ "ok": true, "connecting": { "country": "Sweden" } })
+        );
+        // Connecting may already be over by the time it is published
+        let event = loop {
+            let event = read_line(&mut app, &mut events);
+            if event["status"]["state"] != "connecting" {
+                break event;
+            }
+        };
+        assert_eq!(event["status"]["country"], "Sweden");
+        assert_eq!(event["status"]["target"], json!({ "country": "Sweden" }));
+        assert!(matches!(app.status, ConnectionStatus::Connected { .. }));
+
+        writeln!(control, r#"{{"cmd":"disconnect"}}"#).unwrap();
+        assert_eq!(read_line(&mut app, &mut replies), json!({ "ok": true }));
+        assert_eq!(
+            read_line(&mut app, &mut events)["status"]["state"],
+            "disconnected"
+        );
+        assert_eq!(fake.state()["status"].as_str(), Some("disconnected"));
+
+        writeln!(control, r#"{{"cmd":"history","limit":1}}"#).unwrap();
+        let reply = read_line(&mut app, &mut replies);
+        assert_eq!(reply["history"].as_array().unwrap().len(), 1);
+        assert_eq!(reply["history"][0]["kind"], "disconnected");
+
+        // A second instance leaves the running one alone
+        let (tx, _rx) = mpsc::channel();
+        assert!(ipc::listen(&path, tx).is_err());
+        drop(app);
+        assert!(!path.exists());
+    }
+}
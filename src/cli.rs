@@ -0,0 +1,148 @@
+use std::path::PathBuf;
+
+/// What the binary was asked to do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Run the interactive TUI (default)
+    Tui,
+    /// Run the diagnostics checks and print a report
+    Doctor,
+    /// Apply a profile from the config file and connect
+    Profile(String),
+    /// Export, compare or import daemon settings
+    Settings(SettingsCommand),
+    /// Apply the `[agent]` rules whenever the network changes, or once
+    Agent { once: bool },
+    /// Print the status whenever it changes, for status bars
+    Watch(WatchOptions),
+    /// Print usage
+    Help,
+    /// Print the version
+    Version,
+}
+
+/// `nordvpn-tui settings ...`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SettingsCommand {
+    /// Write the current settings to a file, or print them
+    Export { path: Option<PathBuf>, json: bool },
+    /// Show how a file differs from the current settings
+    Diff(PathBuf),
+    /// Show the differences, then apply them after confirming
+    Import { path: PathBuf, yes: bool },
+}
+
+/// `nordvpn-tui watch ...`; unset options come from the `[watch]` config
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WatchOptions {
+    pub format: Option<String>,
+    pub interval: Option<u64>,
+    pub json: bool,
+    /// Print the current status and exit
+    pub once: bool,
+}
+
+pub const USAGE: &str = "\
+A thin wrapper around the nordvpn cli to manage connectivity
+
+Usage: nordvpn-tui [COMMAND]
+
+Commands:
+  doctor          Check the NordVPN installation and print a report
+  profile <NAME>  Apply a profile from the config file and connect
+  settings export [--json] [FILE]
+                  Save the daemon settings to FILE (.toml or .json), or print them
+  settings diff <FILE>
+                  Show how FILE differs from the current settings
+  settings import [--yes] <FILE>
+                  Show the differences, then apply them after asking
+  agent [--once]  Connect or disconnect by the [agent] rules whenever the
+                  network changes; --once checks the current network and exits
+  watch [--json] [--once] [--interval SECS] [--format TEMPLATE]
+                  Print the status each time it changes, for status bars
+
+Options:
+  -h, --help     Print help
+  -V, --version  Print version";
+
+/// Parse command line arguments (without the program name)
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+    let args: Vec<String> = args.into_iter().collect();
+    match args.first().map(String::as_str) {
+        None => Ok(Command::Tui),
+        Some("-h" | "--help" | "help") => Ok(Command::Help),
+        Some("-V" | "--version") => Ok(Command::Version),
+        Some("doctor") if args.len() == 1 => Ok(Command::Doctor),
+        Some("profile") => match &args[1..] {
+            [name] => Ok(Command::Profile(name.clone())),
+            [] => Err("profile needs a name".to_string()),
+            [_, extra, ..] => Err(format!("unexpected argument '{}'", extra)),
+        },
+        Some("agent") => match &args[1..] {
+            [] => Ok(Command::Agent { once: false }),
+            [flag] if flag == "--once" => Ok(Command::Agent { once: true }),
+            [extra, ..] => Err(format!("unexpected argument '{}'", extra)),
+        },
+        Some("watch") => parse_watch(&args[1..]).map(Command::Watch),
+        Some("settings") => parse_settings(&args[1..]).map(Command::Settings),
+        Some(other) => Err(format!("unexpected argument '{}'", other)),
+    }
+}
+
+fn parse_watch(args: &[String]) -> Result<WatchOptions, String> {
+    let mut options = WatchOptions::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--json" => options.json = true,
+            "--once" => options.once = true,
+            "--format" => options.format = Some(value()?),
+            "--interval" => {
+                let interval = value()?;
+                match interval.parse::<u64>() {
+                    Ok(secs) if secs > 0 => options.interval = Some(secs),
+                    _ => return Err(format!("invalid interval '{}'", interval)),
+                }
+            }
+            other => return Err(format!("unexpected argument '{}'", other)),
+        }
+    }
+    Ok(options)
+}
+
+fn parse_settings(args: &[String]) -> Result<SettingsCommand, String> {
+    let (flags, paths): (Vec<&String>, Vec<&String>) =
+        args.iter().skip(1).partition(|arg| arg.starts_with("--"));
+    let flag = |name: &str| flags.iter().any(|f| *f == name);
+    let allowed: &[&str] = match args.first().map(String::as_str) {
+        Some("export") => &["--json"],
+        Some("import") => &["--yes"],
+        _ => &[],
+    };
+    if let Some(unknown) = flags.iter().find(|f| !allowed.contains(&f.as_str())) {
+        return Err(format!("unexpected argument '{}'", unknown));
+    }
+    if let Some(extra) = paths.get(1) {
+        return Err(format!("unexpected argument '{}'", extra));
+    }
+    let path = paths.first().map(PathBuf::from);
+    match (args.first().map(String::as_str), path) {
+        (Some("export"), path) => Ok(SettingsCommand::Export {
+            path,
+            json: flag("--json"),
+        }),
+        (Some("diff"), Some(path)) => Ok(SettingsCommand::Diff(path)),
+        (Some("import"), Some(path)) => Ok(SettingsCommand::Import {
+            path,
+            yes: flag("--yes"),
+        }),
+        (Some("diff" | "import"), None) => Err("settings file missing".to_string()),
+        (Some(other), _) => Err(format!("unexpected argument '{}'", other)),
+        (None, _) => Err("settings needs export, diff or import".to_string()),
+    }
+}
@@ -0,0 +1,319 @@
+use crate::agent::Rule;
+use crate::doctor::DAEMON_SOCKET;
+use crate::profile::Profile;
+use crate::watch::Template;
+use color_eyre::{Result, eyre::WrapErr, eyre::eyre};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// User configuration, read from `$XDG_CONFIG_HOME/nordvpn-tui/config.toml`.
+///
+/// Every field has a default, so a missing file or section is fine.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Path of the `nordvpn` CLI, if it is not on the PATH
+    pub nordvpn_binary: Option<PathBuf>,
+    pub backend: Backend,
+    /// Socket of the daemon's gRPC API, used by the gRPC backend
+    pub daemon_socket: PathBuf,
+    /// How often to poll `nordvpn status` in the background (0 disables)
+    pub status_poll_secs: u64,
+    /// NordVPN API endpoint listing recommended servers, used to complete
+    /// hostnames in the server search
+    pub recommendations_url: String,
+    pub leak_test: LeakTestConfig,
+    pub watchdog: WatchdogConfig,
+    /// Named targets with settings, applied from the Profiles view or
+    /// `nordvpn-tui profile <name>`
+    pub profiles: BTreeMap<String, Profile>,
+    pub agent: AgentConfig,
+    pub control: ControlConfig,
+    pub watch: WatchConfig,
+    pub desktop_notifications: DesktopNotificationsConfig,
+    pub hooks: HooksConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            nordvpn_binary: None,
+            backend: Backend::default(),
+            daemon_socket: PathBuf::from(DAEMON_SOCKET),
+            status_poll_secs: 10,
+            recommendations_url: "https://api.nordvpn.com/v1/servers/recommendations?limit=100"
+                .to_string(),
+            leak_test: LeakTestConfig::default(),
+            watchdog: WatchdogConfig::default(),
+            profiles: BTreeMap::new(),
+            agent: AgentConfig::default(),
+            control: ControlConfig::default(),
+            watch: WatchConfig::default(),
+            desktop_notifications: DesktopNotificationsConfig::default(),
+            hooks: HooksConfig::default(),
+        }
+    }
+}
+
+/// How to talk to nordvpnd
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// Run the `nordvpn` CLI for everything
+    #[default]
+    Cli,
+    /// Use the daemon socket where possible, falling back to the CLI
+    Grpc,
+}
+
+/// Post-connect verification of the public IP and DNS resolvers
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LeakTestConfig {
+    /// Run the check automatically after connecting
+    pub enabled: bool,
+    /// Endpoint returning the caller's public IP as plain text
+    pub ip_endpoint: String,
+    /// Request timeout in seconds
+    pub timeout_secs: u64,
+    /// Resolvers considered safe while connected
+    pub expected_resolvers: Vec<String>,
+}
+
+impl Default for LeakTestConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ip_endpoint: "https://api.ipify.org".to_string(),
+            timeout_secs: 5,
+            expected_resolvers: vec!["103.86.96.100".to_string(), "103.86.99.100".to_string()],
+        }
+    }
+}
+
+/// Automatic reconnection after an unexpected disconnect
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WatchdogConfig {
+    /// Start with the watchdog armed (it can also be toggled at runtime)
+    pub enabled: bool,
+    /// Reconnect attempts per target before giving up on it
+    pub max_attempts: u32,
+    /// Delay before the first attempt, doubled after each failure
+    pub backoff_initial_secs: u64,
+    /// Upper bound for the delay between attempts
+    pub backoff_max_secs: u64,
+    /// Try the favorites in order once the last target is exhausted
+    pub fallback_to_favorites: bool,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_attempts: 5,
+            backoff_initial_secs: 2,
+            backoff_max_secs: 60,
+            fallback_to_favorites: false,
+        }
+    }
+}
+
+/// Rules applied by `nordvpn-tui agent` when the network changes
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AgentConfig {
+    /// Wi-Fi networks matched by `network = "trusted-wifi"`; any other
+    /// Wi-Fi counts as untrusted
+    pub trusted_ssids: Vec<String>,
+    /// Command printing the current SSID, e.g. `["iwgetid", "-r"]`;
+    /// NetworkManager is asked if unset
+    pub ssid_command: Option<Vec<String>>,
+    /// How often to re-check the network between route events
+    pub poll_secs: u64,
+    /// Checked in order; the first match wins
+    pub rules: Vec<Rule>,
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            trusted_ssids: Vec::new(),
+            ssid_command: None,
+            poll_secs: 30,
+            rules: Vec::new(),
+        }
+    }
+}
+
+/// Unix socket through which other tools can query and drive the app
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ControlConfig {
+    pub enabled: bool,
+    /// Defaults to `$XDG_RUNTIME_DIR/nordvpn-tui/control.sock`
+    pub socket: Option<PathBuf>,
+}
+
+impl ControlConfig {
+    /// Where to listen, falling back to the state directory without
+    /// `$XDG_RUNTIME_DIR`
+    pub fn socket_path(&self) -> Option<PathBuf> {
+        if let Some(socket) = &self.socket {
+            return Some(socket.clone());
+        }
+        let base = std::env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .or_else(|| {
+                crate::state::State::path()?
+                    .parent()?
+                    .parent()
+                    .map(PathBuf::from)
+            })?;
+        Some(base.join("nordvpn-tui").join("control.sock"))
+    }
+}
+
+/// Output of `nordvpn-tui watch`; the command line can override these
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WatchConfig {
+    /// Template with `{field}` placeholders, e.g. "{country_code} {city}"
+    pub format: String,
+    /// Template used instead while not connected
+    pub disconnected_format: Option<String>,
+    /// How often to poll `nordvpn status`
+    pub interval_secs: u64,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            format: "{status}".to_string(),
+            disconnected_format: None,
+            interval_secs: 5,
+        }
+    }
+}
+
+/// Desktop notifications sent by the app when the connection changes,
+/// separate from the daemon's own `notify` setting
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DesktopNotificationsConfig {
+    pub enabled: bool,
+    pub connected: bool,
+    pub disconnected: bool,
+    pub dropped: bool,
+    pub reconnected: bool,
+    pub connect_failed: bool,
+    pub gave_up: bool,
+    /// Minimum time between two notifications about the same kind of event
+    pub min_interval_secs: u64,
+}
+
+impl Default for DesktopNotificationsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            // Changes the user made themselves are shown in the app anyway
+            connected: false,
+            disconnected: false,
+            dropped: true,
+            reconnected: true,
+            connect_failed: true,
+            gave_up: true,
+            min_interval_secs: 30,
+        }
+    }
+}
+
+/// Shell commands run when the connection changes, with the status in
+/// `NORDVPN_*` environment variables
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    /// Before connecting; the connect waits for these
+    pub pre_connect: Vec<String>,
+    pub post_connect: Vec<String>,
+    /// After disconnecting on request
+    pub disconnect: Vec<String>,
+    /// After the tunnel went down on its own
+    pub unexpected_drop: Vec<String>,
+    /// After the server changed while staying connected
+    pub server_change: Vec<String>,
+    /// How long a hook may run before it is killed
+    pub timeout_secs: u64,
+}
+
+impl Default for HooksConfig {
+    fn default() -> Self {
+        Self {
+            pre_connect: Vec::new(),
+            post_connect: Vec::new(),
+            disconnect: Vec::new(),
+            unexpected_drop: Vec::new(),
+            server_change: Vec::new(),
+            timeout_secs: 30,
+        }
+    }
+}
+
+impl Config {
+    /// Location of the config file
+    pub fn path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(base.join("nordvpn-tui").join("config.toml"))
+    }
+
+    /// Load the config file, falling back to defaults if it does not exist
+    pub fn load() -> Result<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(&path)
+            .wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&contents).wrap_err_with(|| format!("Invalid config in {}", path.display()))
+    }
+
+    /// The `nordvpn` CLI to use; `$NORDVPN_BINARY` overrides the config file
+    pub fn nordvpn_binary(&self) -> Option<PathBuf> {
+        std::env::var_os("NORDVPN_BINARY")
+            .map(PathBuf::from)
+            .or_else(|| self.nordvpn_binary.clone())
+    }
+
+    /// Parse config file contents
+    pub fn parse(contents: &str) -> Result<Self> {
+        let config: Self = toml::from_str(contents)?;
+        for (name, profile) in &config.profiles {
+            profile
+                .validate()
+                .map_err(|e| eyre!("profile {:?}: {}", name, e))?;
+        }
+        for (i, rule) in config.agent.rules.iter().enumerate() {
+            rule.validate()
+                .and_then(|()| match &rule.profile {
+                    Some(name) if !config.profiles.contains_key(name) => {
+                        Err(format!("no profile named {:?}", name))
+                    }
+                    _ => Ok(()),
+                })
+                .map_err(|e| eyre!("agent rule {}: {}", i + 1, e))?;
+        }
+        Template::parse(&config.watch.format).map_err(|e| eyre!("watch.format: {}", e))?;
+        if let Some(format) = &config.watch.disconnected_format {
+            Template::parse(format).map_err(|e| eyre!("watch.disconnected_format: {}", e))?;
+        }
+        if config.hooks.timeout_secs == 0 {
+            return Err(eyre!("hooks.timeout_secs must be at least 1"));
+        }
+        Ok(config)
+    }
+}
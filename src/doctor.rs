@@ -0,0 +1,458 @@
+use crate::error::BackendError;
+#[cfg(feature = "grpc")]
+use crate::grpc;
+use crate::leak;
+use crate::nordvpn::{self, NordVPN};
+use crate::types::{ConnectionStatus, Settings};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::ToSocketAddrs;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::process::Command;
+
+/// Path of the socket `nordvpnd` listens on
+pub const DAEMON_SOCKET: &str = "/run/nordvpn/nordvpnd.sock";
+
+/// Interface names used by the NordVPN tunnel
+const TUNNEL_INTERFACES: &[&str] = &["nordlynx", "nordtun", "tun"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckStatus::Pass => write!(f, "PASS"),
+            CheckStatus::Warn => write!(f, "WARN"),
+            CheckStatus::Fail => write!(f, "FAIL"),
+        }
+    }
+}
+
+/// Outcome of a single diagnostic check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Run every diagnostic check in order.
+///
+/// Checks that only make sense while connected are reported as warnings
+/// when there is no active tunnel.
+pub fn run_checks(daemon_socket: &Path) -> Vec<Check> {
+    let status = NordVPN::get_status().ok();
+    let connected = matches!(status, Some(ConnectionStatus::Connected { .. }));
+    let settings = NordVPN::get_settings();
+
+    let mut checks = vec![check_binary(), check_daemon_socket(daemon_socket)];
+    #[cfg(feature = "grpc")]
+    checks.extend(check_grpc());
+    checks.extend([
+        check_group_membership(),
+        check_login(),
+        check_settings(&settings),
+        check_meshnet(&settings),
+        check_dns(connected),
+        check_default_route(connected),
+        check_ipv6_leak(connected),
+    ]);
+    checks
+}
+
+/// Plain-text report suitable for pasting into a bug report
+pub fn report(checks: &[Check]) -> String {
+    let mut out = format!("nordvpn-tui {} diagnostics\n", env!("CARGO_PKG_VERSION"));
+    for check in checks {
+        out.push_str(&format!(
+            "[{}] {}: {}\n",
+            check.status, check.name, check.detail
+        ));
+    }
+    out
+}
+
+/// Whether any check failed outright
+pub fn has_failures(checks: &[Check]) -> bool {
+    checks.iter().any(|c| c.status == CheckStatus::Fail)
+}
+
+fn check_binary() -> Check {
+    const NAME: &str = "nordvpn binary";
+    match NordVPN::version() {
+        Ok(version) => Check::new(NAME, CheckStatus::Pass, version),
+        Err(BackendError::BinaryNotFound) => Check::new(
+            NAME,
+            CheckStatus::Fail,
+            "not found on PATH; install from https://nordvpn.com/download/linux/",
+        ),
+        Err(e) => Check::new(NAME, CheckStatus::Warn, format!("version unknown: {}", e)),
+    }
+}
+
+fn check_daemon_socket(path: &Path) -> Check {
+    const NAME: &str = "daemon socket";
+    let socket = path.display();
+    match UnixStream::connect(path) {
+        Ok(_) => Check::new(NAME, CheckStatus::Pass, format!("{} reachable", socket)),
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => Check::new(
+            NAME,
+            CheckStatus::Fail,
+            format!("{}: permission denied (not in nordvpn group?)", socket),
+        ),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Check::new(
+            NAME,
+            CheckStatus::Fail,
+            format!(
+                "{} missing; start it with: sudo systemctl enable --now nordvpnd",
+                socket
+            ),
+        ),
+        Err(e) => Check::new(NAME, CheckStatus::Fail, format!("{}: {}", socket, e)),
+    }
+}
+
+/// Whether the gRPC backend, when enabled, can actually be used
+#[cfg(feature = "grpc")]
+fn check_grpc() -> Option<Check> {
+    const NAME: &str = "grpc backend";
+    let socket = NordVPN::grpc_socket()?;
+    Some(match grpc::status(&socket) {
+        Ok(_) => Check::new(
+            NAME,
+            CheckStatus::Pass,
+            format!("{} answering", socket.display()),
+        ),
+        Err(grpc::Error::Unavailable(reason)) => Check::new(
+            NAME,
+            CheckStatus::Warn,
+            format!("{}: {}; falling back to the CLI", socket.display(), reason),
+        ),
+        Err(grpc::Error::Backend(e)) => Check::new(NAME, CheckStatus::Warn, e.to_string()),
+    })
+}
+
+fn check_group_membership() -> Check {
+    const NAME: &str = "nordvpn group";
+    let user = current_user();
+    if user.as_deref() == Some("root") {
+        return Check::new(NAME, CheckStatus::Pass, "running as root");
+    }
+
+    let active = command_stdout("id", &["-Gn"])
+        .map(|groups| groups.split_whitespace().any(|g| g == "nordvpn"))
+        .unwrap_or(false);
+    if active {
+        return Check::new(
+            NAME,
+            CheckStatus::Pass,
+            "current session is in nordvpn group",
+        );
+    }
+
+    let group_line = fs::read_to_string("/etc/group").ok().and_then(|groups| {
+        groups
+            .lines()
+            .find(|line| line.starts_with("nordvpn:"))
+            .map(str::to_string)
+    });
+    let configured = match (&group_line, &user) {
+        (Some(line), Some(user)) => line
+            .rsplit(':')
+            .next()
+            .map(|members| members.split(',').any(|m| m == user))
+            .unwrap_or(false),
+        _ => false,
+    };
+
+    match (group_line, configured) {
+        (None, _) => Check::new(NAME, CheckStatus::Fail, "nordvpn group does not exist"),
+        (Some(_), true) => Check::new(
+            NAME,
+            CheckStatus::Warn,
+            "added to nordvpn group but not active yet; log out and back in",
+        ),
+        (Some(_), false) => Check::new(
+            NAME,
+            CheckStatus::Fail,
+            "not a member; run: sudo usermod -aG nordvpn $USER",
+        ),
+    }
+}
+
+fn check_login() -> Check {
+    const NAME: &str = "login";
+    match NordVPN::account() {
+        Ok(account) => {
+            let email = account
+                .email
+                .map(|email| format!("logged in as {}", email))
+                .unwrap_or_else(|| "logged in".to_string());
+            Check::new(NAME, CheckStatus::Pass, email)
+        }
+        Err(BackendError::NotLoggedIn) => {
+            Check::new(NAME, CheckStatus::Fail, "not logged in; run: nordvpn login")
+        }
+        Err(e) => Check::new(NAME, CheckStatus::Warn, format!("could not check: {}", e)),
+    }
+}
+
+fn check_settings(settings: &nordvpn::Result<Settings>) -> Check {
+    const NAME: &str = "settings";
+    let settings = match settings {
+        Ok(settings) => settings,
+        Err(e) => return Check::new(NAME, CheckStatus::Warn, format!("could not read: {}", e)),
+    };
+    let mut summary = vec![];
+    for key in ["Technology", "Protocol", "Kill Switch", "Firewall"] {
+        if let Some(value) = settings.get(key) {
+            summary.push(format!("{} {}", key.to_lowercase(), value));
+        }
+    }
+    Check::new(NAME, CheckStatus::Pass, summary.join(", "))
+}
+
+fn check_meshnet(settings: &nordvpn::Result<Settings>) -> Check {
+    const NAME: &str = "meshnet";
+    if !matches!(settings, Ok(settings) if settings.is_enabled("Meshnet") == Some(true)) {
+        return Check::new(NAME, CheckStatus::Pass, "disabled");
+    }
+    match NordVPN::meshnet_peers() {
+        Ok(peers) => {
+            let online = peers.iter().filter(|peer| peer.connected).count();
+            Check::new(
+                NAME,
+                CheckStatus::Pass,
+                format!("enabled, {} of {} peers connected", online, peers.len()),
+            )
+        }
+        Err(e) => Check::new(
+            NAME,
+            CheckStatus::Warn,
+            format!("enabled, but peers could not be listed: {}", e),
+        ),
+    }
+}
+
+fn check_dns(connected: bool) -> Check {
+    const NAME: &str = "DNS in tunnel";
+    if !connected {
+        return Check::new(NAME, CheckStatus::Warn, "not connected; skipped");
+    }
+
+    let resolvers: Vec<String> = leak::system_resolvers()
+        .into_iter()
+        .filter(|server| !is_loopback(server))
+        .collect();
+    let routes: Vec<(String, Option<String>)> = resolvers
+        .into_iter()
+        .map(|server| {
+            let device = route_device(&["route", "get", &server]);
+            (server, device)
+        })
+        .collect();
+
+    let (status, detail) = dns_routes_verdict(&routes);
+    if status != CheckStatus::Pass {
+        return Check::new(NAME, status, detail);
+    }
+    match ("nordvpn.com", 443).to_socket_addrs() {
+        Ok(_) => Check::new(NAME, CheckStatus::Pass, detail),
+        Err(e) => Check::new(
+            NAME,
+            CheckStatus::Fail,
+            format!("cannot resolve nordvpn.com: {} ({})", e, detail),
+        ),
+    }
+}
+
+/// Judge resolvers by the device that traffic to each leaves through
+fn dns_routes_verdict(routes: &[(String, Option<String>)]) -> (CheckStatus, String) {
+    if routes.is_empty() {
+        return (CheckStatus::Warn, "no resolvers found".to_string());
+    }
+    let outside: Vec<String> = routes
+        .iter()
+        .filter_map(|(server, device)| match device {
+            Some(device) if !is_tunnel(device) => Some(format!("{} via {}", server, device)),
+            _ => None,
+        })
+        .collect();
+    if !outside.is_empty() {
+        return (
+            CheckStatus::Fail,
+            format!("resolvers outside the tunnel: {}", outside.join(", ")),
+        );
+    }
+    let unknown: Vec<&str> = routes
+        .iter()
+        .filter(|(_, device)| device.is_none())
+        .map(|(server, _)| server.as_str())
+        .collect();
+    if !unknown.is_empty() {
+        return (
+            CheckStatus::Warn,
+            format!("could not determine route to {}", unknown.join(", ")),
+        );
+    }
+    let servers: Vec<String> = routes
+        .iter()
+        .map(|(server, device)| format!("{} via {}", server, device.as_deref().unwrap_or("")))
+        .collect();
+    (CheckStatus::Pass, servers.join(", "))
+}
+
+fn is_loopback(server: &str) -> bool {
+    server
+        .parse::<std::net::IpAddr>()
+        .is_ok_and(|ip| ip.is_loopback())
+}
+
+fn check_default_route(connected: bool) -> Check {
+    const NAME: &str = "default route";
+    if !connected {
+        return Check::new(NAME, CheckStatus::Warn, "not connected; skipped");
+    }
+
+    match route_device(&["route", "get", "1.1.1.1"]) {
+        Some(dev) if is_tunnel(&dev) => Check::new(
+            NAME,
+            CheckStatus::Pass,
+            format!("via tunnel interface {}", dev),
+        ),
+        Some(dev) => Check::new(
+            NAME,
+            CheckStatus::Fail,
+            format!("traffic leaves via {} instead of the tunnel", dev),
+        ),
+        None => Check::new(NAME, CheckStatus::Warn, "could not determine route"),
+    }
+}
+
+fn check_ipv6_leak(connected: bool) -> Check {
+    const NAME: &str = "IPv6 leak";
+    match fs::read_to_string("/proc/sys/net/ipv6/conf/all/disable_ipv6") {
+        Ok(value) if value.trim() == "1" => {
+            return Check::new(NAME, CheckStatus::Pass, "IPv6 is disabled");
+        }
+        Ok(_) => {}
+        Err(e) => {
+            return Check::new(
+                NAME,
+                CheckStatus::Warn,
+                format!("cannot tell whether IPv6 is disabled: {}", e),
+            );
+        }
+    }
+    if !connected {
+        return Check::new(NAME, CheckStatus::Warn, "not connected; skipped");
+    }
+
+    match route_device(&["-6", "route", "get", "2001:4860:4860::8888"]) {
+        Some(dev) if is_tunnel(&dev) => {
+            Check::new(NAME, CheckStatus::Pass, format!("IPv6 via tunnel {}", dev))
+        }
+        Some(dev) => Check::new(
+            NAME,
+            CheckStatus::Fail,
+            format!("IPv6 traffic bypasses the tunnel via {}", dev),
+        ),
+        None => Check::new(NAME, CheckStatus::Pass, "no IPv6 route outside the tunnel"),
+    }
+}
+
+/// Ask `ip` which device a packet would leave through
+fn route_device(args: &[&str]) -> Option<String> {
+    let out = command_stdout("ip", args)?;
+    let mut words = out.split_whitespace();
+    while let Some(word) = words.next() {
+        if word == "dev" {
+            return words.next().map(str::to_string);
+        }
+    }
+    None
+}
+
+fn is_tunnel(dev: &str) -> bool {
+    TUNNEL_INTERFACES.iter().any(|t| dev.starts_with(t))
+}
+
+fn current_user() -> Option<String> {
+    std::env::var("USER")
+        .ok()
+        .or_else(|| command_stdout("id", &["-un"]).map(|u| u.trim().to_string()))
+}
+
+fn command_stdout(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+
+    #[test]
+    fn probes_configured_daemon_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nordvpnd.sock");
+        let check = check_daemon_socket(&path);
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(
+            check
+                .detail
+                .starts_with(&format!("{} missing", path.display()))
+        );
+
+        let _listener = UnixListener::bind(&path).unwrap();
+        let check = check_daemon_socket(&path);
+        assert_eq!(check.status, CheckStatus::Pass);
+        assert_eq!(check.detail, format!("{} reachable", path.display()));
+    }
+
+    #[test]
+    fn judges_resolvers_by_route() {
+        let route =
+            |server: &str, device: Option<&str>| (server.to_string(), device.map(str::to_string));
+        assert_eq!(
+            dns_routes_verdict(&[route("103.86.96.100", Some("nordlynx"))]),
+            (CheckStatus::Pass, "103.86.96.100 via nordlynx".to_string())
+        );
+        assert_eq!(
+            dns_routes_verdict(&[
+                route("103.86.96.100", Some("nordlynx")),
+                route("192.168.1.1", Some("wlp3s0")),
+            ]),
+            (
+                CheckStatus::Fail,
+                "resolvers outside the tunnel: 192.168.1.1 via wlp3s0".to_string()
+            )
+        );
+        assert_eq!(
+            dns_routes_verdict(&[route("10.0.0.1", None)]).0,
+            CheckStatus::Warn
+        );
+        assert_eq!(dns_routes_verdict(&[]).0, CheckStatus::Warn);
+    }
+}
@@ -0,0 +1,271 @@
+use crate::parse;
+use std::fmt;
+use std::io;
+use std::process::Output;
+
+/// Errors produced when talking to the nordvpn CLI, classified so the UI
+/// can react to the cause instead of just printing text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackendError {
+    /// The `nordvpn` executable could not be found on `PATH`
+    BinaryNotFound,
+    /// The CLI is installed but cannot reach `nordvpnd`
+    DaemonUnavailable(String),
+    /// The daemon is running but no account is logged in
+    NotLoggedIn,
+    /// The current user may not access the daemon socket
+    PermissionDenied(String),
+    /// The requested country, city, group or server does not exist
+    InvalidTarget(String),
+    /// The process could not be spawned for another reason
+    Io(String),
+    /// Any other non-zero exit
+    Command {
+        action: &'static str,
+        code: Option<i32>,
+        message: String,
+    },
+}
+
+impl BackendError {
+    /// Classify a failed spawn of the `nordvpn` process.
+    pub fn from_io(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound => BackendError::BinaryNotFound,
+            io::ErrorKind::PermissionDenied => BackendError::PermissionDenied(err.to_string()),
+            _ => BackendError::Io(err.to_string()),
+        }
+    }
+
+    /// Classify a finished `nordvpn` process that exited unsuccessfully.
+    ///
+    /// The CLI prints most errors on stdout, so both streams are inspected.
+    pub fn from_output(action: &'static str, output: &Output) -> Self {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let message = if stderr.trim().is_empty() {
+            parse::clean(&stdout)
+        } else {
+            parse::clean(&stderr)
+        };
+        Self::classify(action, output.status.code(), message)
+    }
+
+    /// Classify an error from the exit code and message printed by the CLI.
+    pub fn classify(action: &'static str, code: Option<i32>, message: String) -> Self {
+        let lower = message.to_lowercase();
+
+        if lower.contains("cannot reach system daemon")
+            || lower.contains("nordvpnd.sock: connect: no such file")
+            || lower.contains("connection refused")
+        {
+            BackendError::DaemonUnavailable(message)
+        } else if lower.contains("permission denied") {
+            BackendError::PermissionDenied(message)
+        } else if lower.contains("not logged in") || lower.contains("please log in") {
+            BackendError::NotLoggedIn
+        } else if action == "connect"
+            && (lower.contains("does not exist")
+                || lower.contains("doesn't exist")
+                || lower.contains("not available")
+                || lower.contains("invalid"))
+        {
+            BackendError::InvalidTarget(message)
+        } else if code == Some(127) {
+            BackendError::BinaryNotFound
+        } else {
+            BackendError::Command {
+                action,
+                code,
+                message,
+            }
+        }
+    }
+
+    /// A short, actionable remediation for this error, if there is one.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            BackendError::BinaryNotFound => {
+                Some("Install NordVPN: https://nordvpn.com/download/linux/")
+            }
+            BackendError::DaemonUnavailable(_) => {
+                Some("Start the daemon: sudo systemctl enable --now nordvpnd")
+            }
+            BackendError::NotLoggedIn => Some("Log in first: nordvpn login"),
+            BackendError::PermissionDenied(_) => Some(
+                "Add yourself to the nordvpn group: sudo usermod -aG nordvpn $USER, then log in again",
+            ),
+            BackendError::InvalidTarget(_) => {
+                Some("Check the name against `nordvpn countries` or `nordvpn groups`")
+            }
+            BackendError::Io(_) | BackendError::Command { .. } => None,
+        }
+    }
+
+    /// Step-by-step advice shown when the daemon cannot be used at all:
+    /// the hint, then how to check it worked.
+    pub fn advice(&self) -> Vec<&'static str> {
+        let Some(hint) = self.hint() else {
+            return vec![
+                "Make sure NordVPN is installed: https://nordvpn.com/download/linux/",
+                "Make sure the nordvpn daemon is running",
+                "Make sure you are logged in: nordvpn login",
+            ];
+        };
+        let check = match self {
+            BackendError::BinaryNotFound => "Then check it is on your PATH: which nordvpn",
+            BackendError::DaemonUnavailable(_) => "Check its state: systemctl status nordvpnd",
+            BackendError::PermissionDenied(_) => "Check the group is active: id -Gn",
+            BackendError::NotLoggedIn => "Check your account: nordvpn account",
+            _ => return vec![hint],
+        };
+        vec![hint, check]
+    }
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendError::BinaryNotFound => write!(f, "nordvpn executable not found"),
+            BackendError::DaemonUnavailable(msg) => {
+                write!(f, "NordVPN daemon is not reachable: {}", msg)
+            }
+            BackendError::NotLoggedIn => write!(f, "You are not logged in to NordVPN"),
+            BackendError::PermissionDenied(msg) => write!(f, "Permission denied: {}", msg),
+            BackendError::InvalidTarget(msg) => write!(f, "Invalid target: {}", msg),
+            BackendError::Io(msg) => write!(f, "Failed to run nordvpn: {}", msg),
+            BackendError::Command {
+                action,
+                code,
+                message,
+            } => {
+                if message.is_empty() {
+                    write!(f, "nordvpn {} failed", action)?;
+                } else {
+                    write!(f, "{}", message)?;
+                }
+                if let Some(code) = code {
+                    write!(f, " (exit code {})", code)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for BackendError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::ExitStatus;
+
+    #[test]
+    fn advice_starts_with_hint() {
+        for error in [
+            BackendError::BinaryNotFound,
+            BackendError::DaemonUnavailable(String::new()),
+            BackendError::NotLoggedIn,
+            BackendError::PermissionDenied(String::new()),
+        ] {
+            let advice = error.advice();
+            assert_eq!(advice.first().copied(), error.hint());
+            assert_eq!(advice.len(), 2);
+        }
+        assert_eq!(BackendError::Io(String::new()).advice().len(), 3);
+    }
+
+    #[test]
+    fn classifies_cli_failures() {
+        let command = |action, code: Option<i32>, message: &str| BackendError::Command {
+            action,
+            code,
+            message: message.to_string(),
+        };
+        let cases = [
+            (
+                "status",
+                Some(1),
+                "Whoops! Cannot reach System Daemon.",
+                BackendError::DaemonUnavailable("Whoops! Cannot reach System Daemon.".to_string()),
+            ),
+            (
+                "countries",
+                Some(1),
+                "dial unix /run/nordvpn/nordvpnd.sock: connect: permission denied",
+                BackendError::PermissionDenied(
+                    "dial unix /run/nordvpn/nordvpnd.sock: connect: permission denied".to_string(),
+                ),
+            ),
+            (
+                "connect",
+                Some(1),
+                "You are not logged in.",
+                BackendError::NotLoggedIn,
+            ),
+            (
+                "connect",
+                Some(1),
+                "The specified server does not exist.",
+                BackendError::InvalidTarget("The specified server does not exist.".to_string()),
+            ),
+            // Only a connect has a target to blame
+            (
+                "set",
+                Some(1),
+                "The specified server does not exist.",
+                command("set", Some(1), "The specified server does not exist."),
+            ),
+            ("status", Some(127), "", BackendError::BinaryNotFound),
+            (
+                "disconnect",
+                Some(2),
+                "Something went wrong.",
+                command("disconnect", Some(2), "Something went wrong."),
+            ),
+        ];
+        for (action, code, message, expected) in cases {
+            assert_eq!(
+                BackendError::classify(action, code, message.to_string()),
+                expected,
+                "{action} {code:?} {message:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn prefers_stderr_and_cleans_output() {
+        let output = |stdout: &str, stderr: &str| Output {
+            status: ExitStatus::from_raw(1 << 8),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: stderr.as_bytes().to_vec(),
+        };
+        assert_eq!(
+            BackendError::from_output("login", &output("\r-\r  \rYou are not logged in.\n", "")),
+            BackendError::NotLoggedIn
+        );
+        assert_eq!(
+            BackendError::from_output("status", &output("ignored", "connection refused")),
+            BackendError::DaemonUnavailable("connection refused".to_string())
+        );
+        assert_eq!(
+            BackendError::from_io(io::Error::from(io::ErrorKind::NotFound)),
+            BackendError::BinaryNotFound
+        );
+    }
+
+    #[test]
+    fn hints_only_for_actionable_errors() {
+        assert!(BackendError::BinaryNotFound.hint().is_some());
+        assert!(BackendError::NotLoggedIn.hint().is_some());
+        assert!(BackendError::Io("broken pipe".to_string()).hint().is_none());
+        let command = BackendError::Command {
+            action: "connect",
+            code: Some(1),
+            message: String::new(),
+        };
+        assert!(command.hint().is_none());
+        assert_eq!(command.to_string(), "nordvpn connect failed (exit code 1)");
+    }
+}
@@ -0,0 +1,198 @@
+use crate::config::LeakTestConfig;
+use crate::doctor::{self, Check};
+use crate::error::BackendError;
+use crate::hooks::{HookJob, HookRun, HookRunner};
+use crate::ipc::Request;
+use crate::leak::{self, LeakReport};
+use crate::nordvpn::{ConnectCancel, NordVPN};
+use crate::servers::{self, Recommendation};
+use crate::types::{ConnectionStatus, Country, Target};
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Delay before the first retry when the daemon is unavailable
+pub const RETRY_INITIAL: Duration = Duration::from_secs(1);
+/// Upper bound for the retry backoff
+pub const RETRY_MAX: Duration = Duration::from_secs(30);
+/// How long to wait for the recommended servers
+const RECOMMENDATIONS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What the app loads once the daemon answers
+#[derive(Debug)]
+pub struct DaemonReady {
+    pub countries: Vec<Country>,
+    pub groups: Vec<String>,
+    pub status: ConnectionStatus,
+}
+
+/// Events produced by background work and delivered to the app's main loop
+#[derive(Debug)]
+pub enum AppEvent {
+    /// Outcome of a background attempt to reach the daemon
+    DaemonRetry {
+        attempt: u32,
+        result: Result<DaemonReady, BackendError>,
+        next_retry: Duration,
+    },
+    /// Results of a diagnostics run
+    Diagnostics(Vec<Check>),
+    /// Outcome of the post-connect leak test
+    LeakTest(Result<LeakReport, String>),
+    /// Periodic background status poll
+    StatusPolled {
+        /// When the poll was started
+        sampled_at: Instant,
+        result: Result<ConnectionStatus, BackendError>,
+    },
+    /// A line of progress printed while connecting
+    ConnectProgress(String),
+    /// Outcome of a connect started by the user
+    Connected {
+        target: Target,
+        /// Connect id, to ignore connects cancelled meanwhile
+        id: u64,
+        result: Result<ConnectionStatus, BackendError>,
+    },
+    /// Recommended servers fetched for the server search
+    Recommendations(Result<Vec<Recommendation>, String>),
+    /// Outcome of a watchdog reconnect attempt
+    Reconnect {
+        target: Target,
+        attempt: u32,
+        /// Watchdog attempt id, to ignore attempts cancelled meanwhile
+        id: u64,
+        result: Result<ConnectionStatus, BackendError>,
+    },
+    /// A request from a control socket client, answered on `reply`
+    Control {
+        request: Result<Request, String>,
+        reply: Sender<String>,
+    },
+    /// A hook command finished
+    HookFinished(HookRun),
+    /// A desktop notification could not be sent
+    #[cfg_attr(not(feature = "notifications"), allow(dead_code))]
+    NotificationFailed(String),
+}
+
+/// Retry loading countries with exponential backoff until the daemon answers.
+///
+/// Stops after the first success or once the receiving app has gone away.
+pub fn spawn_daemon_retry(tx: Sender<AppEvent>) {
+    thread::spawn(move || {
+        let mut delay = RETRY_INITIAL;
+        let mut attempt = 0;
+        loop {
+            thread::sleep(delay);
+            attempt += 1;
+            // Everything the UI needs is fetched here, off its thread
+            let result = NordVPN::get_countries().map(|countries| DaemonReady {
+                countries,
+                groups: NordVPN::get_groups().unwrap_or_default(),
+                status: NordVPN::get_status().unwrap_or(ConnectionStatus::Disconnected),
+            });
+            let done = result.is_ok();
+            delay = (delay * 2).min(RETRY_MAX);
+            let event = AppEvent::DaemonRetry {
+                attempt,
+                result,
+                next_retry: delay,
+            };
+            if tx.send(event).is_err() || done {
+                break;
+            }
+        }
+    });
+}
+
+/// Run the diagnostics checks off the UI thread
+pub fn spawn_diagnostics(tx: Sender<AppEvent>, daemon_socket: PathBuf) {
+    thread::spawn(move || {
+        let _ = tx.send(AppEvent::Diagnostics(doctor::run_checks(&daemon_socket)));
+    });
+}
+
+/// Verify the public IP and resolvers after connecting.
+///
+/// Without an expected IP, the one reported by `nordvpn status` is used.
+pub fn spawn_leak_test(tx: Sender<AppEvent>, config: LeakTestConfig, expected_ip: Option<String>) {
+    thread::spawn(move || {
+        let expected_ip = expected_ip.or_else(|| match NordVPN::get_status() {
+            Ok(ConnectionStatus::Connected { ip, .. }) => ip,
+            _ => None,
+        });
+        let _ = tx.send(AppEvent::LeakTest(leak::run(&config, expected_ip)));
+    });
+}
+
+/// Refresh the recommended servers the server search completes from
+pub fn spawn_recommendations(tx: Sender<AppEvent>, url: String) {
+    thread::spawn(move || {
+        let result = servers::fetch(&url, RECOMMENDATIONS_TIMEOUT);
+        let _ = tx.send(AppEvent::Recommendations(result));
+    });
+}
+
+/// Poll the connection status at a fixed interval until the app goes away
+pub fn spawn_status_poller(tx: Sender<AppEvent>, interval: Duration) {
+    thread::spawn(move || {
+        loop {
+            thread::sleep(interval);
+            let sampled_at = Instant::now();
+            let result = NordVPN::get_status();
+            if tx
+                .send(AppEvent::StatusPolled { sampled_at, result })
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+}
+
+/// Connect to a target, streaming the CLI's progress back to the app,
+/// after running the pre-connect hooks if any
+pub fn spawn_connect(
+    tx: Sender<AppEvent>,
+    target: Target,
+    id: u64,
+    cancel: ConnectCancel,
+    pre_connect: Option<(HookRunner, Vec<HookJob>)>,
+) {
+    thread::spawn(move || {
+        if let Some((runner, jobs)) = pre_connect {
+            runner.run(jobs);
+        }
+        let result = NordVPN::connect_cancellable(&target, &cancel, |line| {
+            let _ = tx.send(AppEvent::ConnectProgress(line.to_string()));
+        });
+        let _ = tx.send(AppEvent::Connected { target, id, result });
+    });
+}
+
+/// Reconnect to a target on behalf of the watchdog
+pub fn spawn_reconnect(
+    tx: Sender<AppEvent>,
+    target: Target,
+    attempt: u32,
+    id: u64,
+    cancel: ConnectCancel,
+    pre_connect: Option<(HookRunner, Vec<HookJob>)>,
+) {
+    thread::spawn(move || {
+        if let Some((runner, jobs)) = pre_connect {
+            runner.run(jobs);
+        }
+        let result = NordVPN::connect_cancellable(&target, &cancel, |line| {
+            let _ = tx.send(AppEvent::ConnectProgress(line.to_string()));
+        });
+        let _ = tx.send(AppEvent::Reconnect {
+            target,
+            attempt,
+            id,
+            result,
+        });
+    });
+}
@@ -0,0 +1,463 @@
+//! Client for the gRPC API nordvpnd serves on its unix socket.
+//!
+//! Talking to the daemon directly avoids spawning the CLI for every status
+//! poll and parsing its text output. Only the hot operations are covered;
+//! everything else still goes through the CLI.
+
+use crate::error::BackendError;
+use crate::types::{ConnectionStatus, Country, Target};
+use hyper_util::rt::TokioIo;
+use pb::daemon_client::DaemonClient;
+use std::future::Future;
+use std::path::Path;
+use tokio::net::UnixStream;
+use tonic::Code;
+use tonic::transport::{Channel, Endpoint, Uri};
+
+pub mod pb {
+    tonic::include_proto!("pb");
+}
+
+/// Enums the daemon shares with its settings, in their own package upstream
+// The variant names come from upstream
+#[allow(clippy::enum_variant_names)]
+pub mod config {
+    tonic::include_proto!("config");
+}
+
+/// Why a call over the socket did not produce a result
+#[derive(Debug)]
+pub enum Error {
+    /// The socket or the call is unusable, so the CLI should be tried
+    Unavailable(String),
+    /// The daemon handled the call and reported a failure
+    Backend(BackendError),
+}
+
+impl Error {
+    /// Classify a failed call. Only a daemon that cannot serve it makes the
+    /// CLI worth trying; anything else is the daemon's answer.
+    fn from_status(action: &'static str, status: tonic::Status) -> Self {
+        match status.code() {
+            Code::Unavailable | Code::Unimplemented => {
+                Error::Unavailable(status.message().to_string())
+            }
+            _ if is_transport_error(&status) => Error::Unavailable(status.message().to_string()),
+            _ => Error::Backend(BackendError::classify(
+                action,
+                None,
+                status.message().to_string(),
+            )),
+        }
+    }
+}
+
+/// Whether the call failed below gRPC, on the socket or the connection
+fn is_transport_error(status: &tonic::Status) -> bool {
+    let mut source = std::error::Error::source(status);
+    while let Some(err) = source {
+        if err.is::<std::io::Error>() || err.is::<tonic::transport::Error>() {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Get current connection status
+pub fn status(socket: &Path) -> Result<ConnectionStatus> {
+    call(socket, |mut client| async move {
+        let response = client
+            .status(pb::Empty {})
+            .await
+            .map_err(|e| Error::from_status("status", e))?
+            .into_inner();
+        Ok(connection_status(response))
+    })
+}
+
+/// Get list of available countries
+pub fn countries(socket: &Path) -> Result<Vec<Country>> {
+    call(socket, |mut client| async move {
+        let list = client
+            .countries(pb::Empty {})
+            .await
+            .map_err(|e| Error::from_status("countries", e))?
+            .into_inner();
+        Ok(list
+            .servers
+            .into_iter()
+            .map(|group| Country::new(group.name))
+            .collect())
+    })
+}
+
+/// Connect to a target, passing on the daemon's progress messages, and
+/// return the resulting status
+pub fn connect(
+    socket: &Path,
+    target: &Target,
+    on_line: &mut dyn FnMut(&str),
+) -> Result<ConnectionStatus> {
+    let request = match target {
+        Target::Group(group) => pb::ConnectRequest {
+            server_tag: String::new(),
+            server_group: group.clone(),
+        },
+        _ => pb::ConnectRequest {
+            server_tag: target.connect_args().join(" "),
+            server_group: String::new(),
+        },
+    };
+    call(socket, |mut client| async move {
+        let mut stream = client
+            .connect(request)
+            .await
+            .map_err(|e| Error::from_status("connect", e))?
+            .into_inner();
+        let mut last = None;
+        // Once the daemon has started connecting, retrying through the CLI
+        // would connect a second time
+        while let Some(payload) = stream.message().await.map_err(|e| match last {
+            Some(_) => Error::Backend(BackendError::classify(
+                "connect",
+                None,
+                e.message().to_string(),
+            )),
+            None => Error::from_status("connect", e),
+        })? {
+            for line in &payload.data {
+                on_line(line);
+            }
+            last = Some(payload);
+        }
+
+        // The payload codes are internal to the daemon, so judge the
+        // outcome by the state it ends up in
+        let status = client
+            .status(pb::Empty {})
+            .await
+            .map_err(|e| Error::from_status("connect", e))?
+            .into_inner();
+        if status.state() == pb::ConnectionState::Connected {
+            return Ok(connection_status(status));
+        }
+        let message = match last {
+            Some(payload) if !payload.data.is_empty() => payload.data.join(" "),
+            Some(payload) => format!("daemon reported code {}", payload.r#type),
+            None => "daemon did not connect".to_string(),
+        };
+        Err(Error::Backend(BackendError::classify(
+            "connect", None, message,
+        )))
+    })
+}
+
+/// Disconnect from VPN
+pub fn disconnect(socket: &Path) -> Result<()> {
+    call(socket, |mut client| async move {
+        let mut stream = client
+            .disconnect(pb::Empty {})
+            .await
+            .map_err(|e| Error::from_status("disconnect", e))?
+            .into_inner();
+        while stream
+            .message()
+            .await
+            .map_err(|e| Error::from_status("disconnect", e))?
+            .is_some()
+        {}
+
+        let status = client
+            .status(pb::Empty {})
+            .await
+            .map_err(|e| Error::from_status("disconnect", e))?
+            .into_inner();
+        match status.state() {
+            pb::ConnectionState::Disconnected => Ok(()),
+            state => Err(Error::Backend(BackendError::Command {
+                action: "disconnect",
+                code: None,
+                message: format!(
+                    "still {} after disconnecting",
+                    state.as_str_name().to_lowercase()
+                ),
+            })),
+        }
+    })
+}
+
+fn connection_status(response: pb::StatusResponse) -> ConnectionStatus {
+    let non_empty = |value: String| (!value.is_empty()).then_some(value);
+    match response.state() {
+        pb::ConnectionState::Connected => ConnectionStatus::Connected {
+            country: response.country,
+            city: non_empty(response.city),
+            server: non_empty(response.hostname),
+            ip: non_empty(response.ip),
+        },
+        pb::ConnectionState::Connecting => ConnectionStatus::Connecting,
+        pb::ConnectionState::Disconnected | pb::ConnectionState::UnknownState => {
+            ConnectionStatus::Disconnected
+        }
+    }
+}
+
+/// Run one request against the daemon on a short-lived runtime.
+///
+/// The rest of the app is synchronous, and a call per runtime keeps this
+/// self-contained; it is still far cheaper than spawning the CLI.
+fn call<T, F, Fut>(socket: &Path, request: F) -> Result<T>
+where
+    F: FnOnce(DaemonClient<Channel>) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| Error::Unavailable(e.to_string()))?;
+    let socket = socket.to_path_buf();
+
+    runtime.block_on(async move {
+        // The URI is required but unused, the connector picks the socket
+        let channel =
+            Endpoint::from_static("http://nordvpnd")
+                .connect_with_connector(tower::service_fn(move |_: Uri| {
+                    let socket = socket.clone();
+                    async move {
+                        Ok::<_, std::io::Error>(TokioIo::new(UnixStream::connect(socket).await?))
+                    }
+                }))
+                .await
+                .map_err(|e| Error::Unavailable(e.to_string()))?;
+        request(DaemonClient::new(channel)).await
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nordvpn::NordVPN;
+    use crate::testing::FakeNordVPN;
+    use pb::daemon_server::{Daemon, DaemonServer};
+    use std::sync::Mutex;
+    use tempfile::TempDir;
+    use tokio_stream::wrappers::UnixListenerStream;
+    use tonic::{Request, Response, Status};
+
+    type PayloadStream =
+        tokio_stream::Iter<std::vec::IntoIter<std::result::Result<pb::Payload, Status>>>;
+
+    /// In-process stand-in for nordvpnd
+    #[derive(Default)]
+    struct Stub {
+        /// Country connected to, if any
+        connected: Mutex<Option<String>>,
+    }
+
+    #[tonic::async_trait]
+    impl Daemon for Stub {
+        async fn countries(
+            &self,
+            _: Request<pb::Empty>,
+        ) -> std::result::Result<Response<pb::ServerGroupsList>, Status> {
+            let servers = ["Germany", "United_States"]
+                .into_iter()
+                .map(|name| pb::ServerGroup {
+                    name: name.to_string(),
+                    virtual_location: false,
+                })
+                .collect();
+            Ok(Response::new(pb::ServerGroupsList {
+                r#type: 1000,
+                servers,
+            }))
+        }
+
+        type ConnectStream = PayloadStream;
+
+        async fn connect(
+            &self,
+            request: Request<pb::ConnectRequest>,
+        ) -> std::result::Result<Response<PayloadStream>, Status> {
+            let tag = request.into_inner().server_tag;
+            if tag == "Broken" {
+                return Err(Status::internal("failed to configure the tunnel"));
+            }
+            let payloads = if tag == "Germany" {
+                *self.connected.lock().unwrap() = Some(tag);
+                vec![Ok(pb::Payload {
+                    r#type: 1000,
+                    data: vec!["Germany #1045".to_string()],
+                })]
+            } else {
+                vec![Ok(pb::Payload {
+                    r#type: 3001,
+                    data: vec!["The specified server does not exist.".to_string()],
+                })]
+            };
+            Ok(Response::new(tokio_stream::iter(payloads)))
+        }
+
+        type DisconnectStream = PayloadStream;
+
+        async fn disconnect(
+            &self,
+            _: Request<pb::Empty>,
+        ) -> std::result::Result<Response<PayloadStream>, Status> {
+            *self.connected.lock().unwrap() = None;
+            Ok(Response::new(tokio_stream::iter(vec![])))
+        }
+
+        async fn status(
+            &self,
+            _: Request<pb::Empty>,
+        ) -> std::result::Result<Response<pb::StatusResponse>, Status> {
+            let response = match self.connected.lock().unwrap().clone() {
+                Some(country) => pb::StatusResponse {
+                    state: pb::ConnectionState::Connected.into(),
+                    technology: config::Technology::Nordlynx.into(),
+                    protocol: config::Protocol::Udp.into(),
+                    hostname: "de1045.nordvpn.com".to_string(),
+                    ip: "185.130.184.86".to_string(),
+                    country,
+                    city: "Frankfurt".to_string(),
+                    ..Default::default()
+                },
+                None => pb::StatusResponse {
+                    state: pb::ConnectionState::Disconnected.into(),
+                    ..Default::default()
+                },
+            };
+            Ok(Response::new(response))
+        }
+    }
+
+    /// Serve a fresh stub on a socket in a temporary directory
+    fn serve() -> (TempDir, std::path::PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("nordvpnd.sock");
+        let listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+        listener.set_nonblocking(true).unwrap();
+
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            runtime.block_on(async move {
+                let listener = tokio::net::UnixListener::from_std(listener).unwrap();
+                tonic::transport::Server::builder()
+                    .add_service(DaemonServer::new(Stub::default()))
+                    .serve_with_incoming(UnixListenerStream::new(listener))
+                    .await
+                    .unwrap();
+            });
+        });
+        (dir, socket)
+    }
+
+    #[test]
+    fn status_and_countries() {
+        let (_dir, socket) = serve();
+
+        assert_eq!(status(&socket).unwrap(), ConnectionStatus::Disconnected);
+        let names: Vec<_> = countries(&socket)
+            .unwrap()
+            .into_iter()
+            .map(|country| country.display_name)
+            .collect();
+        assert_eq!(names, ["Germany", "United States"]);
+    }
+
+    #[test]
+    fn connect_and_disconnect() {
+        let (_dir, socket) = serve();
+
+        let mut lines = vec![];
+        let connected = connect(
+            &socket,
+            &Target::Country("Germany".to_string()),
+            &mut |line| lines.push(line.to_string()),
+        )
+        .unwrap();
+        assert_eq!(lines, ["Germany #1045"]);
+        assert_eq!(connected, status(&socket).unwrap());
+        assert_eq!(
+            connected,
+            ConnectionStatus::Connected {
+                country: "Germany".to_string(),
+                city: Some("Frankfurt".to_string()),
+                server: Some("de1045.nordvpn.com".to_string()),
+                ip: Some("185.130.184.86".to_string()),
+            }
+        );
+
+        disconnect(&socket).unwrap();
+        assert_eq!(status(&socket).unwrap(), ConnectionStatus::Disconnected);
+    }
+
+    #[test]
+    fn connect_failure_is_classified() {
+        let (_dir, socket) = serve();
+
+        let result = connect(
+            &socket,
+            &Target::Country("Atlantis".to_string()),
+            &mut |_| {},
+        );
+        assert!(matches!(
+            result,
+            Err(Error::Backend(BackendError::InvalidTarget(_)))
+        ));
+    }
+
+    #[test]
+    fn daemon_errors_do_not_fall_back_to_cli() {
+        let fake = FakeNordVPN::new("");
+        let (_dir, socket) = serve();
+        let target = Target::Country("Broken".to_string());
+
+        let result = connect(&socket, &target, &mut |_| {});
+        assert!(matches!(
+            result,
+            Err(Error::Backend(BackendError::Command { action: "connect", ref message, .. }))
+                if message == "failed to configure the tunnel"
+        ));
+
+        NordVPN::set_grpc_socket(Some(socket));
+        assert!(NordVPN::connect(&target, |_| {}).is_err());
+        assert!(fake.calls().is_empty());
+    }
+
+    #[test]
+    fn missing_socket_is_unavailable() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = status(&dir.path().join("nordvpnd.sock"));
+        assert!(matches!(result, Err(Error::Unavailable(_))));
+    }
+
+    #[test]
+    fn backend_prefers_socket_and_falls_back_to_cli() {
+        let fake = FakeNordVPN::new("");
+        let (_dir, socket) = serve();
+
+        NordVPN::set_grpc_socket(Some(socket));
+        NordVPN::connect(&Target::Country("Germany".to_string()), |_| {}).unwrap();
+        assert!(matches!(
+            NordVPN::get_status().unwrap(),
+            ConnectionStatus::Connected { .. }
+        ));
+        assert!(fake.calls().is_empty());
+
+        let missing = tempfile::tempdir().unwrap();
+        NordVPN::set_grpc_socket(Some(missing.path().join("nordvpnd.sock")));
+        assert_eq!(
+            NordVPN::get_status().unwrap(),
+            ConnectionStatus::Disconnected
+        );
+        assert_eq!(fake.calls(), ["status"]);
+    }
+}
@@ -0,0 +1,396 @@
+//! Hook commands run when the connection changes, e.g. to restart services
+//! that must pick up the tunnel.
+//!
+//! Hooks run through `sh -c` on a thread of their own, one at a time in
+//! the order they were queued, so they never block the UI. Each gets the
+//! connection status in `NORDVPN_*` environment variables and is killed
+//! once its timeout passes. The outcome and output of every run go back to
+//! the app as an [`AppEvent::HookFinished`].
+
+use crate::config::HooksConfig;
+use crate::event::AppEvent;
+use crate::state::unix_now;
+use crate::types::{ConnectionStatus, Target};
+use std::fmt;
+use std::io::{BufRead, BufReader, Read};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often to check whether a hook has exited
+const POLL: Duration = Duration::from_millis(20);
+/// How long to keep reading output after a hook exited, in case something
+/// it started in the background holds on to its stdout
+const OUTPUT_GRACE: Duration = Duration::from_millis(200);
+/// Output lines kept per run; the last ones are kept
+const OUTPUT_LIMIT: usize = 50;
+
+/// When a hook runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    /// Before the app connects, including watchdog reconnects
+    PreConnect,
+    /// After a connect or reconnect succeeded
+    PostConnect,
+    /// After the user disconnected
+    Disconnect,
+    /// After the tunnel went down without the user asking
+    UnexpectedDrop,
+    /// After the connection moved to another server without going down
+    ServerChange,
+}
+
+impl HookEvent {
+    /// The configured commands for this event
+    pub fn commands(self, config: &HooksConfig) -> &[String] {
+        match self {
+            HookEvent::PreConnect => &config.pre_connect,
+            HookEvent::PostConnect => &config.post_connect,
+            HookEvent::Disconnect => &config.disconnect,
+            HookEvent::UnexpectedDrop => &config.unexpected_drop,
+            HookEvent::ServerChange => &config.server_change,
+        }
+    }
+}
+
+impl fmt::Display for HookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            HookEvent::PreConnect => "pre-connect",
+            HookEvent::PostConnect => "post-connect",
+            HookEvent::Disconnect => "disconnect",
+            HookEvent::UnexpectedDrop => "unexpected-drop",
+            HookEvent::ServerChange => "server-change",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Environment describing the connection to a hook. Every variable is set,
+/// empty if unknown, so scripts can use `set -u`.
+pub fn env(
+    event: HookEvent,
+    status: &ConnectionStatus,
+    target: Option<&Target>,
+    previous_server: Option<&str>,
+) -> Vec<(String, String)> {
+    let (state, country, city, server, ip) = match status {
+        ConnectionStatus::Connected {
+            country,
+            city,
+            server,
+            ip,
+        } => (
+            "connected",
+            country.as_str(),
+            city.as_deref(),
+            server.as_deref(),
+            ip.as_deref(),
+        ),
+        ConnectionStatus::Connecting => ("connecting", "", None, None, None),
+        ConnectionStatus::Disconnected => ("disconnected", "", None, None, None),
+    };
+    [
+        ("NORDVPN_EVENT", event.to_string()),
+        ("NORDVPN_STATE", state.to_string()),
+        ("NORDVPN_COUNTRY", country.to_string()),
+        ("NORDVPN_CITY", city.unwrap_or_default().to_string()),
+        ("NORDVPN_SERVER", server.unwrap_or_default().to_string()),
+        ("NORDVPN_IP", ip.unwrap_or_default().to_string()),
+        (
+            "NORDVPN_TARGET",
+            target.map(Target::to_string).unwrap_or_default(),
+        ),
+        (
+            "NORDVPN_PREVIOUS_SERVER",
+            previous_server.unwrap_or_default().to_string(),
+        ),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), value))
+    .collect()
+}
+
+/// One command to run for an event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookJob {
+    pub event: HookEvent,
+    pub command: String,
+    pub env: Vec<(String, String)>,
+    pub timeout: Duration,
+}
+
+/// How a hook ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookOutcome {
+    Exited(i32),
+    /// Ended by a signal from outside
+    Killed,
+    /// Killed because it ran past its timeout
+    TimedOut(Duration),
+    /// Could not be started or waited for
+    Failed(String),
+}
+
+impl fmt::Display for HookOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HookOutcome::Exited(code) => write!(f, "exited with {}", code),
+            HookOutcome::Killed => write!(f, "killed by a signal"),
+            HookOutcome::TimedOut(timeout) => {
+                write!(f, "killed after {}s timeout", timeout.as_secs())
+            }
+            HookOutcome::Failed(e) => write!(f, "failed: {}", e),
+        }
+    }
+}
+
+/// A finished hook, shown in the hook log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookRun {
+    pub event: HookEvent,
+    pub command: String,
+    pub outcome: HookOutcome,
+    /// Lines printed to stdout and stderr
+    pub output: Vec<String>,
+    /// Seconds since the Unix epoch
+    pub finished_at: u64,
+}
+
+impl HookRun {
+    pub fn succeeded(&self) -> bool {
+        self.outcome == HookOutcome::Exited(0)
+    }
+}
+
+/// Hooks queued together, with a way to tell the queuer they are done
+#[derive(Debug)]
+struct Batch {
+    jobs: Vec<HookJob>,
+    done: Option<Sender<()>>,
+}
+
+/// Runs hooks on a background thread in the order they were queued
+#[derive(Debug, Clone)]
+pub struct HookRunner {
+    tx: Sender<Batch>,
+}
+
+impl HookRunner {
+    /// Start the thread, which stops once every runner handle is dropped
+    pub fn spawn(events: Sender<AppEvent>) -> Self {
+        let (tx, batches) = mpsc::channel::<Batch>();
+        thread::spawn(move || {
+            for batch in batches {
+                for job in &batch.jobs {
+                    let _ = events.send(AppEvent::HookFinished(run(job)));
+                }
+                if let Some(done) = batch.done {
+                    let _ = done.send(());
+                }
+            }
+        });
+        Self { tx }
+    }
+
+    /// Run `jobs` after the hooks queued so far, without waiting
+    pub fn queue(&self, jobs: Vec<HookJob>) {
+        if !jobs.is_empty() {
+            let _ = self.tx.send(Batch { jobs, done: None });
+        }
+    }
+
+    /// Run `jobs` after the hooks queued so far and wait for all of them;
+    /// for the pre-connect hooks, which run on the connect thread
+    pub fn run(&self, jobs: Vec<HookJob>) {
+        let (done, finished) = mpsc::channel();
+        let batch = Batch {
+            jobs,
+            done: Some(done),
+        };
+        if self.tx.send(batch).is_ok() {
+            let _ = finished.recv();
+        }
+    }
+}
+
+/// Run one hook to completion or until its timeout, capturing its output
+pub fn run(job: &HookJob) -> HookRun {
+    let (outcome, output) = match Command::new("sh")
+        .arg("-c")
+        .arg(&job.command)
+        .envs(job.env.iter().map(|(name, value)| (name, value)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // A group of its own, so a timeout also kills what the hook started
+        .process_group(0)
+        .spawn()
+    {
+        Ok(mut child) => {
+            let (line_tx, lines) = mpsc::channel();
+            if let Some(stdout) = child.stdout.take() {
+                spawn_reader(stdout, line_tx.clone());
+            }
+            if let Some(stderr) = child.stderr.take() {
+                spawn_reader(stderr, line_tx);
+            }
+
+            let deadline = Instant::now() + job.timeout;
+            let outcome = loop {
+                match child.try_wait() {
+                    Ok(Some(status)) => {
+                        break status
+                            .code()
+                            .map_or(HookOutcome::Killed, HookOutcome::Exited);
+                    }
+                    Ok(None) if Instant::now() >= deadline => {
+                        kill_group(&mut child);
+                        let _ = child.wait();
+                        break HookOutcome::TimedOut(job.timeout);
+                    }
+                    Ok(None) => thread::sleep(POLL),
+                    Err(e) => break HookOutcome::Failed(e.to_string()),
+                }
+            };
+
+            let grace = Instant::now() + OUTPUT_GRACE;
+            let mut output = Vec::new();
+            while let Ok(line) = lines.recv_timeout(grace.saturating_duration_since(Instant::now()))
+            {
+                output.push(line);
+            }
+            let excess = output.len().saturating_sub(OUTPUT_LIMIT);
+            output.drain(..excess);
+            (outcome, output)
+        }
+        Err(e) => (HookOutcome::Failed(e.to_string()), Vec::new()),
+    };
+    HookRun {
+        event: job.event,
+        command: job.command.clone(),
+        outcome,
+        output,
+        finished_at: unix_now(),
+    }
+}
+
+/// Kill a hook along with everything left in its process group
+fn kill_group(child: &mut Child) {
+    #[cfg(target_os = "linux")]
+    // SAFETY: only sends a signal; the group id is the hook's pid, and the
+    // hook is not reaped yet so the id cannot have been reused
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = Command::new("kill")
+        .args(["-KILL", "--", &format!("-{}", child.id())])
+        .stderr(Stdio::null())
+        .status();
+    let _ = child.kill();
+}
+
+fn spawn_reader(pipe: impl Read + Send + 'static, tx: Sender<String>) {
+    thread::spawn(move || {
+        for line in BufReader::new(pipe).lines() {
+            let Ok(line) = line else { break };
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(command: &str, timeout: Duration) -> HookJob {
+        let status = ConnectionStatus::Connected {
+            country: "Germany".to_string(),
+            city: Some("Frankfurt".to_string()),
+            server: Some("de1045.nordvpn.com".to_string()),
+            ip: None,
+        };
+        let target = Target::Country("Germany".to_string());
+        HookJob {
+            event: HookEvent::ServerChange,
+            command: command.to_string(),
+            env: env(
+                HookEvent::ServerChange,
+                &status,
+                Some(&target),
+                Some("de1044.nordvpn.com"),
+            ),
+            timeout,
+        }
+    }
+
+    #[test]
+    fn passes_status_and_captures_output() {
+        let run = run(&job(
+            "set -u; echo \"$NORDVPN_EVENT $NORDVPN_STATE $NORDVPN_SERVER\"; \
+             echo \"from $NORDVPN_PREVIOUS_SERVER ip=$NORDVPN_IP\" >&2; exit 3",
+            Duration::from_secs(10),
+        ));
+        assert_eq!(run.outcome, HookOutcome::Exited(3));
+        assert!(!run.succeeded());
+        let mut output = run.output.clone();
+        // stdout and stderr are read separately, so their order may vary
+        output.sort();
+        assert_eq!(
+            output,
+            [
+                "from de1044.nordvpn.com ip=",
+                "server-change connected de1045.nordvpn.com"
+            ]
+        );
+    }
+
+    #[test]
+    fn kills_hooks_that_time_out() {
+        let started = Instant::now();
+        let run = run(&job("echo started; sleep 10", Duration::from_millis(300)));
+        assert_eq!(
+            run.outcome,
+            HookOutcome::TimedOut(Duration::from_millis(300))
+        );
+        assert_eq!(run.output, ["started"]);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn timeout_kills_what_the_hook_started() {
+        // The trailing command keeps the inner shell from exec'ing sleep
+        let run = run(&job(
+            "sh -c 'sleep 31; true' & echo $!; wait",
+            Duration::from_millis(300),
+        ));
+        assert_eq!(
+            run.outcome,
+            HookOutcome::TimedOut(Duration::from_millis(300))
+        );
+        let pid = &run.output[0];
+        let deadline = Instant::now() + Duration::from_secs(5);
+        // Gone, or a zombie waiting for init to reap it
+        while let Ok(stat) = std::fs::read_to_string(format!("/proc/{pid}/stat")) {
+            if stat
+                .rsplit(')')
+                .next()
+                .unwrap()
+                .trim_start()
+                .starts_with('Z')
+            {
+                break;
+            }
+            assert!(
+                Instant::now() < deadline,
+                "process {pid} survived the timeout"
+            );
+            thread::sleep(POLL);
+        }
+    }
+}
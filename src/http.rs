@@ -0,0 +1,14 @@
+use std::time::Duration;
+
+/// Fetch `url` and return the response body, giving up after `timeout`
+pub fn get(url: &str, timeout: Duration) -> Result<String, String> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(timeout))
+        .build()
+        .into();
+    agent
+        .get(url)
+        .call()
+        .and_then(|mut response| response.body_mut().read_to_string())
+        .map_err(|e| format!("Failed to query {}: {}", url, e))
+}
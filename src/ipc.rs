@@ -0,0 +1,247 @@
+//! Control socket: a line-delimited JSON protocol over a Unix socket that
+//! lets other tools query and drive a running instance.
+//!
+//! Each line a client sends is one [`Request`]; each gets exactly one reply
+//! line with `"ok"` set. After `subscribe`, the client additionally gets an
+//! `{"event": "status", ...}` line whenever the connection status changes.
+//! Requests are handled on the app's event loop, so every client sees the
+//! same state as the TUI.
+
+use crate::event::AppEvent;
+use crate::types::{ConnectionStatus, Target};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+/// One request line
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Request {
+    Status,
+    /// Stream status changes on this connection
+    Subscribe,
+    /// Connect in the background; without a target to the fastest server
+    Connect {
+        #[serde(default)]
+        target: Option<Target>,
+    },
+    Disconnect,
+    Favorites,
+    /// The most recent history entries, oldest first
+    History {
+        #[serde(default)]
+        limit: Option<usize>,
+    },
+}
+
+/// Parse a request line, describing what is wrong with it otherwise
+pub fn parse_request(line: &str) -> Result<Request, String> {
+    serde_json::from_str(line).map_err(|e| format!("invalid request: {}", e))
+}
+
+/// Encode a reply line; `Ok` carries an object whose fields are added next
+/// to `"ok": true`
+pub fn reply(result: Result<Value, String>) -> String {
+    match result {
+        Ok(Value::Object(mut fields)) => {
+            fields.insert("ok".to_string(), Value::Bool(true));
+            Value::Object(fields).to_string()
+        }
+        Ok(_) => json!({ "ok": true }).to_string(),
+        Err(error) => json!({ "ok": false, "error": error }).to_string(),
+    }
+}
+
+/// Encode a status event line for subscribers
+pub fn status_event(status: &Value) -> String {
+    json!({ "event": "status", "status": status }).to_string()
+}
+
+/// The status as sent to clients, together with the target being connected
+/// to or last connected to
+pub fn status_json(status: &ConnectionStatus, target: Option<&Target>) -> Value {
+    let mut value = match status {
+        ConnectionStatus::Connected {
+            country,
+            city,
+            server,
+            ip,
+        } => json!({
+            "state": "connected",
+            "country": country,
+            "city": city,
+            "server": server,
+            "ip": ip,
+        }),
+        ConnectionStatus::Connecting => json!({ "state": "connecting" }),
+        ConnectionStatus::Disconnected => json!({ "state": "disconnected" }),
+    };
+    if let Some(target) = target {
+        value["target"] = json!(target);
+    }
+    value
+}
+
+/// A listening control socket; the socket file is removed when dropped
+#[derive(Debug)]
+pub struct ControlServer {
+    path: PathBuf,
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Listen on `path`, handing each request to the app as an
+/// [`AppEvent::Control`].
+///
+/// A socket left behind by an instance that crashed is replaced, but one
+/// that still answers, or anything other than a socket, is an error.
+pub fn listen(path: &Path, tx: Sender<AppEvent>) -> io::Result<ControlServer> {
+    if UnixStream::connect(path).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("another instance is listening on {}", path.display()),
+        ));
+    }
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)?,
+        // Most likely a mistyped option; leave the file alone
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a socket", path.display()),
+            ));
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    // Anyone who can connect can disconnect the VPN
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            let tx = tx.clone();
+            thread::spawn(move || serve_client(stream, tx));
+        }
+    });
+    Ok(ControlServer {
+        path: path.to_path_buf(),
+    })
+}
+
+/// Forward a client's requests to the app and write back whatever the app
+/// sends on the client's channel: replies and, once subscribed, events
+fn serve_client(stream: UnixStream, tx: Sender<AppEvent>) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let (out_tx, out_rx) = mpsc::channel::<String>();
+    thread::spawn(move || {
+        for line in out_rx {
+            if writeln!(writer, "{}", line).is_err() {
+                break;
+            }
+        }
+    });
+
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else { break };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event = AppEvent::Control {
+            request: parse_request(&line),
+            reply: out_tx.clone(),
+        };
+        if tx.send(event).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_requests() {
+        assert_eq!(parse_request(r#"{"cmd":"status"}"#), Ok(Request::Status));
+        assert_eq!(
+            parse_request(r#"{"cmd":"connect"}"#),
+            Ok(Request::Connect { target: None })
+        );
+        assert_eq!(
+            parse_request(
+                r#"{"cmd":"connect","target":{"city":{"country":"Germany","city":"Berlin"}}}"#
+            ),
+            Ok(Request::Connect {
+                target: Some(Target::City {
+                    country: "Germany".to_string(),
+                    city: "Berlin".to_string(),
+                })
+            })
+        );
+        assert_eq!(
+            parse_request(r#"{"cmd":"connect","target":"quick"}"#),
+            Ok(Request::Connect {
+                target: Some(Target::Quick)
+            })
+        );
+        for bad in [
+            r#"{"cmd":"reboot"}"#,
+            "status",
+            r#"{"cmd":"history","limit":"all"}"#,
+        ] {
+            let e = parse_request(bad).unwrap_err();
+            assert!(e.starts_with("invalid request"), "{bad:?} gave {e:?}");
+        }
+    }
+
+    #[test]
+    fn encodes_replies_and_status() {
+        assert_eq!(reply(Ok(json!({}))), r#"{"ok":true}"#);
+        assert_eq!(
+            reply(Err("Not connected".to_string())),
+            r#"{"error":"Not connected","ok":false}"#
+        );
+        let status = ConnectionStatus::Connected {
+            country: "Germany".to_string(),
+            city: None,
+            server: Some("de1045".to_string()),
+            ip: None,
+        };
+        let target = Target::Country("Germany".to_string());
+        assert_eq!(
+            status_event(&status_json(&status, Some(&target))),
+            r#"{"event":"status","status":{"city":null,"country":"Germany","ip":null,"server":"de1045","state":"connected","target":{"country":"Germany"}}}"#
+        );
+    }
+
+    #[test]
+    fn listen_leaves_other_files_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "backend = \"grpc\"\n").unwrap();
+
+        let (tx, _rx) = mpsc::channel();
+        let e = listen(&path, tx).err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "backend = \"grpc\"\n"
+        );
+    }
+}
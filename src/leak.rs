@@ -0,0 +1,133 @@
+use crate::config::LeakTestConfig;
+use crate::http;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// Resolver lists to inspect, most specific first. With systemd-resolved
+/// `/etc/resolv.conf` only points at the local stub, so the upstream list
+/// it forwards to is checked instead.
+const RESOLV_CONF_PATHS: &[&str] = &["/run/systemd/resolve/resolv.conf", "/etc/resolv.conf"];
+
+/// Result of verifying where traffic actually exits after connecting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeakReport {
+    /// Public IP as seen by the configured endpoint
+    pub public_ip: String,
+    /// IP reported by `nordvpn status`, if any
+    pub expected_ip: Option<String>,
+    /// Resolvers in use that are not in the expected list
+    pub unexpected_resolvers: Vec<String>,
+}
+
+impl LeakReport {
+    /// The public IP differs from the one the CLI reported
+    pub fn ip_mismatch(&self) -> bool {
+        self.expected_ip
+            .as_ref()
+            .is_some_and(|expected| *expected != self.public_ip)
+    }
+
+    /// DNS queries may go to resolvers outside the tunnel
+    pub fn dns_leak(&self) -> bool {
+        !self.unexpected_resolvers.is_empty()
+    }
+}
+
+/// Query the public IP endpoint and inspect the resolvers in use
+pub fn run(config: &LeakTestConfig, expected_ip: Option<String>) -> Result<LeakReport, String> {
+    let public_ip = public_ip(
+        &config.ip_endpoint,
+        Duration::from_secs(config.timeout_secs),
+    )?;
+    let resolvers = system_resolvers();
+
+    Ok(LeakReport {
+        public_ip,
+        expected_ip,
+        unexpected_resolvers: unexpected_resolvers(&resolvers, &config.expected_resolvers),
+    })
+}
+
+/// Fetch the caller's public IP from an endpoint that returns it as text
+pub fn public_ip(endpoint: &str, timeout: Duration) -> Result<String, String> {
+    let body = http::get(endpoint, timeout)?;
+
+    let ip = body.trim();
+    ip.parse::<IpAddr>()
+        .map(|ip| ip.to_string())
+        .map_err(|_| format!("{} did not return an IP address: {:?}", endpoint, ip))
+}
+
+/// Nameservers from the first resolver list that names any
+pub fn system_resolvers() -> Vec<String> {
+    RESOLV_CONF_PATHS
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .map(|conf| nameservers(&conf))
+        .find(|servers| !servers.is_empty())
+        .unwrap_or_default()
+}
+
+/// Extract `nameserver` entries from a resolv.conf
+pub fn nameservers(conf: &str) -> Vec<String> {
+    conf.lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .map(|server| server.trim().to_string())
+        .collect()
+}
+
+/// Resolvers that are neither expected nor a local stub
+fn unexpected_resolvers(resolvers: &[String], expected: &[String]) -> Vec<String> {
+    resolvers
+        .iter()
+        .filter(|server| !expected.contains(server))
+        .filter(|server| !server.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback()))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn fetches_public_ip_from_local_endpoint() {
+        let endpoint = testing::serve_once("text/plain", "185.2.3.4\n");
+        let ip = public_ip(&endpoint, Duration::from_secs(5)).unwrap();
+        assert_eq!(ip, "185.2.3.4");
+    }
+
+    #[test]
+    fn rejects_non_ip_response() {
+        let endpoint = testing::serve_once("text/plain", "<html>rate limited</html>");
+        assert!(public_ip(&endpoint, Duration::from_secs(5)).is_err());
+    }
+
+    #[test]
+    fn flags_ip_mismatch_against_status() {
+        let config = LeakTestConfig {
+            ip_endpoint: testing::serve_once("text/plain", "185.2.3.4"),
+            ..LeakTestConfig::default()
+        };
+        let report = run(&config, Some("185.9.9.9".to_string())).unwrap();
+        assert!(report.ip_mismatch());
+
+        let config = LeakTestConfig {
+            ip_endpoint: testing::serve_once("text/plain", "185.9.9.9"),
+            ..LeakTestConfig::default()
+        };
+        let report = run(&config, Some("185.9.9.9".to_string())).unwrap();
+        assert!(!report.ip_mismatch());
+    }
+
+    #[test]
+    fn ignores_expected_and_stub_resolvers() {
+        let conf = "# generated\nnameserver 127.0.0.53\nnameserver 103.86.96.100\nnameserver 8.8.8.8\noptions edns0\n";
+        let resolvers = nameservers(conf);
+        assert_eq!(resolvers, ["127.0.0.53", "103.86.96.100", "8.8.8.8"]);
+
+        let expected = LeakTestConfig::default().expected_resolvers;
+        assert_eq!(unexpected_resolvers(&resolvers, &expected), ["8.8.8.8"]);
+    }
+}
@@ -1,31 +1,124 @@
+mod action;
+mod agent;
 mod app;
+mod cli;
+mod clipboard;
+mod config;
+mod doctor;
+mod error;
+mod event;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod hooks;
+mod http;
+mod ipc;
+mod leak;
 mod nordvpn;
+mod notify;
+mod parse;
+mod profile;
+mod servers;
+mod snapshot;
+mod state;
+#[cfg(test)]
+mod testing;
 mod types;
 mod ui;
+mod watch;
+mod watchdog;
 
 use app::App;
+use cli::Command;
 use color_eyre::Result;
+use config::{Backend, Config};
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use nordvpn::NordVPN;
+use servers::ServerCache;
+use state::State;
 
 fn main() -> Result<()> {
     color_eyre::install()?;
 
-    // Try to create the app - if it fails, show a helpful error
-    let app = match App::new() {
-        Ok(app) => app,
+    let command = match cli::parse(std::env::args().skip(1)) {
+        Ok(command) => command,
         Err(e) => {
-            eprintln!("Error: Failed to initialize nordvpn-tui\n");
-            eprintln!("This application requires the NordVPN service to be running.");
-            eprintln!("\nPlease ensure:");
-            eprintln!("  1. NordVPN is installed: https://nordvpn.com/download/linux/");
-            eprintln!("  2. The nordvpn daemon is running");
-            eprintln!("  3. You are logged in: nordvpn login");
-            eprintln!("\nOriginal error: {}", e);
-            std::process::exit(1);
+            eprintln!("error: {}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
         }
     };
 
+    match command {
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Command::Version => {
+            println!("nordvpn-tui {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        Command::Doctor
+        | Command::Profile(_)
+        | Command::Settings(_)
+        | Command::Agent { .. }
+        | Command::Watch(_)
+        | Command::Tui => {}
+    }
+
+    let config = Config::load()?;
+    if let Some(path) = config.nordvpn_binary() {
+        NordVPN::set_binary(path);
+    }
+    match config.backend {
+        Backend::Cli => {}
+        #[cfg(feature = "grpc")]
+        Backend::Grpc => NordVPN::set_grpc_socket(Some(config.daemon_socket.clone())),
+        #[cfg(not(feature = "grpc"))]
+        Backend::Grpc => eprintln!("warning: built without gRPC support, using the nordvpn CLI"),
+    }
+
+    if command == Command::Doctor {
+        let checks = doctor::run_checks(&config.daemon_socket);
+        print!("{}", doctor::report(&checks));
+        std::process::exit(if doctor::has_failures(&checks) { 1 } else { 0 });
+    }
+
+    if let Command::Profile(name) = &command {
+        let Some(profile) = config.profiles.get(name) else {
+            let names: Vec<&str> = config.profiles.keys().map(String::as_str).collect();
+            eprintln!(
+                "error: no profile named {:?} (available: {})",
+                name,
+                if names.is_empty() {
+                    "none".to_string()
+                } else {
+                    names.join(", ")
+                }
+            );
+            std::process::exit(2);
+        };
+        let applied = profile::apply_printing(name, profile);
+        std::process::exit(if applied { 0 } else { 1 });
+    }
+
+    if let Command::Settings(command) = &command {
+        std::process::exit(snapshot::run(command));
+    }
+
+    if let Command::Watch(options) = &command {
+        std::process::exit(watch::run(options, &config.watch));
+    }
+
+    if let Command::Agent { once } = command {
+        std::process::exit(agent::run(&config, once));
+    }
+
+    // The app starts even if the daemon is unreachable and keeps retrying
+    let app = App::new(config, State::load(), ServerCache::load());
+
     let terminal = ratatui::init();
+    crossterm::execute!(std::io::stdout(), EnableMouseCapture)?;
     let result = app.run(terminal);
+    crossterm::execute!(std::io::stdout(), DisableMouseCapture)?;
     ratatui::restore();
     result
 }
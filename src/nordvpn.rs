@@ -1,113 +1,457 @@
-use crate::types::{ConnectionStatus, Country};
-use color_eyre::Result;
-use std::process::Command;
+use crate::error::BackendError;
+#[cfg(feature = "grpc")]
+use crate::grpc;
+use crate::parse;
+use crate::types::{
+    Account, ChangeSet, ConnectionStatus, Country, DnsSettings, MeshnetPeer, PortRule,
+    SettingChange, Settings, StatusDetails, Target, Technology, Toggle, TunnelSettings,
+};
+use std::io::{BufRead, BufReader, Read};
+use std::net::IpAddr;
+#[cfg(feature = "grpc")]
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+
+pub type Result<T> = std::result::Result<T, BackendError>;
+
+/// A change set that failed part way through
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeSetError {
+    /// The step the daemon rejected
+    pub change: SettingChange,
+    pub error: BackendError,
+    /// Earlier steps that could not be undone and are still in effect
+    pub not_undone: Vec<SettingChange>,
+}
+
+fn cancelled(action: &'static str) -> BackendError {
+    BackendError::Command {
+        action,
+        code: None,
+        message: "Cancelled".to_string(),
+    }
+}
+
+/// Stops a connect running on another thread
+#[derive(Debug, Clone, Default)]
+pub struct ConnectCancel(Arc<Mutex<CancelState>>);
+
+#[derive(Debug, Default)]
+struct CancelState {
+    cancelled: bool,
+    /// The CLI while it runs
+    child: Option<Child>,
+}
+
+impl ConnectCancel {
+    /// Kill the connecting CLI and wait for it to exit, or keep it from
+    /// starting; the connect then fails
+    pub fn cancel(&self) {
+        let mut state = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        state.cancelled = true;
+        if let Some(mut child) = state.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).cancelled
+    }
+}
+
+/// CLI to run instead of `nordvpn` from the PATH
+static BINARY: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Daemon socket to try before the CLI, if the gRPC backend is enabled
+#[cfg(feature = "grpc")]
+static GRPC_SOCKET: RwLock<Option<PathBuf>> = RwLock::new(None);
 
 pub struct NordVPN;
 
 impl NordVPN {
-    /// Get list of available countries
-    pub fn get_countries() -> Result<Vec<Country>> {
-        let output = Command::new("nordvpn").arg("countries").output()?;
+    /// Use the CLI at `path` for all further commands
+    pub fn set_binary(path: impl Into<PathBuf>) {
+        *BINARY.write().unwrap_or_else(|e| e.into_inner()) = Some(path.into());
+    }
+
+    fn binary() -> PathBuf {
+        BINARY
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("nordvpn"))
+    }
+
+    /// Talk to the daemon over `socket` where possible, or only use the CLI
+    #[cfg(feature = "grpc")]
+    pub fn set_grpc_socket(socket: Option<PathBuf>) {
+        *GRPC_SOCKET.write().unwrap_or_else(|e| e.into_inner()) = socket;
+    }
+
+    /// Daemon socket tried before the CLI, if the gRPC backend is enabled
+    #[cfg(feature = "grpc")]
+    pub fn grpc_socket() -> Option<PathBuf> {
+        GRPC_SOCKET
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Make a call over the daemon socket, if enabled.
+    ///
+    /// Returns `None` when the CLI should be used instead, either because
+    /// the gRPC backend is off or the socket could not serve the call.
+    #[cfg(feature = "grpc")]
+    fn grpc<T>(call: impl FnOnce(&Path) -> grpc::Result<T>) -> Option<Result<T>> {
+        let socket = Self::grpc_socket()?;
+        match call(&socket) {
+            Ok(value) => Some(Ok(value)),
+            Err(grpc::Error::Backend(e)) => Some(Err(e)),
+            Err(grpc::Error::Unavailable(_)) => None,
+        }
+    }
+
+    /// Run `nordvpn` with the given arguments and return its stdout
+    fn run(action: &'static str, args: &[&str]) -> Result<String> {
+        let output = Command::new(Self::binary())
+            .args(args)
+            .output()
+            .map_err(BackendError::from_io)?;
 
         if !output.status.success() {
-            return Err(color_eyre::eyre::eyre!(
-                "Failed to get countries: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+            return Err(BackendError::from_output(action, &output));
         }
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let countries: Vec<Country> = stdout
-            .lines()
-            .filter(|line| !line.trim().is_empty())
-            .map(|line| Country::new(line.trim().to_string()))
-            .collect();
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Get the version string reported by the CLI
+    pub fn version() -> Result<String> {
+        let stdout = Self::run("get version", &["--version"])?;
+        Ok(parse::clean(&stdout))
+    }
+
+    /// Get the account information for the logged in user
+    pub fn account() -> Result<Account> {
+        let stdout = Self::run("get account", &["account"])?;
+        Ok(parse::account(&stdout))
+    }
+
+    /// Get list of available countries
+    pub fn get_countries() -> Result<Vec<Country>> {
+        #[cfg(feature = "grpc")]
+        if let Some(result) = Self::grpc(grpc::countries) {
+            return result;
+        }
+        let stdout = Self::run("get countries", &["countries"])?;
+        Ok(parse::names(&stdout)
+            .into_iter()
+            .map(Country::new)
+            .collect())
+    }
 
-        Ok(countries)
+    /// Get list of server groups, e.g. "P2P" or "Europe"
+    pub fn get_groups() -> Result<Vec<String>> {
+        let stdout = Self::run("get groups", &["groups"])?;
+        Ok(parse::names(&stdout))
     }
 
     /// Get current connection status
     pub fn get_status() -> Result<ConnectionStatus> {
-        let output = Command::new("nordvpn").arg("status").output()?;
+        #[cfg(feature = "grpc")]
+        if let Some(result) = Self::grpc(grpc::status) {
+            return result;
+        }
+        let stdout = Self::run("get status", &["status"])?;
+        Ok(parse::status(&stdout))
+    }
 
-        if !output.status.success() {
-            return Err(color_eyre::eyre::eyre!(
-                "Failed to get status: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
-        }
-
-        let stdout = String::from_utf8_lossy(&output.stdout);
-
-        // Parse the status output
-        if stdout.contains("Status: Disconnected") {
-            return Ok(ConnectionStatus::Disconnected);
-        }
-
-        if stdout.contains("Status: Connected") {
-            let mut country = None;
-            let mut city = None;
-            let mut server = None;
-            let mut ip = None;
-
-            for line in stdout.lines() {
-                let line = line.trim();
-                if line.starts_with("Country:") {
-                    country = Some(line.strip_prefix("Country:").unwrap().trim().to_string());
-                } else if line.starts_with("City:") {
-                    city = Some(line.strip_prefix("City:").unwrap().trim().to_string());
-                } else if line.starts_with("Server:") || line.starts_with("Hostname:") {
-                    server = line
-                        .strip_prefix("Server:")
-                        .or_else(|| line.strip_prefix("Hostname:"))
-                        .map(|s| s.trim().to_string());
-                } else if line.starts_with("IP:") {
-                    ip = Some(line.strip_prefix("IP:").unwrap().trim().to_string());
-                }
+    /// Get the connection status with uptime and traffic.
+    ///
+    /// Always runs the CLI, since the daemon socket does not report traffic.
+    pub fn get_status_details() -> Result<StatusDetails> {
+        let stdout = Self::run("get status", &["status"])?;
+        Ok(parse::status_details(&stdout))
+    }
+
+    /// Get the daemon settings
+    pub fn get_settings() -> Result<Settings> {
+        let stdout = Self::run("get settings", &["settings"])?;
+        Ok(parse::settings(&stdout))
+    }
+
+    /// Technology of the current connection, or else the configured one
+    pub fn get_technology() -> Result<Option<Technology>> {
+        let status = Self::run("get status", &["status"])?;
+        if let Some(technology) = parse::technology(&status) {
+            return Ok(Some(technology));
+        }
+        let settings = Self::run("get settings", &["settings"])?;
+        Ok(parse::technology(&settings))
+    }
+
+    /// Switch technology, then protocol where the technology takes one.
+    ///
+    /// Takes effect on the next connect.
+    pub fn set_technology(technology: Technology) -> Result<()> {
+        Self::run(
+            "set technology",
+            &["set", "technology", technology.technology_arg()],
+        )?;
+        if let Some(protocol) = technology.protocol_arg() {
+            Self::run("set protocol", &["set", "protocol", protocol])?;
+        }
+        Ok(())
+    }
+
+    /// Get the technology, obfuscation, post-quantum and Meshnet settings
+    pub fn get_tunnel() -> Result<TunnelSettings> {
+        Ok(Self::get_settings()?.tunnel())
+    }
+
+    /// Apply a single tunnel setting
+    pub fn set(change: SettingChange) -> Result<()> {
+        let on_off = |enabled: bool| if enabled { "on" } else { "off" };
+        match change {
+            SettingChange::Technology(technology) => Self::set_technology(technology),
+            SettingChange::Obfuscate(enabled) => {
+                Self::run("set obfuscation", &["set", "obfuscate", on_off(enabled)])?;
+                Ok(())
             }
+            SettingChange::PostQuantum(enabled) => {
+                Self::run(
+                    "set post-quantum encryption",
+                    &["set", "post-quantum", on_off(enabled)],
+                )?;
+                Ok(())
+            }
+            SettingChange::Meshnet(enabled) => {
+                Self::run("set Meshnet", &["set", "meshnet", on_off(enabled)])?;
+                Ok(())
+            }
+        }
+    }
 
-            if let Some(country) = country {
-                return Ok(ConnectionStatus::Connected {
-                    country,
-                    city,
-                    server,
-                    ip,
+    /// Apply every step of a change set, or as few as possible.
+    ///
+    /// When a step fails the steps before it are undone in reverse order,
+    /// so the daemon ends up with the settings it started with.
+    pub fn apply(plan: &ChangeSet) -> std::result::Result<(), ChangeSetError> {
+        for (done, step) in plan.steps.iter().enumerate() {
+            if let Err(error) = Self::set(step.change) {
+                let mut not_undone = vec![];
+                for applied in plan.steps[..done].iter().rev() {
+                    if applied.undo.is_none_or(|undo| Self::set(undo).is_err()) {
+                        not_undone.push(applied.change);
+                    }
+                }
+                return Err(ChangeSetError {
+                    change: step.change,
+                    error,
+                    not_undone,
                 });
             }
         }
+        Ok(())
+    }
+
+    /// Get the custom DNS servers and Threat Protection Lite setting
+    pub fn get_dns() -> Result<DnsSettings> {
+        Ok(Self::get_settings()?.dns())
+    }
 
-        // Default to disconnected if we can't parse
-        Ok(ConnectionStatus::Disconnected)
+    /// Resolve through these servers instead of NordVPN's DNS
+    pub fn set_dns(servers: &[IpAddr]) -> Result<()> {
+        let servers: Vec<String> = servers.iter().map(IpAddr::to_string).collect();
+        let mut args = vec!["set", "dns"];
+        args.extend(servers.iter().map(String::as_str));
+        Self::run("set DNS", &args)?;
+        Ok(())
     }
 
-    /// Connect to a country
-    pub fn connect(country: &str) -> Result<()> {
-        let output = Command::new("nordvpn")
-            .arg("connect")
-            .arg(country)
-            .output()?;
+    /// Drop custom DNS servers and go back to NordVPN's
+    pub fn reset_dns() -> Result<()> {
+        Self::run("reset DNS", &["set", "dns", "off"])?;
+        Ok(())
+    }
 
-        if !output.status.success() {
-            return Err(color_eyre::eyre::eyre!(
-                "Failed to connect: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
-        }
+    /// Turn Threat Protection Lite on or off
+    pub fn set_threat_protection(enabled: bool) -> Result<()> {
+        let value = if enabled { "on" } else { "off" };
+        Self::run(
+            "set Threat Protection Lite",
+            &["set", "threatprotectionlite", value],
+        )?;
+        Ok(())
+    }
 
+    /// Turn an independent on/off setting on or off
+    pub fn set_toggle(toggle: Toggle, enabled: bool) -> Result<()> {
+        let value = if enabled { "on" } else { "off" };
+        Self::run("change setting", &["set", toggle.set_arg(), value])?;
         Ok(())
     }
 
-    /// Disconnect from VPN
-    pub fn disconnect() -> Result<()> {
-        let output = Command::new("nordvpn").arg("disconnect").output()?;
+    /// Add a subnet to the allowlist, or remove it
+    pub fn allowlist_subnet(subnet: &str, allow: bool) -> Result<()> {
+        let action = if allow { "add" } else { "remove" };
+        Self::run("change allowlist", &["allowlist", action, "subnet", subnet])?;
+        Ok(())
+    }
+
+    /// Add a port or port range to the allowlist, or remove it
+    pub fn allowlist_port(rule: &PortRule, allow: bool) -> Result<()> {
+        let action = if allow { "add" } else { "remove" };
+        let rule_args = rule.allowlist_args();
+        let mut args = vec!["allowlist", action];
+        args.extend(rule_args.iter().map(String::as_str));
+        Self::run("change allowlist", &args)?;
+        Ok(())
+    }
+
+    /// Get the other devices in the meshnet
+    pub fn meshnet_peers() -> Result<Vec<MeshnetPeer>> {
+        let stdout = Self::run("get meshnet peers", &["meshnet", "peer", "list"])?;
+        Ok(parse::meshnet_peers(&stdout))
+    }
+
+    /// Connect to a target, passing each line of progress to `on_line` as
+    /// the CLI prints it.
+    ///
+    /// Returns the status read from the final success line, which names the
+    /// server but not the city or IP; use [`NordVPN::get_status`] for those.
+    pub fn connect(target: &Target, on_line: impl FnMut(&str)) -> Result<ConnectionStatus> {
+        Self::connect_cancellable(target, &ConnectCancel::default(), on_line)
+    }
+
+    /// Like [`NordVPN::connect`], but `cancel` can stop the CLI from
+    /// another thread. Over the daemon socket a connect cannot be stopped
+    /// this way; a disconnect that follows aborts it in the daemon instead.
+    pub fn connect_cancellable(
+        target: &Target,
+        cancel: &ConnectCancel,
+        mut on_line: impl FnMut(&str),
+    ) -> Result<ConnectionStatus> {
+        if cancel.is_cancelled() {
+            return Err(cancelled("connect"));
+        }
+        #[cfg(feature = "grpc")]
+        if let Some(result) = Self::grpc(|socket| grpc::connect(socket, target, &mut on_line)) {
+            return result;
+        }
+        let mut args = vec!["connect"];
+        args.extend(target.connect_args());
+        let stdout = Self::run_streaming("connect", &args, cancel, &mut on_line)?;
+        match parse::connected(&stdout) {
+            Some(status) => Ok(status),
+            // Unknown wording; ask instead of guessing
+            None => Self::get_status(),
+        }
+    }
+
+    /// Like [`NordVPN::run`], but hands every non-empty stdout line to
+    /// `on_line` as soon as it is printed
+    fn run_streaming(
+        action: &'static str,
+        args: &[&str],
+        cancel: &ConnectCancel,
+        on_line: &mut dyn FnMut(&str),
+    ) -> Result<String> {
+        let mut child = Command::new(Self::binary())
+            .args(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(BackendError::from_io)?;
+
+        // Drained alongside stdout, or a chatty CLI blocks on a full pipe
+        let stderr = child.stderr.take().map(|mut pipe| {
+            thread::spawn(move || {
+                let mut stderr = Vec::new();
+                let _ = pipe.read_to_end(&mut stderr);
+                stderr
+            })
+        });
+        let stdout_pipe = child.stdout.take();
+
+        // Hand the child over so `cancel` can kill it while we read
+        {
+            let mut state = cancel.0.lock().unwrap_or_else(|e| e.into_inner());
+            if state.cancelled {
+                let _ = child.kill();
+                let _ = child.wait();
+                let _ = stderr.map(|reader| reader.join());
+                return Err(cancelled(action));
+            }
+            state.child = Some(child);
+        }
+
+        let mut stdout = Vec::new();
+        let mut read_error = None;
+        if let Some(pipe) = stdout_pipe {
+            let mut reader = BufReader::new(pipe);
+            let mut line = Vec::new();
+            loop {
+                match reader.read_until(b'\n', &mut line) {
+                    Ok(0) => break,
+                    Ok(_) => {}
+                    Err(e) => {
+                        read_error = Some(e);
+                        break;
+                    }
+                }
+                let cleaned = parse::clean(&String::from_utf8_lossy(&line));
+                if !cleaned.is_empty() {
+                    on_line(&cleaned);
+                }
+                stdout.append(&mut line);
+            }
+        }
 
+        // Reap the CLI and the stderr reader however reading ended
+        let child = cancel
+            .0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .child
+            .take();
+        let Some(mut child) = child else {
+            // Killed and reaped by `cancel`
+            let _ = stderr.map(|reader| reader.join());
+            return Err(cancelled(action));
+        };
+        if read_error.is_some() {
+            let _ = child.kill();
+        }
+        let status = child.wait();
+        let stderr = stderr
+            .and_then(|reader| reader.join().ok())
+            .unwrap_or_default();
+        if let Some(e) = read_error {
+            return Err(BackendError::from_io(e));
+        }
+        let output = Output {
+            status: status.map_err(BackendError::from_io)?,
+            stdout,
+            stderr,
+        };
         if !output.status.success() {
-            return Err(color_eyre::eyre::eyre!(
-                "Failed to disconnect: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+            return Err(BackendError::from_output(action, &output));
         }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
 
+    /// Disconnect from VPN
+    pub fn disconnect() -> Result<()> {
+        #[cfg(feature = "grpc")]
+        if let Some(result) = Self::grpc(grpc::disconnect) {
+            return result;
+        }
+        Self::run("disconnect", &["disconnect"])?;
         Ok(())
     }
 }
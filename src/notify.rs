@@ -0,0 +1,370 @@
+//! Desktop notifications about the connection, sent to the freedesktop
+//! notification server over the D-Bus session bus.
+//!
+//! These come from this app and are unrelated to the daemon's own `notify`
+//! setting, which makes nordvpnd show notifications itself.
+
+use crate::config::DesktopNotificationsConfig;
+use crate::event::AppEvent;
+use crate::state::HistoryKind;
+use std::collections::HashMap;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+/// Connection changes a notification can be sent for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NotifyEvent {
+    Connected,
+    Disconnected,
+    Dropped,
+    Reconnected,
+    ConnectFailed,
+    GaveUp,
+}
+
+impl NotifyEvent {
+    /// The event a history entry stands for; single reconnect attempts are
+    /// too noisy to notify about
+    pub fn from_history(kind: HistoryKind) -> Option<Self> {
+        match kind {
+            HistoryKind::Connected => Some(NotifyEvent::Connected),
+            HistoryKind::Disconnected => Some(NotifyEvent::Disconnected),
+            HistoryKind::Dropped => Some(NotifyEvent::Dropped),
+            HistoryKind::Reconnected => Some(NotifyEvent::Reconnected),
+            HistoryKind::ConnectFailed => Some(NotifyEvent::ConnectFailed),
+            HistoryKind::GaveUp => Some(NotifyEvent::GaveUp),
+            HistoryKind::ReconnectAttempt | HistoryKind::ReconnectFailed => None,
+        }
+    }
+
+    fn summary(self) -> &'static str {
+        match self {
+            NotifyEvent::Connected => "VPN connected",
+            NotifyEvent::Disconnected => "VPN disconnected",
+            NotifyEvent::Dropped => "VPN connection dropped",
+            NotifyEvent::Reconnected => "VPN reconnected",
+            NotifyEvent::ConnectFailed => "VPN connect failed",
+            NotifyEvent::GaveUp => "VPN watchdog gave up",
+        }
+    }
+
+    /// Urgency hint: 1 is normal, 2 critical
+    fn urgency(self) -> u8 {
+        match self {
+            NotifyEvent::Dropped | NotifyEvent::GaveUp => 2,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub summary: String,
+    pub body: String,
+    pub urgency: u8,
+}
+
+/// Decides which events get a notification and hands those to a
+/// background sender, so a slow or missing bus never blocks the app
+#[derive(Debug)]
+pub struct Notifier {
+    config: DesktopNotificationsConfig,
+    last_sent: HashMap<NotifyEvent, Instant>,
+    sender: Option<Sender<Notification>>,
+}
+
+impl Notifier {
+    pub fn new(config: DesktopNotificationsConfig) -> Self {
+        Self {
+            config,
+            last_sent: HashMap::new(),
+            sender: None,
+        }
+    }
+
+    /// Connect to the session bus in the background; `address` overrides
+    /// `$DBUS_SESSION_BUS_ADDRESS`. Failures to send are reported once as
+    /// an [`AppEvent::NotificationFailed`].
+    #[cfg(feature = "notifications")]
+    pub fn start(&mut self, address: Option<String>, tx: Sender<AppEvent>) -> Result<(), String> {
+        self.sender = Some(dbus::spawn_sender(address, tx));
+        Ok(())
+    }
+
+    #[cfg(not(feature = "notifications"))]
+    pub fn start(&mut self, _address: Option<String>, _tx: Sender<AppEvent>) -> Result<(), String> {
+        Err("built without desktop notification support".to_string())
+    }
+
+    /// Whether `event` is turned on and was not notified about too recently,
+    /// counting it as sent if so
+    pub fn allow(&mut self, event: NotifyEvent, now: Instant) -> bool {
+        let config = &self.config;
+        let enabled = match event {
+            NotifyEvent::Connected => config.connected,
+            NotifyEvent::Disconnected => config.disconnected,
+            NotifyEvent::Dropped => config.dropped,
+            NotifyEvent::Reconnected => config.reconnected,
+            NotifyEvent::ConnectFailed => config.connect_failed,
+            NotifyEvent::GaveUp => config.gave_up,
+        };
+        let min_interval = Duration::from_secs(config.min_interval_secs);
+        let recent = self
+            .last_sent
+            .get(&event)
+            .is_some_and(|sent| now.duration_since(*sent) < min_interval);
+        if !enabled || recent {
+            return false;
+        }
+        self.last_sent.insert(event, now);
+        true
+    }
+
+    /// Send a notification for `event` unless it is turned off or rate
+    /// limited; does nothing until started
+    pub fn notify(&mut self, event: NotifyEvent, body: String) {
+        if self.sender.is_none() || !self.allow(event, Instant::now()) {
+            return;
+        }
+        let notification = Notification {
+            summary: event.summary().to_string(),
+            body,
+            urgency: event.urgency(),
+        };
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(notification);
+        }
+    }
+}
+
+#[cfg(feature = "notifications")]
+mod dbus {
+    use super::Notification;
+    use crate::event::AppEvent;
+    use std::collections::HashMap;
+    use std::sync::mpsc::{self, Sender};
+    use std::thread;
+    use zbus::blocking::{Connection, connection};
+    use zbus::zvariant::Value;
+
+    const APP_NAME: &str = "nordvpn-tui";
+    const ICON: &str = "network-vpn";
+
+    /// Send notifications from a thread of their own, each one replacing
+    /// the previous so the popups do not pile up
+    pub fn spawn_sender(address: Option<String>, tx: Sender<AppEvent>) -> Sender<Notification> {
+        let (sender, notifications) = mpsc::channel::<Notification>();
+        thread::spawn(move || {
+            let mut bus = None;
+            let mut replaces_id = 0;
+            let mut reported = false;
+            for notification in notifications {
+                match send(&mut bus, address.as_deref(), &notification, replaces_id) {
+                    Ok(id) => replaces_id = id,
+                    Err(e) => {
+                        // Connect again next time, e.g. once the bus is back
+                        bus = None;
+                        if !reported {
+                            reported = true;
+                            let _ = tx.send(AppEvent::NotificationFailed(e.to_string()));
+                        }
+                    }
+                }
+            }
+        });
+        sender
+    }
+
+    fn send(
+        bus: &mut Option<Connection>,
+        address: Option<&str>,
+        notification: &Notification,
+        replaces_id: u32,
+    ) -> zbus::Result<u32> {
+        let connection = match bus {
+            Some(connection) => connection,
+            None => bus.insert(match address {
+                Some(address) => connection::Builder::address(address)?.build()?,
+                None => Connection::session()?,
+            }),
+        };
+        let hints = HashMap::from([("urgency", Value::U8(notification.urgency))]);
+        let reply = connection.call_method(
+            Some("org.freedesktop.Notifications"),
+            "/org/freedesktop/Notifications",
+            Some("org.freedesktop.Notifications"),
+            "Notify",
+            &(
+                APP_NAME,
+                replaces_id,
+                ICON,
+                notification.summary.as_str(),
+                notification.body.as_str(),
+                Vec::<&str>::new(),
+                hints,
+                // Server default timeout
+                -1i32,
+            ),
+        )?;
+        reply.body().deserialize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> DesktopNotificationsConfig {
+        DesktopNotificationsConfig {
+            enabled: true,
+            min_interval_secs: 30,
+            ..DesktopNotificationsConfig::default()
+        }
+    }
+
+    #[test]
+    fn toggles_and_rate_limit() {
+        let mut notifier = Notifier::new(config());
+        let start = Instant::now();
+        let later = |secs| start + Duration::from_secs(secs);
+
+        // Off by default: the app itself shows these
+        assert!(!notifier.allow(NotifyEvent::Connected, start));
+        assert!(notifier.allow(NotifyEvent::Dropped, start));
+        // A flapping connection only notifies once per interval and event
+        assert!(notifier.allow(NotifyEvent::Reconnected, later(1)));
+        assert!(!notifier.allow(NotifyEvent::Dropped, later(10)));
+        assert!(!notifier.allow(NotifyEvent::Reconnected, later(11)));
+        assert!(notifier.allow(NotifyEvent::Dropped, later(30)));
+        assert!(notifier.allow(NotifyEvent::GaveUp, later(31)));
+    }
+
+    #[test]
+    fn maps_history_to_events() {
+        assert_eq!(
+            NotifyEvent::from_history(HistoryKind::Dropped),
+            Some(NotifyEvent::Dropped)
+        );
+        assert_eq!(
+            NotifyEvent::from_history(HistoryKind::ReconnectAttempt),
+            None
+        );
+    }
+
+    /// Runs a private bus with a fake notification server on it
+    #[cfg(feature = "notifications")]
+    #[test]
+    fn sends_over_session_bus() {
+        use std::io::{BufRead, BufReader};
+        use std::process::{Child, Command, Stdio};
+        use std::sync::{Arc, Mutex};
+
+        type Received = Arc<Mutex<Vec<(u32, String, String, u8)>>>;
+
+        /// Stops the bus however the test ends
+        struct Daemon(Child);
+
+        impl Drop for Daemon {
+            fn drop(&mut self) {
+                let _ = self.0.kill();
+                let _ = self.0.wait();
+            }
+        }
+
+        struct Server {
+            received: Received,
+        }
+
+        #[zbus::interface(name = "org.freedesktop.Notifications")]
+        impl Server {
+            #[allow(clippy::too_many_arguments)]
+            fn notify(
+                &self,
+                _app_name: &str,
+                replaces_id: u32,
+                _app_icon: &str,
+                summary: &str,
+                body: &str,
+                _actions: Vec<&str>,
+                hints: HashMap<&str, zbus::zvariant::OwnedValue>,
+                _expire_timeout: i32,
+            ) -> u32 {
+                let urgency = hints
+                    .get("urgency")
+                    .and_then(|value| u8::try_from(value).ok())
+                    .unwrap_or_default();
+                let mut received = self.received.lock().unwrap();
+                received.push((replaces_id, summary.into(), body.into(), urgency));
+                received.len() as u32
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let bus_config = dir.path().join("bus.conf");
+        std::fs::write(
+            &bus_config,
+            format!(
+                "<busconfig><type>session</type>\
+                 <listen>unix:path={}</listen><auth>EXTERNAL</auth>\
+                 <policy context=\"default\"><allow send_destination=\"*\"/>\
+                 <allow receive_sender=\"*\"/><allow own=\"*\"/></policy>\
+                 </busconfig>",
+                dir.path().join("bus").display()
+            ),
+        )
+        .unwrap();
+        let child = Command::new("dbus-daemon")
+            .arg(format!("--config-file={}", bus_config.display()))
+            .args(["--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut daemon = match child {
+            Ok(child) => Daemon(child),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                eprintln!("skipping: dbus-daemon is not on PATH");
+                return;
+            }
+            Err(e) => panic!("failed to start dbus-daemon: {}", e),
+        };
+        let mut address = String::new();
+        BufReader::new(daemon.0.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        let address = address.trim().to_string();
+
+        let received = Received::default();
+        let _server = zbus::blocking::connection::Builder::address(address.as_str())
+            .unwrap()
+            .name("org.freedesktop.Notifications")
+            .unwrap()
+            .serve_at(
+                "/org/freedesktop/Notifications",
+                Server {
+                    received: received.clone(),
+                },
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let mut notifier = Notifier::new(config());
+        notifier.start(Some(address), tx).unwrap();
+        notifier.notify(NotifyEvent::Dropped, "Germany".to_string());
+        notifier.notify(NotifyEvent::Reconnected, "Germany: attempt 1".to_string());
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while received.lock().unwrap().len() < 2 {
+            assert!(Instant::now() < deadline, "timed out waiting for the bus");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(
+            *received.lock().unwrap(),
+            [
+                (0, "VPN connection dropped".into(), "Germany".into(), 2),
+                (1, "VPN reconnected".into(), "Germany: attempt 1".into(), 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,577 @@
+use crate::types::{Account, ConnectionStatus, MeshnetPeer, Settings, StatusDetails, Technology};
+
+/// Characters the CLI cycles through while it waits for the daemon
+const SPINNER: &[char] = &['-', '\\', '|', '/'];
+
+/// Strip ANSI escapes and carriage-return spinner frames from CLI output.
+///
+/// The CLI redraws its spinner with `\r`, so only the text after the last
+/// `\r` on a line is what a terminal would end up showing.
+pub fn clean(raw: &str) -> String {
+    strip_ansi(raw)
+        .split('\n')
+        .map(|line| {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            line.rsplit('\r').next().unwrap_or(line).trim_end()
+        })
+        .filter(|line| !line.trim().chars().all(|c| SPINNER.contains(&c)) || line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+fn strip_ansi(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters up to a final byte in @..~
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: up to BEL or ESC \
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Sentences the CLI prints around the actual output, like update reminders
+fn is_notice(line: &str) -> bool {
+    let line = line.trim();
+    !line.contains(':') && (line.ends_with('.') || line.ends_with('!'))
+}
+
+/// `Key: value` pairs from cleaned output, in order.
+///
+/// Indented lines without a key of their own (e.g. allowlisted subnets)
+/// are appended to the previous value, separated by commas.
+fn fields(text: &str) -> Vec<(String, String)> {
+    let mut fields: Vec<(String, String)> = vec![];
+    for line in text.lines() {
+        if line.trim().is_empty() || is_notice(line) {
+            continue;
+        }
+        let indented = line.starts_with(char::is_whitespace);
+        match line.split_once(':') {
+            Some((key, value)) if !indented => {
+                fields.push((key.trim().to_string(), value.trim().to_string()));
+            }
+            _ => {
+                if let Some((_, value)) = fields.last_mut() {
+                    if !value.is_empty() {
+                        value.push_str(", ");
+                    }
+                    value.push_str(line.trim());
+                }
+            }
+        }
+    }
+    fields
+}
+
+/// First non-empty value among `keys`, in order of preference
+fn field(fields: &[(String, String)], keys: &[&str]) -> Option<String> {
+    keys.iter().find_map(|key| {
+        fields
+            .iter()
+            .find(|(name, value)| name.eq_ignore_ascii_case(key) && !value.is_empty())
+            .map(|(_, value)| value.clone())
+    })
+}
+
+/// Parse `nordvpn status`
+pub fn status(raw: &str) -> ConnectionStatus {
+    let fields = fields(&clean(raw));
+    let state = field(&fields, &["Status"]).unwrap_or_default();
+
+    match state.to_lowercase().as_str() {
+        "connected" => match field(&fields, &["Country"]) {
+            Some(country) => ConnectionStatus::Connected {
+                country,
+                city: field(&fields, &["City"]),
+                // Older versions call the hostname "Current server"; newer
+                // ones print a display name as "Server" next to the hostname
+                server: field(&fields, &["Hostname", "Current server", "Server"]),
+                ip: field(&fields, &["IP", "Your new IP"]),
+            },
+            None => ConnectionStatus::Disconnected,
+        },
+        "connecting" => ConnectionStatus::Connecting,
+        _ => ConnectionStatus::Disconnected,
+    }
+}
+
+/// Parse `nordvpn status` including uptime and traffic
+pub fn status_details(raw: &str) -> StatusDetails {
+    let fields = fields(&clean(raw));
+    // "1.21 MiB received, 370.82 KiB sent"
+    let transfer = field(&fields, &["Transfer"]).and_then(|transfer| {
+        let (received, sent) = transfer.split_once(',')?;
+        Some((
+            bytes(received.trim().strip_suffix("received")?)?,
+            bytes(sent.trim().strip_suffix("sent")?)?,
+        ))
+    });
+    StatusDetails {
+        status: status(raw),
+        technology: technology(raw),
+        uptime: field(&fields, &["Uptime"]),
+        transfer,
+    }
+}
+
+/// A size such as "14.37 KiB" in bytes
+fn bytes(size: &str) -> Option<u64> {
+    let (number, unit) = size.trim().split_once(' ')?;
+    let exponent = ["B", "KiB", "MiB", "GiB", "TiB"]
+        .iter()
+        .position(|u| *u == unit.trim())?;
+    let number: f64 = number.parse().ok()?;
+    Some((number * 1024f64.powi(exponent as i32)).round() as u64)
+}
+
+/// Parse the final line of `nordvpn connect`.
+///
+/// "You are connected to Germany #1045 (de1045.nordvpn.com)!" names the
+/// server and its hostname; the country is the server name without its
+/// number. City and IP are only known to `status`.
+pub fn connected(raw: &str) -> Option<ConnectionStatus> {
+    let cleaned = clean(raw);
+    let line = cleaned
+        .lines()
+        .rev()
+        .find_map(|line| line.trim().strip_prefix("You are connected to "))?;
+    let line = line.trim_end_matches(['!', '.']);
+    let (name, hostname) = match line.rsplit_once(" (") {
+        Some((name, rest)) => (name, rest.strip_suffix(')')),
+        None => (line, None),
+    };
+    let country = name.split(" #").next().unwrap_or(name).trim();
+    if country.is_empty() {
+        return None;
+    }
+    Some(ConnectionStatus::Connected {
+        country: country.to_string(),
+        city: None,
+        server: hostname.map(str::to_string),
+        ip: None,
+    })
+}
+
+/// Technology in use, from `status` while connected, or configured, from
+/// `settings`
+pub fn technology(raw: &str) -> Option<Technology> {
+    let fields = fields(&clean(raw));
+    let technology = field(&fields, &["Current technology", "Technology"])?;
+    let protocol = field(&fields, &["Current protocol", "Protocol"]);
+    Technology::from_names(&technology, protocol.as_deref())
+}
+
+/// Parse a list of names, as printed by `countries`, `cities` and `groups`.
+///
+/// Depending on the version these come one per line, in tab-separated
+/// columns or comma-separated; names never contain spaces.
+pub fn names(raw: &str) -> Vec<String> {
+    clean(raw)
+        .lines()
+        .filter(|line| !is_notice(line))
+        .flat_map(|line| line.split(|c: char| c == ',' || c.is_whitespace()))
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parse `nordvpn settings`
+pub fn settings(raw: &str) -> Settings {
+    Settings {
+        entries: fields(&clean(raw)),
+    }
+}
+
+/// Parse `nordvpn account`
+pub fn account(raw: &str) -> Account {
+    let fields = fields(&clean(raw));
+    Account {
+        email: field(&fields, &["Email Address"]),
+        vpn_service: field(&fields, &["VPN Service"]),
+    }
+}
+
+/// Parse `nordvpn meshnet peer list`, skipping this device.
+pub fn meshnet_peers(raw: &str) -> Vec<MeshnetPeer> {
+    let mut peers = vec![];
+    let mut local = None;
+    // Peers are blocks of fields separated by blank lines
+    for block in clean(raw).split("\n\n") {
+        let mut lines = block.lines().peekable();
+        match lines
+            .peek()
+            .map(|line| line.trim().to_lowercase())
+            .as_deref()
+        {
+            Some("this device:") => local = None,
+            Some("local peers:") => local = Some(true),
+            Some("external peers:") => local = Some(false),
+            _ => {}
+        }
+        let Some(local) = local else {
+            continue;
+        };
+        let fields = fields(block);
+        if let Some(hostname) = field(&fields, &["Hostname"]) {
+            peers.push(MeshnetPeer {
+                hostname,
+                ip: field(&fields, &["Ip"]),
+                connected: field(&fields, &["Status"])
+                    .is_some_and(|status| status.eq_ignore_ascii_case("connected")),
+                local,
+            });
+        }
+    }
+    peers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{PortProtocol, PortRule};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    fn fixtures_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/nordvpn")
+    }
+
+    /// Every fixture as (version, file stem, contents)
+    fn fixtures() -> Vec<(String, String, String)> {
+        let mut fixtures = vec![];
+        for version in fs::read_dir(fixtures_dir()).unwrap() {
+            let version = version.unwrap().path();
+            if !version.is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(&version).unwrap() {
+                let path = file.unwrap().path();
+                fixtures.push((
+                    version.file_name().unwrap().to_string_lossy().into_owned(),
+                    path.file_stem().unwrap().to_string_lossy().into_owned(),
+                    fs::read_to_string(&path).unwrap(),
+                ));
+            }
+        }
+        fixtures.sort();
+        fixtures
+    }
+
+    fn fixture(version: &str, name: &str) -> String {
+        fs::read_to_string(fixtures_dir().join(version).join(format!("{name}.txt"))).unwrap()
+    }
+
+    #[test]
+    fn clean_strips_spinner_and_escapes() {
+        let raw =
+            "\r-\r  \r\r-\r  \r\x1b[33mnotice\x1b[0m\nStatus: Connected\r\n\x1b]0;title\x07done";
+        assert_eq!(clean(raw), "notice\nStatus: Connected\ndone");
+    }
+
+    #[test]
+    fn every_fixture_parses() {
+        let fixtures = fixtures();
+        assert!(!fixtures.is_empty());
+
+        for (version, name, raw) in fixtures {
+            let context = format!("{version}/{name}");
+            let cleaned = clean(&raw);
+            assert!(
+                !cleaned.contains(['\x1b', '\r']),
+                "{context}: noise left after cleaning"
+            );
+
+            let kind = name.split('-').next().unwrap();
+            match kind {
+                "status" => {
+                    let status = status(&raw);
+                    let expected = name.strip_prefix("status-").unwrap();
+                    let matches = match expected {
+                        "connected" => matches!(
+                            status,
+                            ConnectionStatus::Connected {
+                                city: Some(_),
+                                server: Some(_),
+                                ip: Some(_),
+                                ..
+                            }
+                        ),
+                        "connecting" => status == ConnectionStatus::Connecting,
+                        _ => status == ConnectionStatus::Disconnected,
+                    };
+                    assert!(matches, "{context}: parsed as {status:?}");
+                }
+                "countries" | "cities" | "groups" => {
+                    let names = names(&raw);
+                    assert!(!names.is_empty(), "{context}: no names");
+                    for name in &names {
+                        assert!(
+                            name.chars().all(|c| c.is_alphanumeric() || c == '_'),
+                            "{context}: bad name {name:?}"
+                        );
+                    }
+                }
+                "settings" => {
+                    let settings = settings(&raw);
+                    assert!(
+                        settings.get("Technology").is_some(),
+                        "{context}: no technology in {settings:?}"
+                    );
+                    assert!(settings.is_enabled("Firewall").is_some(), "{context}");
+                }
+                "account" => {
+                    assert_eq!(
+                        account(&raw).email.as_deref(),
+                        Some("user@example.com"),
+                        "{context}"
+                    );
+                }
+                "connect" => {
+                    assert!(
+                        matches!(connected(&raw), Some(ConnectionStatus::Connected { .. })),
+                        "{context}: no success line"
+                    );
+                }
+                "meshnet" => {
+                    let peers = meshnet_peers(&raw);
+                    assert_eq!(peers.is_empty(), name != "meshnet-peers", "{context}");
+                }
+                _ => panic!("{context}: unknown fixture kind {kind:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn status_across_versions() {
+        assert_eq!(
+            status(&fixture("3.8.10", "status-connected")),
+            ConnectionStatus::Connected {
+                country: "Germany".to_string(),
+                city: Some("Frankfurt".to_string()),
+                server: Some("de1045.nordvpn.com".to_string()),
+                ip: Some("185.130.184.86".to_string()),
+            }
+        );
+        // "Server: Germany #1045" must not shadow the hostname
+        assert_eq!(
+            status(&fixture("3.19.0", "status-connected")),
+            status(&fixture("3.8.10", "status-connected"))
+        );
+        assert_eq!(
+            status(&fixture("3.16.9", "status-connected")),
+            ConnectionStatus::Connected {
+                country: "Netherlands".to_string(),
+                city: Some("Amsterdam".to_string()),
+                server: Some("nl812.nordvpn.com".to_string()),
+                ip: Some("194.127.172.90".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn status_details_with_traffic() {
+        let details = status_details(&fixture("3.8.10", "status-connected"));
+        assert_eq!(details.technology, Some(Technology::OpenVpnUdp));
+        assert_eq!(details.uptime.as_deref(), Some("1 minute 3 seconds"));
+        assert_eq!(details.transfer, Some((1_268_777, 379_720)));
+        assert_eq!(
+            status_details(&fixture("3.19.0", "status-connected")).transfer,
+            Some((14_715, 6_021))
+        );
+        let disconnected = status_details(&fixture("3.19.0", "status-disconnected"));
+        assert_eq!(disconnected.status, ConnectionStatus::Disconnected);
+        assert_eq!(disconnected.transfer, None);
+    }
+
+    #[test]
+    fn connected_from_success_line() {
+        let germany = Some(ConnectionStatus::Connected {
+            country: "Germany".to_string(),
+            city: None,
+            server: Some("de1045.nordvpn.com".to_string()),
+            ip: None,
+        });
+        assert_eq!(connected(&fixture("3.8.10", "connect")), germany);
+        assert_eq!(connected(&fixture("3.19.0", "connect")), germany);
+        assert!(matches!(
+            connected(&fixture("3.16.9", "connect")),
+            Some(ConnectionStatus::Connected { country, .. }) if country == "Netherlands"
+        ));
+        assert_eq!(
+            connected("You are connected to United States #5102 (us5102.nordvpn.com)!"),
+            Some(ConnectionStatus::Connected {
+                country: "United States".to_string(),
+                city: None,
+                server: Some("us5102.nordvpn.com".to_string()),
+                ip: None,
+            })
+        );
+        assert_eq!(
+            connected("Connecting to Germany #1045 (de1045.nordvpn.com)"),
+            None
+        );
+    }
+
+    #[test]
+    fn technology_from_status_and_settings() {
+        let cases = [
+            ("3.8.10", "status-connected", Some(Technology::OpenVpnUdp)),
+            ("3.19.0", "status-connected", Some(Technology::NordLynx)),
+            ("3.19.0", "status-disconnected", None),
+            ("3.16.9", "settings", Some(Technology::NordLynx)),
+            ("3.19.0", "settings", Some(Technology::OpenVpnTcp)),
+        ];
+        for (version, name, expected) in cases {
+            assert_eq!(
+                technology(&fixture(version, name)),
+                expected,
+                "{version}/{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn names_in_every_layout() {
+        let expected = ["Albania", "Argentina", "Australia", "Austria", "Belgium"];
+        assert_eq!(names(&fixture("3.8.10", "countries"))[..5], expected);
+        for version in ["3.8.10", "3.16.9", "3.19.0"] {
+            let countries = names(&fixture(version, "countries"));
+            assert!(countries.contains(&"Bosnia_And_Herzegovina".to_string()));
+            assert!(countries.contains(&"United_States".to_string()));
+        }
+        assert_eq!(
+            names(&fixture("3.19.0", "cities-germany")),
+            names(&fixture("3.8.10", "cities-germany"))
+        );
+        assert_eq!(
+            names(&fixture("3.16.9", "groups")),
+            names(&fixture("3.19.0", "groups"))
+        );
+    }
+
+    #[test]
+    fn settings_values_and_continuations() {
+        let settings = settings(&fixture("3.8.10", "settings"));
+        assert_eq!(settings.get("technology"), Some("OpenVPN"));
+        assert_eq!(settings.get("DNS"), Some("103.86.96.100, 103.86.99.100"));
+        assert_eq!(
+            settings.get("Whitelisted ports"),
+            Some("22 (UDP|TCP), 8080 - 8090 (TCP)")
+        );
+        assert_eq!(settings.is_enabled("Kill Switch"), Some(false));
+
+        let settings = super::settings(&fixture("3.16.9", "settings"));
+        assert_eq!(
+            settings.get("Allowlisted subnets"),
+            Some("192.168.1.0/24, fd00::/8")
+        );
+        assert_eq!(settings.is_enabled("Meshnet"), Some(true));
+        assert_eq!(settings.get("Firewall Mark"), Some("0xe1f1"));
+    }
+
+    #[test]
+    fn allowlist_settings() {
+        let allowlist = settings(&fixture("3.8.10", "settings")).allowlist();
+        assert_eq!(allowlist.subnets, ["192.168.1.0/24"]);
+        assert_eq!(
+            allowlist.ports,
+            [
+                PortRule::port(22),
+                PortRule {
+                    start: 8080,
+                    end: 8090,
+                    protocol: Some(PortProtocol::Tcp),
+                },
+            ]
+        );
+
+        let allowlist = settings(&fixture("3.16.9", "settings")).allowlist();
+        assert_eq!(allowlist.subnets, ["192.168.1.0/24", "fd00::/8"]);
+        assert!(allowlist.ports.is_empty());
+    }
+
+    #[test]
+    fn tunnel_settings() {
+        let tunnel = settings(&fixture("3.8.10", "settings")).tunnel();
+        assert_eq!(tunnel.technology, Some(Technology::OpenVpnUdp));
+        assert_eq!(tunnel.obfuscate, Some(false));
+        assert_eq!(tunnel.post_quantum, None);
+        assert_eq!(tunnel.meshnet, None);
+
+        let tunnel = settings(&fixture("3.19.0", "settings")).tunnel();
+        assert_eq!(tunnel.technology, Some(Technology::OpenVpnTcp));
+        assert_eq!(tunnel.obfuscate, Some(true));
+        assert_eq!(tunnel.post_quantum, Some(false));
+        assert_eq!(tunnel.meshnet, Some(false));
+    }
+
+    #[test]
+    fn dns_settings() {
+        let ip = |s: &str| s.parse::<std::net::IpAddr>().unwrap();
+        let dns = settings(&fixture("3.8.10", "settings")).dns();
+        assert_eq!(dns.servers, [ip("103.86.96.100"), ip("103.86.99.100")]);
+        assert_eq!(dns.threat_protection, Some(false));
+
+        let dns = settings(&fixture("3.16.9", "settings")).dns();
+        assert!(dns.servers.is_empty());
+        assert_eq!(dns.threat_protection, Some(true));
+
+        let dns = settings(&fixture("3.19.0", "settings")).dns();
+        assert_eq!(dns.servers, [ip("1.1.1.1"), ip("9.9.9.9")]);
+        assert_eq!(dns.threat_protection, Some(false));
+    }
+
+    #[test]
+    fn account_fields() {
+        let account = account(&fixture("3.19.0", "account"));
+        assert_eq!(
+            account.vpn_service.as_deref(),
+            Some("Active (Expires on Mar 14th, 2027)")
+        );
+    }
+
+    #[test]
+    fn meshnet_peers_by_section() {
+        let peers = meshnet_peers(&fixture("3.16.9", "meshnet-peers"));
+        let summary: Vec<_> = peers
+            .iter()
+            .map(|peer| (peer.hostname.as_str(), peer.local, peer.connected))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("user-alps.nord", true, true),
+                ("user-andes.nord", false, false),
+                ("user-atlas.nord", false, true),
+            ]
+        );
+        assert_eq!(peers[0].ip.as_deref(), Some("100.64.12.2"));
+    }
+}
@@ -0,0 +1,315 @@
+//! Profiles: named bundles of a connect target and the settings to apply
+//! before connecting, defined in the `[profiles.<name>]` tables of the
+//! config file.
+
+use crate::nordvpn::{self, NordVPN};
+use crate::snapshot::SettingsSnapshot;
+use crate::types::{self, Allowlist, DnsChange, PortRule, SettingChange, Settings, Target, Toggle};
+use serde::Deserialize;
+use std::fmt;
+use std::net::IpAddr;
+
+/// A profile as written in the config file.
+///
+/// The target is a `country` (optionally with a `city`), a `group` or a
+/// `server`; without one the fastest server is used. Settings that are left
+/// out stay as they are.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub group: Option<String>,
+    /// Short hostname, e.g. "uk2345"
+    pub server: Option<String>,
+    /// "nordlynx", "openvpn" or "nordwhisper"
+    pub technology: Option<String>,
+    /// "udp" or "tcp", only with OpenVPN
+    pub protocol: Option<String>,
+    pub obfuscate: Option<bool>,
+    pub kill_switch: Option<bool>,
+    /// Custom DNS servers; an empty list means NordVPN's DNS
+    pub dns: Option<Vec<IpAddr>>,
+    /// The complete allowlist; entries not listed are removed
+    pub allowlist: Option<Allowlist>,
+}
+
+/// One step of applying a profile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    Setting {
+        change: SettingChange,
+        /// Why the step is needed, if the settings file did not ask for it
+        reason: Option<&'static str>,
+    },
+    Toggle(Toggle, bool),
+    Dns(DnsChange),
+    Subnet {
+        subnet: String,
+        allow: bool,
+    },
+    Port {
+        rule: PortRule,
+        allow: bool,
+    },
+    /// Always the last step
+    Connect(Target),
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Setting { change, .. } => write!(f, "{}", change),
+            Step::Toggle(toggle, enabled) => {
+                write!(f, "turn {} {}", if *enabled { "on" } else { "off" }, toggle)
+            }
+            Step::Dns(DnsChange::Servers(servers)) => {
+                write!(f, "set DNS to {}", types::join_addresses(servers))
+            }
+            Step::Dns(DnsChange::Reset) => write!(f, "reset DNS to NordVPN's"),
+            Step::Dns(DnsChange::ThreatProtection(enabled)) => write!(
+                f,
+                "turn {} Threat Protection Lite",
+                if *enabled { "on" } else { "off" }
+            ),
+            Step::Subnet {
+                subnet,
+                allow: true,
+            } => write!(f, "allowlist subnet {}", subnet),
+            Step::Subnet {
+                subnet,
+                allow: false,
+            } => write!(f, "remove subnet {} from allowlist", subnet),
+            Step::Port { rule, allow: true } => write!(f, "allowlist {}", rule.describe()),
+            Step::Port { rule, allow: false } => {
+                write!(f, "remove {} from allowlist", rule.describe())
+            }
+            Step::Connect(target) => write!(f, "connect to {}", target),
+        }
+    }
+}
+
+impl Step {
+    /// Why a step nobody asked for is needed
+    pub fn reason(&self) -> Option<&'static str> {
+        match self {
+            Step::Setting { reason, .. } => *reason,
+            _ => None,
+        }
+    }
+
+    /// Run a settings step; connecting is left to the caller, which
+    /// decides how to show progress
+    pub fn apply(&self) -> nordvpn::Result<()> {
+        match self {
+            Step::Setting { change, .. } => NordVPN::set(*change),
+            Step::Toggle(toggle, enabled) => NordVPN::set_toggle(*toggle, *enabled),
+            Step::Dns(DnsChange::Servers(servers)) => NordVPN::set_dns(servers),
+            Step::Dns(DnsChange::Reset) => NordVPN::reset_dns(),
+            Step::Dns(DnsChange::ThreatProtection(enabled)) => {
+                NordVPN::set_threat_protection(*enabled)
+            }
+            Step::Subnet { subnet, allow } => NordVPN::allowlist_subnet(subnet, *allow),
+            Step::Port { rule, allow } => NordVPN::allowlist_port(rule, *allow),
+            Step::Connect(target) => NordVPN::connect(target, |_| {}).map(|_| ()),
+        }
+    }
+}
+
+impl Profile {
+    /// Where `nordvpn connect` is pointed
+    pub fn target(&self) -> Result<Target, String> {
+        match (&self.country, &self.city, &self.group, &self.server) {
+            (None, None, None, None) => Ok(Target::Quick),
+            (Some(country), None, None, None) => Ok(Target::Country(country.clone())),
+            (Some(country), Some(city), None, None) => Ok(Target::City {
+                country: country.clone(),
+                city: city.clone(),
+            }),
+            (None, Some(_), None, None) => Err("city needs a country".to_string()),
+            (None, None, Some(group), None) => Ok(Target::Group(group.clone())),
+            (None, None, None, Some(server)) => {
+                crate::servers::parse_server(server).map(Target::Server)
+            }
+            _ => Err("set only one of country, group and server".to_string()),
+        }
+    }
+
+    /// The settings part of the profile
+    pub fn settings(&self) -> SettingsSnapshot {
+        SettingsSnapshot {
+            technology: self.technology.clone(),
+            protocol: self.protocol.clone(),
+            obfuscate: self.obfuscate,
+            kill_switch: self.kill_switch,
+            dns: self.dns.clone(),
+            allowlist: self.allowlist.clone(),
+            ..SettingsSnapshot::default()
+        }
+    }
+
+    /// Check everything that can be checked without the daemon
+    pub fn validate(&self) -> Result<(), String> {
+        self.target()?;
+        self.settings().validate()
+    }
+
+    /// Steps that take the daemon from `settings` to this profile, ending
+    /// with the connect
+    pub fn plan(&self, settings: &Settings) -> Result<Vec<Step>, String> {
+        let mut steps = self.settings().plan(settings)?;
+        steps.push(Step::Connect(self.target()?));
+        Ok(steps)
+    }
+}
+
+/// Apply a profile outside the TUI, printing each step's outcome.
+///
+/// Stops at the first failing step and returns whether all of them worked.
+pub fn apply_printing(name: &str, profile: &Profile) -> bool {
+    println!("Applying profile {}", name);
+    match NordVPN::get_settings()
+        .map_err(|e| e.to_string())
+        .and_then(|settings| profile.plan(&settings))
+    {
+        Ok(steps) => apply_steps_printing(&steps),
+        Err(e) => {
+            println!("✗ {}", e);
+            false
+        }
+    }
+}
+
+/// Run steps in order, printing each one's outcome and skipping the rest
+/// after the first failure
+pub fn apply_steps_printing(steps: &[Step]) -> bool {
+    for (i, step) in steps.iter().enumerate() {
+        let result = match step {
+            Step::Connect(target) => {
+                NordVPN::connect(target, |line| println!("  {}", line)).map(|_| ())
+            }
+            step => step.apply(),
+        };
+        match result {
+            Ok(()) => println!("✓ {}", step),
+            Err(e) => {
+                println!("✗ {}: {}", step, e);
+                for skipped in &steps[i + 1..] {
+                    println!("- {} (skipped)", skipped);
+                }
+                return false;
+            }
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::parse;
+
+    const SETTINGS: &str = "\
+Technology: NORDLYNX
+Kill Switch: disabled
+Obfuscate: disabled
+Post-quantum VPN: disabled
+Meshnet: disabled
+DNS: disabled
+Allowlisted subnets:
+\t192.168.1.0/24
+\t10.0.0.0/8
+Allowlisted ports:
+\t22 (UDP|TCP)
+";
+
+    fn profile(toml: &str) -> Result<Profile, String> {
+        let config =
+            Config::parse(&format!("[profiles.test]\n{}", toml)).map_err(|e| e.to_string())?;
+        Ok(config.profiles["test"].clone())
+    }
+
+    #[test]
+    fn plans_settings_diff_then_connect() {
+        let profile = profile(
+            r#"
+            group = "P2P"
+            technology = "openvpn"
+            protocol = "tcp"
+            obfuscate = true
+            kill_switch = true
+            dns = ["1.1.1.1"]
+            allowlist = { subnets = ["192.168.1.0/24"], ports = [
+                22,
+                8080,
+                { start = 6881, end = 6889, protocol = "udp" },
+            ] }
+        "#,
+        )
+        .unwrap();
+        let steps = profile.plan(&parse::settings(SETTINGS)).unwrap();
+        let steps: Vec<String> = steps.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            steps,
+            [
+                "switch technology to OpenVPN (TCP)",
+                "turn on obfuscation",
+                "turn on kill switch",
+                "set DNS to 1.1.1.1",
+                "remove subnet 10.0.0.0/8 from allowlist",
+                "allowlist port 8080",
+                "allowlist ports 6881 - 6889 (UDP)",
+                "connect to P2P group",
+            ]
+        );
+    }
+
+    #[test]
+    fn unchanged_settings_only_connect() {
+        let profile = profile(
+            r#"
+            country = "Germany"
+            city = "Frankfurt"
+            technology = "nordlynx"
+            kill_switch = false
+            dns = []
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            profile.plan(&parse::settings(SETTINGS)).unwrap(),
+            [Step::Connect(Target::City {
+                country: "Germany".to_string(),
+                city: "Frankfurt".to_string(),
+            })]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_profiles() {
+        for (toml, error) in [
+            ("country = \"Germany\"\ngroup = \"P2P\"", "only one of"),
+            ("city = \"Frankfurt\"", "city needs a country"),
+            ("server = \"uk\"", "no server number"),
+            ("technology = \"ikev2\"", "unknown technology"),
+            (
+                "technology = \"nordlynx\"\nprotocol = \"tcp\"",
+                "no protocol choice",
+            ),
+            (
+                "technology = \"nordlynx\"\nobfuscate = true",
+                "obfuscate needs",
+            ),
+            ("allowlist = { subnets = [\"10.0.0.0\"] }", "not a subnet"),
+            (
+                "dns = [\"1.1.1.1\", \"1.0.0.1\", \"8.8.8.8\", \"8.8.4.4\"]",
+                "at most 3",
+            ),
+        ] {
+            let e = profile(toml).unwrap_err();
+            assert!(e.contains(error), "{toml:?} gave {e:?}");
+        }
+        assert!(profile("dns = [\"not an address\"]").is_err());
+    }
+}
@@ -0,0 +1,185 @@
+//! Individual servers for the server search: validating hostnames the user
+//! types and a cache of recommended servers to complete them from.
+
+use crate::http;
+use crate::state::unix_now;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Suffix every NordVPN server hostname shares
+const DOMAIN: &str = ".nordvpn.com";
+
+/// Cached recommendations older than this are refreshed when searching
+pub const CACHE_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// A server suggested by the NordVPN API
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recommendation {
+    /// Full hostname, e.g. "uk2345.nordvpn.com"
+    pub hostname: String,
+    /// Display name, e.g. "United Kingdom #2345"
+    pub name: String,
+    /// Current load in percent
+    #[serde(default)]
+    pub load: u8,
+}
+
+impl Recommendation {
+    /// Hostname without the domain, as `nordvpn connect` takes it
+    pub fn server(&self) -> &str {
+        self.hostname.strip_suffix(DOMAIN).unwrap_or(&self.hostname)
+    }
+}
+
+/// Last fetched recommendations, kept in
+/// `$XDG_CACHE_HOME/nordvpn-tui/servers.toml`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerCache {
+    /// Seconds since the Unix epoch
+    pub fetched_at: u64,
+    pub servers: Vec<Recommendation>,
+    /// Where this cache was loaded from and is saved back to
+    #[serde(skip)]
+    file: Option<PathBuf>,
+}
+
+impl ServerCache {
+    /// Location of the cache file
+    pub fn path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+        Some(base.join("nordvpn-tui").join("servers.toml"))
+    }
+
+    /// Load the cache, starting empty if there is none or it is unreadable
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        let mut cache: Self = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default();
+        cache.file = Some(path);
+        cache
+    }
+
+    /// Write the cache file; caches not loaded from disk stay in memory
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.file else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let contents = toml::to_string(self).map_err(io::Error::other)?;
+        std::fs::write(path, contents)
+    }
+
+    /// Replace the cached servers with freshly fetched ones
+    pub fn update(&mut self, servers: Vec<Recommendation>) {
+        self.servers = servers;
+        self.fetched_at = unix_now();
+    }
+
+    /// Whether the cache is empty or too old to rely on
+    pub fn is_stale(&self) -> bool {
+        self.servers.is_empty()
+            || unix_now().saturating_sub(self.fetched_at) > CACHE_MAX_AGE.as_secs()
+    }
+
+    /// Cached server with the given short hostname
+    pub fn get(&self, server: &str) -> Option<&Recommendation> {
+        self.servers.iter().find(|r| r.server() == server)
+    }
+}
+
+/// Fetch recommended servers from the NordVPN API
+pub fn fetch(url: &str, timeout: Duration) -> Result<Vec<Recommendation>, String> {
+    let body = http::get(url, timeout)?;
+    serde_json::from_str(&body).map_err(|e| format!("Unexpected response from {}: {}", url, e))
+}
+
+/// Validate a server hostname typed by the user and return its short form.
+///
+/// Accepts `uk2345`, `us-ca12` (double VPN pairs) and the same with the
+/// `.nordvpn.com` suffix, in any case.
+pub fn parse_server(input: &str) -> Result<String, String> {
+    let lower = input.trim().to_lowercase();
+    let server = lower.strip_suffix(DOMAIN).unwrap_or(&lower);
+    let letters_end = server
+        .find(|c: char| c.is_ascii_digit())
+        .ok_or_else(|| format!("{:?} has no server number, e.g. uk2345", input.trim()))?;
+    let (prefix, number) = server.split_at(letters_end);
+    let valid_prefix = prefix
+        .split('-')
+        .all(|part| part.len() >= 2 && part.chars().all(|c| c.is_ascii_lowercase()))
+        && prefix.split('-').count() <= 2;
+    if !valid_prefix || !number.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!(
+            "{:?} is not a server hostname, e.g. uk2345",
+            input.trim()
+        ));
+    }
+    Ok(server.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn parses_server_hostnames() {
+        assert_eq!(parse_server("uk2345"), Ok("uk2345".to_string()));
+        assert_eq!(
+            parse_server(" UK2345.nordvpn.com "),
+            Ok("uk2345".to_string())
+        );
+        assert_eq!(parse_server("us-ca12"), Ok("us-ca12".to_string()));
+        for invalid in ["", "uk", "2345", "u2345", "uk23a5", "uk 2345", "a-b-c12"] {
+            assert!(parse_server(invalid).is_err(), "{invalid:?} accepted");
+        }
+    }
+
+    #[test]
+    fn fetches_recommendations() {
+        let url = testing::serve_once(
+            "application/json",
+            r#"[{"id": 1, "name": "United Kingdom #2345", "hostname": "uk2345.nordvpn.com", "load": 17, "status": "online"}]"#,
+        );
+        let servers = fetch(&url, Duration::from_secs(5)).unwrap();
+        assert_eq!(
+            servers,
+            [Recommendation {
+                hostname: "uk2345.nordvpn.com".to_string(),
+                name: "United Kingdom #2345".to_string(),
+                load: 17,
+            }]
+        );
+        assert_eq!(servers[0].server(), "uk2345");
+    }
+
+    #[test]
+    fn rejects_unexpected_response() {
+        let url = testing::serve_once("application/json", "<html>rate limited</html>");
+        assert!(fetch(&url, Duration::from_secs(5)).is_err());
+    }
+
+    #[test]
+    fn stale_until_updated() {
+        let mut cache = ServerCache::default();
+        assert!(cache.is_stale());
+        cache.update(vec![Recommendation {
+            hostname: "de1045.nordvpn.com".to_string(),
+            name: "Germany #1045".to_string(),
+            load: 5,
+        }]);
+        assert!(!cache.is_stale());
+        assert_eq!(cache.get("de1045").map(|r| r.load), Some(5));
+    }
+}
@@ -0,0 +1,617 @@
+//! Settings snapshots: the daemon settings written to or read from a TOML
+//! or JSON file, to compare machines and copy settings between them.
+
+use crate::cli::SettingsCommand;
+use crate::nordvpn::NordVPN;
+use crate::profile::{self, Step};
+use crate::types::{self, DnsChange, SettingChange, Settings, Technology, Toggle};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Daemon settings as stored in a snapshot file.
+///
+/// Settings that are left out, or that the CLI did not report when the
+/// snapshot was taken, are not changed on import.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SettingsSnapshot {
+    /// "nordlynx", "openvpn" or "nordwhisper"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub technology: Option<String>,
+    /// "udp" or "tcp", only with OpenVPN
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub obfuscate: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_quantum: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meshnet: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kill_switch: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub firewall: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_connect: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lan_discovery: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notify: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threat_protection: Option<bool>,
+    /// Custom DNS servers; an empty list means NordVPN's DNS
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns: Option<Vec<IpAddr>>,
+    /// The complete allowlist; entries not listed are removed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowlist: Option<types::Allowlist>,
+}
+
+/// File format of a snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Json,
+}
+
+impl Format {
+    /// JSON for `.json` files, TOML for anything else
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Format::Json,
+            _ => Format::Toml,
+        }
+    }
+}
+
+/// A setting whose value in a snapshot differs from the current one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    pub setting: &'static str,
+    /// `None` if the CLI does not report the setting
+    pub current: Option<String>,
+    pub wanted: String,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} → {}",
+            self.setting,
+            self.current.as_deref().unwrap_or("not reported"),
+            self.wanted
+        )
+    }
+}
+
+impl SettingsSnapshot {
+    /// Take a snapshot of parsed `nordvpn settings`
+    pub fn from_settings(settings: &Settings) -> Self {
+        let tunnel = settings.tunnel();
+        let dns = settings.dns();
+        let toggle = |toggle: Toggle| settings.is_enabled(toggle.setting());
+        Self {
+            technology: tunnel
+                .technology
+                .map(|technology| technology.technology_arg().to_lowercase()),
+            protocol: tunnel
+                .technology
+                .and_then(Technology::protocol_arg)
+                .map(str::to_lowercase),
+            obfuscate: tunnel.obfuscate,
+            post_quantum: tunnel.post_quantum,
+            meshnet: tunnel.meshnet,
+            kill_switch: toggle(Toggle::KillSwitch),
+            firewall: toggle(Toggle::Firewall),
+            auto_connect: toggle(Toggle::AutoConnect),
+            lan_discovery: toggle(Toggle::LanDiscovery),
+            notify: toggle(Toggle::Notify),
+            threat_protection: dns.threat_protection,
+            dns: settings.get("DNS").map(|_| dns.servers),
+            allowlist: Some(settings.allowlist()),
+        }
+    }
+
+    pub fn toggle(&self, toggle: Toggle) -> Option<bool> {
+        match toggle {
+            Toggle::KillSwitch => self.kill_switch,
+            Toggle::Firewall => self.firewall,
+            Toggle::AutoConnect => self.auto_connect,
+            Toggle::LanDiscovery => self.lan_discovery,
+            Toggle::Notify => self.notify,
+        }
+    }
+
+    /// The technology and protocol, checked against what the CLI accepts
+    pub fn technology(&self) -> Result<Option<Technology>, String> {
+        match (&self.technology, &self.protocol) {
+            (None, None) => Ok(None),
+            (None, Some(_)) => Err("protocol needs technology = \"openvpn\"".to_string()),
+            (Some(name), protocol) => {
+                let technology = Technology::from_names(name, protocol.as_deref())
+                    .ok_or_else(|| format!("unknown technology {:?}", name))?;
+                match protocol.as_deref().map(str::to_lowercase).as_deref() {
+                    None => {}
+                    Some("udp" | "tcp") if technology.is_openvpn() => {}
+                    Some("udp" | "tcp") => {
+                        return Err(format!("{} has no protocol choice", technology));
+                    }
+                    Some(other) => return Err(format!("unknown protocol {:?}", other)),
+                }
+                Ok(Some(technology))
+            }
+        }
+    }
+
+    /// Check everything that can be checked without the daemon
+    pub fn validate(&self) -> Result<(), String> {
+        let technology = self.technology()?;
+        if self.obfuscate == Some(true) && technology.is_some_and(|t| !t.is_openvpn()) {
+            return Err("obfuscate needs technology = \"openvpn\"".to_string());
+        }
+        if self.post_quantum == Some(true) {
+            if technology.is_some_and(|t| t != Technology::NordLynx) {
+                return Err("post_quantum needs technology = \"nordlynx\"".to_string());
+            }
+            if self.meshnet == Some(true) {
+                return Err("post_quantum cannot be used with meshnet".to_string());
+            }
+        }
+        if let Some(servers) = &self.dns {
+            if servers.len() > types::MAX_DNS_SERVERS {
+                return Err(format!(
+                    "at most {} DNS servers can be set",
+                    types::MAX_DNS_SERVERS
+                ));
+            }
+            if !servers.is_empty() && self.threat_protection == Some(true) {
+                return Err("threat_protection needs NordVPN's DNS (dns = [])".to_string());
+            }
+        }
+        for subnet in self.allowlist.iter().flat_map(|a| &a.subnets) {
+            if !is_subnet(subnet) {
+                return Err(format!("{:?} is not a subnet like 192.168.1.0/24", subnet));
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the snapshot in the given format
+    pub fn encode(&self, format: Format) -> String {
+        match format {
+            Format::Toml => toml::to_string(self).expect("snapshots serialize to TOML"),
+            Format::Json => {
+                let mut json =
+                    serde_json::to_string_pretty(self).expect("snapshots serialize to JSON");
+                json.push('\n');
+                json
+            }
+        }
+    }
+
+    /// Read and check a snapshot
+    pub fn decode(contents: &str, format: Format) -> Result<Self, String> {
+        let snapshot: Self = match format {
+            Format::Toml => toml::from_str(contents).map_err(|e| e.to_string())?,
+            Format::Json => serde_json::from_str(contents).map_err(|e| e.to_string())?,
+        };
+        snapshot.validate()?;
+        Ok(snapshot)
+    }
+
+    /// Read a snapshot file, in the format its extension names
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::decode(&contents, Format::for_path(path))
+            .map_err(|e| format!("Invalid settings file {}: {}", path.display(), e))
+    }
+
+    /// Write a snapshot file, in the format its extension names
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        std::fs::write(path, self.encode(Format::for_path(path)))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Every setting with its value as shown to the user, `None` where the
+    /// snapshot leaves it out
+    pub fn rows(&self) -> Vec<(&'static str, Option<String>)> {
+        let on_off =
+            |value: Option<bool>| value.map(|on| if on { "on" } else { "off" }.to_string());
+        let technology = match self.technology() {
+            Ok(Some(technology)) => Some(technology.to_string()),
+            _ => self.technology.clone(),
+        };
+        let mut rows = vec![
+            ("Technology", technology),
+            ("Obfuscate", on_off(self.obfuscate)),
+            ("Post-quantum VPN", on_off(self.post_quantum)),
+            ("Meshnet", on_off(self.meshnet)),
+        ];
+        rows.extend(
+            Toggle::ALL
+                .iter()
+                .map(|toggle| (toggle.setting(), on_off(self.toggle(*toggle)))),
+        );
+        rows.extend([
+            ("Threat Protection Lite", on_off(self.threat_protection)),
+            (
+                "DNS",
+                self.dns.as_ref().map(|servers| {
+                    if servers.is_empty() {
+                        "NordVPN default".to_string()
+                    } else {
+                        types::join_addresses(servers)
+                    }
+                }),
+            ),
+            (
+                "Allowlisted subnets",
+                self.allowlist
+                    .as_ref()
+                    .map(|allowlist| none_or_join(&allowlist.subnets)),
+            ),
+            (
+                "Allowlisted ports",
+                self.allowlist.as_ref().map(|allowlist| {
+                    let ports: Vec<String> =
+                        allowlist.ports.iter().map(ToString::to_string).collect();
+                    none_or_join(&ports)
+                }),
+            ),
+        ]);
+        rows
+    }
+
+    /// Settings this snapshot would change in `current`
+    pub fn diff(&self, current: &SettingsSnapshot) -> Vec<Difference> {
+        self.rows()
+            .into_iter()
+            .zip(current.rows())
+            .filter_map(|((setting, wanted), (_, current))| {
+                let wanted = wanted?;
+                (current.as_ref() != Some(&wanted)).then_some(Difference {
+                    setting,
+                    current,
+                    wanted,
+                })
+            })
+            .collect()
+    }
+
+    /// Steps that take the daemon from `settings` to this snapshot
+    pub fn plan(&self, settings: &Settings) -> Result<Vec<Step>, String> {
+        self.validate()?;
+        let mut steps = vec![];
+
+        let mut changes = vec![];
+        if let Some(technology) = self.technology()? {
+            changes.push(SettingChange::Technology(technology));
+        }
+        changes.extend(self.obfuscate.map(SettingChange::Obfuscate));
+        changes.extend(self.post_quantum.map(SettingChange::PostQuantum));
+        changes.extend(self.meshnet.map(SettingChange::Meshnet));
+        let plan = settings.tunnel().plan_all(&changes)?;
+        steps.extend(plan.steps.iter().map(|step| Step::Setting {
+            change: step.change,
+            reason: step.reason,
+        }));
+
+        for toggle in Toggle::ALL {
+            if let Some(enabled) = self.toggle(toggle)
+                && settings.is_enabled(toggle.setting()) != Some(enabled)
+            {
+                steps.push(Step::Toggle(toggle, enabled));
+            }
+        }
+
+        // Threat Protection Lite and custom DNS exclude each other, so the
+        // one being turned off goes first
+        let dns = settings.dns();
+        let threat_protection = self
+            .threat_protection
+            .filter(|enabled| dns.threat_protection != Some(*enabled));
+        if threat_protection == Some(false) {
+            steps.push(Step::Dns(DnsChange::ThreatProtection(false)));
+        }
+        if let Some(servers) = &self.dns
+            && *servers != dns.servers
+        {
+            steps.push(Step::Dns(if servers.is_empty() {
+                DnsChange::Reset
+            } else {
+                DnsChange::Servers(servers.clone())
+            }));
+        }
+        if threat_protection == Some(true) {
+            steps.push(Step::Dns(DnsChange::ThreatProtection(true)));
+        }
+
+        if let Some(wanted) = &self.allowlist {
+            let current = settings.allowlist();
+            for subnet in current
+                .subnets
+                .iter()
+                .filter(|s| !wanted.subnets.contains(s))
+            {
+                steps.push(Step::Subnet {
+                    subnet: subnet.clone(),
+                    allow: false,
+                });
+            }
+            for subnet in wanted
+                .subnets
+                .iter()
+                .filter(|s| !current.subnets.contains(s))
+            {
+                steps.push(Step::Subnet {
+                    subnet: subnet.clone(),
+                    allow: true,
+                });
+            }
+            for rule in current.ports.iter().filter(|r| !wanted.ports.contains(r)) {
+                steps.push(Step::Port {
+                    rule: *rule,
+                    allow: false,
+                });
+            }
+            for rule in wanted.ports.iter().filter(|r| !current.ports.contains(r)) {
+                steps.push(Step::Port {
+                    rule: *rule,
+                    allow: true,
+                });
+            }
+        }
+
+        Ok(steps)
+    }
+}
+
+/// Run `nordvpn-tui settings ...` and return the exit code.
+///
+/// `diff` exits with 1 when there are differences, like diff(1).
+pub fn run(command: &SettingsCommand) -> i32 {
+    let current = match NordVPN::get_settings() {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("error: failed to read settings: {}", e);
+            return 2;
+        }
+    };
+    match command {
+        SettingsCommand::Export { path: None, json } => {
+            let format = if *json { Format::Json } else { Format::Toml };
+            print!(
+                "{}",
+                SettingsSnapshot::from_settings(&current).encode(format)
+            );
+            0
+        }
+        SettingsCommand::Export {
+            path: Some(path), ..
+        } => match SettingsSnapshot::from_settings(&current).save(path) {
+            Ok(()) => {
+                println!("Saved settings to {}", path.display());
+                0
+            }
+            Err(e) => {
+                eprintln!("error: {}", e);
+                2
+            }
+        },
+        SettingsCommand::Diff(path) | SettingsCommand::Import { path, .. } => {
+            let wanted = match SettingsSnapshot::load(path) {
+                Ok(wanted) => wanted,
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    return 2;
+                }
+            };
+            let diff = wanted.diff(&SettingsSnapshot::from_settings(&current));
+            if diff.is_empty() {
+                println!("Settings match {}", path.display());
+                return 0;
+            }
+            for difference in &diff {
+                println!("{}", difference);
+            }
+            let SettingsCommand::Import { yes, .. } = command else {
+                return 1;
+            };
+            let steps = match wanted.plan(&current) {
+                Ok(steps) => steps,
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    return 2;
+                }
+            };
+            // The plan can hold steps the diff does not show, such as
+            // turning off a setting another one conflicts with
+            println!();
+            for step in &steps {
+                match step.reason() {
+                    Some(reason) => println!("• {}: {}", step, reason),
+                    None => println!("• {}", step),
+                }
+            }
+            if !yes && !confirm(&format!("Apply {} changes? [y/N] ", steps.len())) {
+                println!("Settings left unchanged");
+                return 1;
+            }
+            if profile::apply_steps_printing(&steps) {
+                0
+            } else {
+                1
+            }
+        }
+    }
+}
+
+/// Ask a yes/no question on the terminal; anything but "y" is a no
+fn confirm(question: &str) -> bool {
+    print!("{}", question);
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer).is_ok() && matches!(answer.trim(), "y" | "Y" | "yes")
+}
+
+fn none_or_join(entries: &[String]) -> String {
+    if entries.is_empty() {
+        "none".to_string()
+    } else {
+        entries.join(", ")
+    }
+}
+
+/// Whether `input` is an address with a prefix length, e.g. "10.0.0.0/8"
+fn is_subnet(input: &str) -> bool {
+    let Some((address, prefix)) = input.split_once('/') else {
+        return false;
+    };
+    let max = match address.parse::<IpAddr>() {
+        Ok(IpAddr::V4(_)) => 32,
+        Ok(IpAddr::V6(_)) => 128,
+        Err(_) => return false,
+    };
+    prefix.parse::<u8>().is_ok_and(|prefix| prefix <= max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+    use crate::types::{PortProtocol, PortRule};
+
+    const SETTINGS: &str = "\
+Technology: OPENVPN
+Protocol: TCP
+Firewall: enabled
+Kill Switch: disabled
+Threat Protection Lite: disabled
+Obfuscate: enabled
+Meshnet: disabled
+DNS: 1.1.1.1, 1.0.0.1
+Allowlisted subnets:
+\t192.168.1.0/24
+Allowlisted ports:
+\t22 (UDP|TCP)
+\t8080 - 8090 (TCP)
+";
+
+    #[test]
+    fn round_trips_through_toml_and_json() {
+        let snapshot = SettingsSnapshot::from_settings(&parse::settings(SETTINGS));
+        assert_eq!(snapshot.technology.as_deref(), Some("openvpn"));
+        assert_eq!(snapshot.protocol.as_deref(), Some("tcp"));
+        assert_eq!(snapshot.post_quantum, None);
+        assert_eq!(snapshot.dns.as_ref().map(Vec::len), Some(2));
+        for format in [Format::Toml, Format::Json] {
+            let decoded = SettingsSnapshot::decode(&snapshot.encode(format), format);
+            assert_eq!(decoded, Ok(snapshot.clone()), "{format:?}");
+        }
+        assert_eq!(Format::for_path(Path::new("laptop.JSON")), Format::Json);
+        assert_eq!(Format::for_path(Path::new("laptop.conf")), Format::Toml);
+    }
+
+    #[test]
+    fn diffs_and_plans_only_listed_settings() {
+        let current = parse::settings(SETTINGS);
+        let wanted = SettingsSnapshot::decode(
+            r#"
+            technology = "nordlynx"
+            firewall = true
+            kill_switch = true
+            threat_protection = true
+            dns = []
+            allowlist = { subnets = ["192.168.1.0/24"], ports = [] }
+        "#,
+            Format::Toml,
+        )
+        .unwrap();
+
+        // Port ranges are exported like single ports
+        let exported = SettingsSnapshot::from_settings(&current);
+        assert_eq!(
+            exported.allowlist.clone().unwrap_or_default().ports,
+            [
+                PortRule::port(22),
+                PortRule {
+                    start: 8080,
+                    end: 8090,
+                    protocol: Some(PortProtocol::Tcp),
+                },
+            ]
+        );
+        assert!(
+            exported
+                .encode(Format::Toml)
+                .contains(r#"ports = [22, { start = 8080, end = 8090, protocol = "tcp" }]"#)
+        );
+
+        let diff: Vec<String> = wanted
+            .diff(&exported)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            diff,
+            [
+                "Technology: OpenVPN (TCP) → NordLynx",
+                "Kill Switch: off → on",
+                "Threat Protection Lite: off → on",
+                "DNS: 1.1.1.1, 1.0.0.1 → NordVPN default",
+                "Allowlisted ports: 22, 8080 - 8090 (TCP) → none",
+            ]
+        );
+
+        let steps: Vec<String> = wanted
+            .plan(&current)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                "turn off obfuscation",
+                "switch technology to NordLynx",
+                "turn on kill switch",
+                "reset DNS to NordVPN's",
+                "turn on Threat Protection Lite",
+                "remove port 22 from allowlist",
+                "remove ports 8080 - 8090 (TCP) from allowlist",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_conflicting_settings() {
+        for (toml, error) in [
+            (
+                "threat_protection = true\ndns = [\"1.1.1.1\"]",
+                "needs NordVPN's DNS",
+            ),
+            (
+                "technology = \"openvpn\"\npost_quantum = true",
+                "needs technology",
+            ),
+            (
+                "meshnet = true\npost_quantum = true",
+                "cannot be used with meshnet",
+            ),
+            ("kill_switch = \"yes\"", "invalid type"),
+            ("autoconnect = true", "unknown field"),
+        ] {
+            let e = SettingsSnapshot::decode(toml, Format::Toml).unwrap_err();
+            assert!(e.contains(error), "{toml:?} gave {e:?}");
+        }
+    }
+}
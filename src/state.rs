@@ -0,0 +1,166 @@
+use crate::types::Target;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Oldest history entries are dropped beyond this many
+const HISTORY_LIMIT: usize = 200;
+
+/// State persisted between runs in `$XDG_STATE_HOME/nordvpn-tui/state.toml`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct State {
+    /// Favorite targets, in the order they were added
+    pub favorites: Vec<Target>,
+    /// Connection history, oldest first
+    pub history: Vec<HistoryEntry>,
+    /// Where this state was loaded from and is saved back to
+    #[serde(skip)]
+    file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryKind {
+    Connected,
+    Disconnected,
+    ConnectFailed,
+    /// The tunnel went down without the user asking
+    Dropped,
+    ReconnectAttempt,
+    ReconnectFailed,
+    Reconnected,
+    /// The watchdog ran out of attempts
+    GaveUp,
+}
+
+impl fmt::Display for HistoryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            HistoryKind::Connected => "connected",
+            HistoryKind::Disconnected => "disconnected",
+            HistoryKind::ConnectFailed => "connect failed",
+            HistoryKind::Dropped => "dropped",
+            HistoryKind::ReconnectAttempt => "reconnecting",
+            HistoryKind::ReconnectFailed => "reconnect failed",
+            HistoryKind::Reconnected => "reconnected",
+            HistoryKind::GaveUp => "gave up",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub kind: HistoryKind,
+    pub target: Option<Target>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub detail: String,
+}
+
+impl State {
+    /// Location of the state file
+    pub fn path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state"))
+            })?;
+        Some(base.join("nordvpn-tui").join("state.toml"))
+    }
+
+    /// Load saved state, starting fresh if there is none or it is unreadable
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        let mut state: Self = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default();
+        state.file = Some(path);
+        state
+    }
+
+    /// Write the state file, creating its directory if needed.
+    ///
+    /// State that was not loaded from disk, like in tests, is kept in memory.
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.file else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let contents = toml::to_string(self).map_err(io::Error::other)?;
+        std::fs::write(path, contents)
+    }
+
+    pub fn is_favorite(&self, target: &Target) -> bool {
+        self.favorites.contains(target)
+    }
+
+    /// Add or remove a favorite, returning whether it is now a favorite
+    pub fn toggle_favorite(&mut self, target: Target) -> bool {
+        if let Some(pos) = self.favorites.iter().position(|f| *f == target) {
+            self.favorites.remove(pos);
+            false
+        } else {
+            self.favorites.push(target);
+            true
+        }
+    }
+
+    /// Append a history entry stamped with the current time
+    pub fn record(&mut self, kind: HistoryKind, target: Option<Target>, detail: impl Into<String>) {
+        self.history.push(HistoryEntry {
+            timestamp: unix_now(),
+            kind,
+            target,
+            detail: detail.into(),
+        });
+        if self.history.len() > HISTORY_LIMIT {
+            let excess = self.history.len() - HISTORY_LIMIT;
+            self.history.drain(..excess);
+        }
+    }
+}
+
+/// Seconds since the Unix epoch
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Format a Unix timestamp as `YYYY-MM-DD HH:MM:SS` in UTC
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = timestamp / 86_400;
+    let secs = timestamp % 86_400;
+
+    // Civil-from-days, see https://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3_600,
+        secs % 3_600 / 60,
+        secs % 60
+    )
+}
@@ -0,0 +1,93 @@
+//! Test helpers for running the app against the fake `nordvpn` CLI in
+//! `examples/fake-nordvpn.rs`.
+
+use crate::nordvpn::NordVPN;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use tempfile::TempDir;
+
+/// The backend binary is process-wide, so tests using a fake take turns
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// Path of the `fake-nordvpn` example, which `cargo test` builds as well
+pub fn fake_binary() -> PathBuf {
+    // Test executables live in target/<profile>/deps, examples beside it
+    let exe = std::env::current_exe().unwrap();
+    let path = exe
+        .parent()
+        .unwrap()
+        .with_file_name("examples")
+        .join("fake-nordvpn");
+    assert!(
+        path.exists(),
+        "{} is missing, build it with `cargo build --examples`",
+        path.display()
+    );
+    path
+}
+
+/// A fake `nordvpn` in a temporary directory, installed as the backend
+/// for as long as this value lives.
+pub struct FakeNordVPN {
+    dir: TempDir,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl FakeNordVPN {
+    /// Install a fake starting from `state`, the TOML the fake reads
+    pub fn new(state: &str) -> Self {
+        let lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("nordvpn");
+        std::os::unix::fs::symlink(fake_binary(), &binary).unwrap();
+        let fake = Self { dir, _lock: lock };
+        fake.set_state(state);
+        NordVPN::set_binary(binary);
+        #[cfg(feature = "grpc")]
+        NordVPN::set_grpc_socket(None);
+        fake
+    }
+
+    /// Replace the fake's state, e.g. to simulate a dropped connection
+    pub fn set_state(&self, state: &str) {
+        fs::write(self.dir.path().join("nordvpn-state.toml"), state).unwrap();
+    }
+
+    /// The fake's current state, as last written by it or the test
+    pub fn state(&self) -> toml::Table {
+        let contents = fs::read_to_string(self.dir.path().join("nordvpn-state.toml")).unwrap();
+        toml::from_str(&contents).unwrap()
+    }
+
+    /// Arguments of every invocation so far
+    pub fn calls(&self) -> Vec<String> {
+        fs::read_to_string(self.dir.path().join("nordvpn-calls.log"))
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+/// Serve a single HTTP response on a random local port and return its URL
+pub fn serve_once(content_type: &'static str, body: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buf = [0; 1024];
+        let _ = stream.read(&mut buf);
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            content_type,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).unwrap();
+    });
+    format!("http://{}/", addr)
+}
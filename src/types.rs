@@ -1,4 +1,6 @@
+use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::net::IpAddr;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Country {
@@ -55,3 +57,885 @@ impl fmt::Display for ConnectionStatus {
         }
     }
 }
+
+/// Everything `nordvpn status` reports, for the `watch` command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusDetails {
+    pub status: ConnectionStatus,
+    pub technology: Option<Technology>,
+    /// As the CLI words it, e.g. "1 minute 3 seconds"
+    pub uptime: Option<String>,
+    /// Bytes received and sent through the tunnel
+    pub transfer: Option<(u64, u64)>,
+}
+
+/// Something `nordvpn connect` can be pointed at
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    Country(String),
+    /// A city, named together with its country as `nordvpn cities` lists it
+    City {
+        country: String,
+        city: String,
+    },
+    /// A single server by short hostname, e.g. "uk2345"
+    Server(String),
+    /// A server group such as "P2P" or "Europe", fastest server first
+    Group(String),
+    /// Whatever server the daemon recommends, like bare `nordvpn connect`
+    Quick,
+}
+
+impl Target {
+    /// Arguments passed after `nordvpn connect`
+    pub fn connect_args(&self) -> Vec<&str> {
+        match self {
+            Target::Country(name) | Target::Server(name) | Target::Group(name) => vec![name],
+            Target::City { country, city } => vec![country, city],
+            Target::Quick => vec![],
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Country(name) => write!(f, "{}", name.replace('_', " ")),
+            Target::City { country, city } => write!(
+                f,
+                "{}, {}",
+                city.replace('_', " "),
+                country.replace('_', " ")
+            ),
+            Target::Server(hostname) => write!(f, "{}", hostname),
+            Target::Group(name) => write!(f, "{} group", name.replace('_', " ")),
+            Target::Quick => write!(f, "fastest server"),
+        }
+    }
+}
+
+/// A VPN technology, together with its transport protocol where the
+/// technology offers a choice. Only combinations the CLI accepts exist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Technology {
+    NordLynx,
+    OpenVpnUdp,
+    OpenVpnTcp,
+    NordWhisper,
+}
+
+impl Technology {
+    pub const ALL: [Technology; 4] = [
+        Technology::NordLynx,
+        Technology::OpenVpnUdp,
+        Technology::OpenVpnTcp,
+        Technology::NordWhisper,
+    ];
+
+    /// Value for `nordvpn set technology`
+    pub fn technology_arg(self) -> &'static str {
+        match self {
+            Technology::NordLynx => "NORDLYNX",
+            Technology::OpenVpnUdp | Technology::OpenVpnTcp => "OPENVPN",
+            Technology::NordWhisper => "NORDWHISPER",
+        }
+    }
+
+    /// Value for `nordvpn set protocol`, if the technology takes one
+    pub fn protocol_arg(self) -> Option<&'static str> {
+        match self {
+            Technology::OpenVpnUdp => Some("UDP"),
+            Technology::OpenVpnTcp => Some("TCP"),
+            Technology::NordLynx | Technology::NordWhisper => None,
+        }
+    }
+
+    /// Match the names the CLI prints, in any case.
+    ///
+    /// The protocol only matters for OpenVPN and defaults to UDP there.
+    pub fn from_names(technology: &str, protocol: Option<&str>) -> Option<Self> {
+        match technology.to_uppercase().as_str() {
+            "NORDLYNX" => Some(Technology::NordLynx),
+            "NORDWHISPER" => Some(Technology::NordWhisper),
+            "OPENVPN" => match protocol.map(str::to_uppercase).as_deref() {
+                Some("TCP") => Some(Technology::OpenVpnTcp),
+                _ => Some(Technology::OpenVpnUdp),
+            },
+            _ => None,
+        }
+    }
+
+    pub fn is_openvpn(self) -> bool {
+        matches!(self, Technology::OpenVpnUdp | Technology::OpenVpnTcp)
+    }
+}
+
+impl fmt::Display for Technology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Technology::NordLynx => write!(f, "NordLynx"),
+            Technology::OpenVpnUdp => write!(f, "OpenVPN (UDP)"),
+            Technology::OpenVpnTcp => write!(f, "OpenVPN (TCP)"),
+            Technology::NordWhisper => write!(f, "NordWhisper"),
+        }
+    }
+}
+
+/// Output of `nordvpn settings`, in the order the CLI printed it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Settings {
+    pub entries: Vec<(String, String)>,
+}
+
+impl Settings {
+    /// Value of a setting, matched case-insensitively by name
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Whether an on/off setting is enabled, if it is present
+    pub fn is_enabled(&self, key: &str) -> Option<bool> {
+        match self.get(key)?.to_lowercase().as_str() {
+            "enabled" | "on" => Some(true),
+            "disabled" | "off" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Settings that decide how the tunnel is built
+    pub fn tunnel(&self) -> TunnelSettings {
+        TunnelSettings {
+            technology: self
+                .get("Technology")
+                .and_then(|technology| Technology::from_names(technology, self.get("Protocol"))),
+            obfuscate: self.is_enabled("Obfuscate"),
+            post_quantum: self.is_enabled("Post-quantum VPN"),
+            meshnet: self.is_enabled("Meshnet"),
+        }
+    }
+
+    /// Allowlisted subnets, ports and port ranges
+    pub fn allowlist(&self) -> Allowlist {
+        // Called whitelist before 3.16
+        let entries = |kind: &str| -> Vec<String> {
+            self.get(&format!("Allowlisted {}", kind))
+                .or_else(|| self.get(&format!("Whitelisted {}", kind)))
+                .map(|value| value.split(", ").map(str::to_string).collect())
+                .unwrap_or_default()
+        };
+        Allowlist {
+            subnets: entries("subnets"),
+            ports: entries("ports")
+                .iter()
+                .filter_map(|entry| PortRule::from_setting(entry))
+                .collect(),
+        }
+    }
+
+    /// Custom DNS servers and Threat Protection Lite
+    pub fn dns(&self) -> DnsSettings {
+        let servers = match self.get("DNS") {
+            Some(value) if self.is_enabled("DNS").is_none() => value
+                .split(',')
+                .filter_map(|server| server.trim().parse().ok())
+                .collect(),
+            _ => vec![],
+        };
+        DnsSettings {
+            servers,
+            // Called CyberSec before 3.12
+            threat_protection: self
+                .is_enabled("Threat Protection Lite")
+                .or_else(|| self.is_enabled("CyberSec")),
+        }
+    }
+}
+
+/// Tunnel settings that depend on each other. `None` means this CLI
+/// version does not report the setting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TunnelSettings {
+    pub technology: Option<Technology>,
+    /// Obfuscated servers, which hide that the traffic is a VPN
+    pub obfuscate: Option<bool>,
+    /// Post-quantum encryption
+    pub post_quantum: Option<bool>,
+    pub meshnet: Option<bool>,
+}
+
+/// On/off settings no other setting depends on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Toggle {
+    KillSwitch,
+    Firewall,
+    AutoConnect,
+    LanDiscovery,
+    Notify,
+}
+
+impl Toggle {
+    pub const ALL: [Toggle; 5] = [
+        Toggle::KillSwitch,
+        Toggle::Firewall,
+        Toggle::AutoConnect,
+        Toggle::LanDiscovery,
+        Toggle::Notify,
+    ];
+
+    /// Name in `nordvpn settings`
+    pub fn setting(self) -> &'static str {
+        match self {
+            Toggle::KillSwitch => "Kill Switch",
+            Toggle::Firewall => "Firewall",
+            Toggle::AutoConnect => "Auto-connect",
+            Toggle::LanDiscovery => "LAN Discovery",
+            Toggle::Notify => "Notify",
+        }
+    }
+
+    /// Setting name for `nordvpn set`
+    pub fn set_arg(self) -> &'static str {
+        match self {
+            Toggle::KillSwitch => "killswitch",
+            Toggle::Firewall => "firewall",
+            Toggle::AutoConnect => "autoconnect",
+            Toggle::LanDiscovery => "lan-discovery",
+            Toggle::Notify => "notify",
+        }
+    }
+}
+
+impl fmt::Display for Toggle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Toggle::KillSwitch => write!(f, "kill switch"),
+            Toggle::Firewall => write!(f, "firewall"),
+            Toggle::AutoConnect => write!(f, "auto-connect"),
+            Toggle::LanDiscovery => write!(f, "LAN discovery"),
+            Toggle::Notify => write!(f, "notifications"),
+        }
+    }
+}
+
+/// A change to one tunnel setting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingChange {
+    Technology(Technology),
+    Obfuscate(bool),
+    PostQuantum(bool),
+    Meshnet(bool),
+}
+
+impl SettingChange {
+    /// Name of the setting, e.g. "Obfuscation"
+    pub fn name(self) -> &'static str {
+        match self {
+            SettingChange::Technology(_) => "Technology",
+            SettingChange::Obfuscate(_) => "Obfuscation",
+            SettingChange::PostQuantum(_) => "Post-quantum encryption",
+            SettingChange::Meshnet(_) => "Meshnet",
+        }
+    }
+}
+
+impl fmt::Display for SettingChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let on_off = |enabled: bool| if enabled { "on" } else { "off" };
+        match *self {
+            SettingChange::Technology(technology) => {
+                write!(f, "switch technology to {}", technology)
+            }
+            SettingChange::Obfuscate(enabled) => write!(f, "turn {} obfuscation", on_off(enabled)),
+            SettingChange::PostQuantum(enabled) => {
+                write!(f, "turn {} post-quantum encryption", on_off(enabled))
+            }
+            SettingChange::Meshnet(enabled) => write!(f, "turn {} Meshnet", on_off(enabled)),
+        }
+    }
+}
+
+/// One step of a [`ChangeSet`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeStep {
+    pub change: SettingChange,
+    /// Change that restores the previous value, if it is known
+    pub undo: Option<SettingChange>,
+    /// Why this step is needed, for steps the user did not ask for
+    pub reason: Option<&'static str>,
+}
+
+/// Changes to apply in order so that every step is accepted by the daemon,
+/// ending with the one the user asked for
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangeSet {
+    pub steps: Vec<ChangeStep>,
+}
+
+impl ChangeSet {
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Steps taken only to satisfy the one asked for
+    pub fn required(&self) -> &[ChangeStep] {
+        &self.steps[..self.steps.len().saturating_sub(1)]
+    }
+}
+
+impl TunnelSettings {
+    /// Plan the changes needed to apply `change`.
+    ///
+    /// The daemon rejects combinations it does not support with terse
+    /// errors: obfuscation needs OpenVPN, and post-quantum encryption needs
+    /// NordLynx and cannot be used with Meshnet. The plan changes those
+    /// other settings first. It is empty if `change` is already in effect.
+    pub fn plan(&self, change: SettingChange) -> Result<ChangeSet, String> {
+        self.plan_all(&[change])
+    }
+
+    /// Plan several changes, each in the settings the ones before it leave
+    pub fn plan_all(&self, changes: &[SettingChange]) -> Result<ChangeSet, String> {
+        let mut settings = *self;
+        let mut plan = ChangeSet::default();
+        for &change in changes {
+            if settings.value(change).is_none() {
+                return Err(format!(
+                    "{} is not available in this version of NordVPN",
+                    change.name()
+                ));
+            }
+            settings.resolve(change, None, &mut plan);
+        }
+        Ok(plan)
+    }
+
+    fn resolve(
+        &mut self,
+        change: SettingChange,
+        reason: Option<&'static str>,
+        plan: &mut ChangeSet,
+    ) {
+        if self.is_set(change) {
+            return;
+        }
+        for (required, why) in self.requirements(change) {
+            self.resolve(required, Some(why), plan);
+        }
+        plan.steps.push(ChangeStep {
+            change,
+            undo: self.value(change),
+            reason,
+        });
+        self.apply(change);
+    }
+
+    /// Changes `change` needs first, with the reason for each
+    fn requirements(&self, change: SettingChange) -> Vec<(SettingChange, &'static str)> {
+        let mut required = vec![];
+        match change {
+            SettingChange::Obfuscate(true) => {
+                if !self.technology.is_some_and(Technology::is_openvpn) {
+                    required.push((
+                        SettingChange::Technology(Technology::OpenVpnUdp),
+                        "obfuscated servers need OpenVPN",
+                    ));
+                }
+            }
+            SettingChange::PostQuantum(true) => {
+                if self.technology != Some(Technology::NordLynx) {
+                    required.push((
+                        SettingChange::Technology(Technology::NordLynx),
+                        "post-quantum encryption needs NordLynx",
+                    ));
+                }
+                if self.meshnet == Some(true) {
+                    required.push((
+                        SettingChange::Meshnet(false),
+                        "post-quantum encryption cannot be used with Meshnet",
+                    ));
+                }
+            }
+            SettingChange::Technology(technology) => {
+                if !technology.is_openvpn() && self.obfuscate == Some(true) {
+                    required.push((
+                        SettingChange::Obfuscate(false),
+                        "obfuscation only works with OpenVPN",
+                    ));
+                }
+                if technology != Technology::NordLynx && self.post_quantum == Some(true) {
+                    required.push((
+                        SettingChange::PostQuantum(false),
+                        "post-quantum encryption only works with NordLynx",
+                    ));
+                }
+            }
+            SettingChange::Meshnet(true) if self.post_quantum == Some(true) => {
+                required.push((
+                    SettingChange::PostQuantum(false),
+                    "Meshnet cannot be used with post-quantum encryption",
+                ));
+            }
+            SettingChange::Obfuscate(false)
+            | SettingChange::PostQuantum(false)
+            | SettingChange::Meshnet(_) => {}
+        }
+        required
+    }
+
+    /// Current value of the setting `change` applies to, if known
+    fn value(&self, change: SettingChange) -> Option<SettingChange> {
+        match change {
+            SettingChange::Technology(_) => self.technology.map(SettingChange::Technology),
+            SettingChange::Obfuscate(_) => self.obfuscate.map(SettingChange::Obfuscate),
+            SettingChange::PostQuantum(_) => self.post_quantum.map(SettingChange::PostQuantum),
+            SettingChange::Meshnet(_) => self.meshnet.map(SettingChange::Meshnet),
+        }
+    }
+
+    fn is_set(&self, change: SettingChange) -> bool {
+        self.value(change) == Some(change)
+    }
+
+    fn apply(&mut self, change: SettingChange) {
+        match change {
+            SettingChange::Technology(technology) => self.technology = Some(technology),
+            SettingChange::Obfuscate(enabled) => self.obfuscate = Some(enabled),
+            SettingChange::PostQuantum(enabled) => self.post_quantum = Some(enabled),
+            SettingChange::Meshnet(enabled) => self.meshnet = Some(enabled),
+        }
+    }
+}
+
+/// Traffic that bypasses the VPN tunnel
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Allowlist {
+    /// Subnets in CIDR notation, e.g. "192.168.1.0/24"
+    pub subnets: Vec<String>,
+    /// Ports and port ranges
+    pub ports: Vec<PortRule>,
+}
+
+/// An allowlisted port or range of ports.
+///
+/// Written as a bare number for a single port with both protocols, or as
+/// a table like `{ start = 8080, end = 8090, protocol = "tcp" }`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "PortEntry", into = "PortEntry")]
+pub struct PortRule {
+    pub start: u16,
+    /// Same as `start` for a single port
+    pub end: u16,
+    /// `None` for both UDP and TCP
+    pub protocol: Option<PortProtocol>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PortProtocol {
+    Tcp,
+    Udp,
+}
+
+/// How a [`PortRule`] is written in config and snapshot files
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum PortEntry {
+    Port(u16),
+    Range(PortRange),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PortRange {
+    start: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    end: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    protocol: Option<PortProtocol>,
+}
+
+impl TryFrom<PortEntry> for PortRule {
+    type Error = String;
+
+    fn try_from(entry: PortEntry) -> Result<Self, String> {
+        match entry {
+            PortEntry::Port(port) => Ok(PortRule::port(port)),
+            PortEntry::Range(range) => {
+                let end = range.end.unwrap_or(range.start);
+                if end < range.start {
+                    return Err(format!(
+                        "port range {} - {} ends before it starts",
+                        range.start, end
+                    ));
+                }
+                Ok(PortRule {
+                    start: range.start,
+                    end,
+                    protocol: range.protocol,
+                })
+            }
+        }
+    }
+}
+
+impl From<PortRule> for PortEntry {
+    fn from(rule: PortRule) -> Self {
+        if !rule.is_range() && rule.protocol.is_none() {
+            return PortEntry::Port(rule.start);
+        }
+        PortEntry::Range(PortRange {
+            start: rule.start,
+            end: rule.is_range().then_some(rule.end),
+            protocol: rule.protocol,
+        })
+    }
+}
+
+impl PortRule {
+    /// A single port, for both UDP and TCP
+    pub fn port(port: u16) -> Self {
+        Self {
+            start: port,
+            end: port,
+            protocol: None,
+        }
+    }
+
+    pub fn is_range(&self) -> bool {
+        self.end != self.start
+    }
+
+    /// Parse an entry of `nordvpn settings`, e.g. "22 (UDP|TCP)" or
+    /// "8080 - 8090 (TCP)"
+    pub fn from_setting(entry: &str) -> Option<Self> {
+        let (ports, protocol) = match entry.split_once('(') {
+            Some((ports, protocol)) => (ports, protocol.trim_end().strip_suffix(')')?),
+            None => (entry, "UDP|TCP"),
+        };
+        let protocol = match protocol.trim().to_uppercase().as_str() {
+            "TCP" => Some(PortProtocol::Tcp),
+            "UDP" => Some(PortProtocol::Udp),
+            "UDP|TCP" | "TCP|UDP" => None,
+            _ => return None,
+        };
+        let (start, end) = match ports.split_once('-') {
+            Some((start, end)) => (start.trim().parse().ok()?, end.trim().parse().ok()?),
+            None => {
+                let port = ports.trim().parse().ok()?;
+                (port, port)
+            }
+        };
+        Some(Self {
+            start,
+            end,
+            protocol,
+        })
+    }
+
+    /// The rule with what it covers, e.g. "port 22" or
+    /// "ports 8080 - 8090 (TCP)"
+    pub fn describe(&self) -> String {
+        let noun = if self.is_range() { "ports" } else { "port" };
+        format!("{} {}", noun, self)
+    }
+
+    /// Arguments after `nordvpn allowlist add` or `remove`
+    pub fn allowlist_args(&self) -> Vec<String> {
+        let mut args = if self.is_range() {
+            vec![
+                "ports".to_string(),
+                self.start.to_string(),
+                self.end.to_string(),
+            ]
+        } else {
+            vec!["port".to_string(), self.start.to_string()]
+        };
+        if let Some(protocol) = self.protocol {
+            args.extend(["protocol".to_string(), protocol.to_string()]);
+        }
+        args
+    }
+}
+
+impl fmt::Display for PortRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.start)?;
+        if self.is_range() {
+            write!(f, " - {}", self.end)?;
+        }
+        if let Some(protocol) = self.protocol {
+            write!(f, " ({})", protocol)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for PortProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PortProtocol::Tcp => write!(f, "TCP"),
+            PortProtocol::Udp => write!(f, "UDP"),
+        }
+    }
+}
+
+/// Most resolvers `nordvpn set dns` accepts
+pub const MAX_DNS_SERVERS: usize = 3;
+
+/// DNS part of the daemon settings
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DnsSettings {
+    /// Custom resolvers; empty means NordVPN's own DNS
+    pub servers: Vec<IpAddr>,
+    /// Threat Protection Lite, if this CLI version reports it
+    pub threat_protection: Option<bool>,
+}
+
+/// A change to the DNS settings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DnsChange {
+    Servers(Vec<IpAddr>),
+    /// Go back to NordVPN's DNS
+    Reset,
+    ThreatProtection(bool),
+}
+
+impl DnsSettings {
+    /// Warning for a change that silently undoes another setting.
+    ///
+    /// Threat Protection Lite filters through NordVPN's DNS, so the daemon
+    /// turns it off when custom servers are set and drops the custom
+    /// servers when it is turned on.
+    pub fn conflict(&self, change: &DnsChange) -> Option<String> {
+        match change {
+            DnsChange::Servers(_) if self.threat_protection == Some(true) => Some(
+                "Custom DNS turns off Threat Protection Lite, which needs NordVPN's DNS"
+                    .to_string(),
+            ),
+            DnsChange::ThreatProtection(true) if !self.servers.is_empty() => Some(format!(
+                "Threat Protection Lite replaces your custom DNS ({}) with NordVPN's",
+                join_addresses(&self.servers)
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// Comma-separated list of addresses, as the CLI prints them
+pub fn join_addresses(servers: &[IpAddr]) -> String {
+    servers
+        .iter()
+        .map(IpAddr::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Validate the DNS servers typed by the user, separated by commas or
+/// spaces.
+pub fn parse_dns_servers(input: &str) -> Result<Vec<IpAddr>, String> {
+    let mut servers: Vec<IpAddr> = vec![];
+    for word in input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|word| !word.is_empty())
+    {
+        let server = word
+            .parse()
+            .map_err(|_| format!("{:?} is not an IP address", word))?;
+        if servers.contains(&server) {
+            return Err(format!("{} is listed twice", server));
+        }
+        servers.push(server);
+    }
+    match servers.len() {
+        0 => Err("Enter at least one address, or reset to use NordVPN's DNS".to_string()),
+        n if n > MAX_DNS_SERVERS => Err(format!(
+            "At most {} DNS servers can be set",
+            MAX_DNS_SERVERS
+        )),
+        _ => Ok(servers),
+    }
+}
+
+/// Output of `nordvpn account`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Account {
+    pub email: Option<String>,
+    /// Subscription state, e.g. "Active (Expires on Mar 14th, 2027)"
+    pub vpn_service: Option<String>,
+}
+
+/// A device in the meshnet, from `nordvpn meshnet peer list`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MeshnetPeer {
+    pub hostname: String,
+    pub ip: Option<String>,
+    pub connected: bool,
+    /// Listed under "Local Peers" (same account) rather than "External Peers"
+    pub local: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn port_rules_from_settings_and_config() {
+        let range = PortRule {
+            start: 8080,
+            end: 8090,
+            protocol: Some(PortProtocol::Tcp),
+        };
+        let udp = PortRule {
+            protocol: Some(PortProtocol::Udp),
+            ..PortRule::port(53)
+        };
+        assert_eq!(
+            PortRule::from_setting("22 (UDP|TCP)"),
+            Some(PortRule::port(22))
+        );
+        assert_eq!(PortRule::from_setting("8080 - 8090 (TCP)"), Some(range));
+        assert_eq!(PortRule::from_setting("53 (UDP)"), Some(udp));
+        assert_eq!(PortRule::from_setting("ssh (TCP)"), None);
+
+        assert_eq!(range.describe(), "ports 8080 - 8090 (TCP)");
+        assert_eq!(
+            range.allowlist_args(),
+            ["ports", "8080", "8090", "protocol", "TCP"]
+        );
+        assert_eq!(PortRule::port(22).allowlist_args(), ["port", "22"]);
+
+        let allowlist: Allowlist = toml::from_str(
+            r#"ports = [22, { start = 8080, end = 8090, protocol = "tcp" }, { start = 53, protocol = "udp" }]"#,
+        )
+        .unwrap();
+        assert_eq!(allowlist.ports, [PortRule::port(22), range, udp]);
+        assert_eq!(
+            toml::from_str::<Allowlist>(&toml::to_string(&allowlist).unwrap()),
+            Ok(allowlist)
+        );
+        assert!(toml::from_str::<Allowlist>("ports = [{ start = 90, end = 80 }]").is_err());
+        assert!(toml::from_str::<Allowlist>("ports = [{ port = 22 }]").is_err());
+    }
+
+    #[test]
+    fn parses_dns_servers() {
+        assert_eq!(
+            parse_dns_servers(" 1.1.1.1, 2606:4700:4700::1111 9.9.9.9"),
+            Ok(vec![
+                "1.1.1.1".parse().unwrap(),
+                "2606:4700:4700::1111".parse().unwrap(),
+                "9.9.9.9".parse().unwrap(),
+            ])
+        );
+        for invalid in ["", " , ", "1.1.1", "dns.google", "1.1.1.1 1.1.1.1"] {
+            assert!(parse_dns_servers(invalid).is_err(), "{invalid:?} accepted");
+        }
+        assert_eq!(
+            parse_dns_servers("1.1.1.1 1.0.0.1 8.8.8.8 8.8.4.4"),
+            Err("At most 3 DNS servers can be set".to_string())
+        );
+    }
+
+    fn changes(plan: &ChangeSet) -> Vec<SettingChange> {
+        plan.steps.iter().map(|step| step.change).collect()
+    }
+
+    #[test]
+    fn plans_prerequisites_first() {
+        let settings = TunnelSettings {
+            technology: Some(Technology::NordLynx),
+            obfuscate: Some(false),
+            post_quantum: Some(true),
+            meshnet: Some(false),
+        };
+        let plan = settings.plan(SettingChange::Obfuscate(true)).unwrap();
+        assert_eq!(
+            changes(&plan),
+            [
+                SettingChange::PostQuantum(false),
+                SettingChange::Technology(Technology::OpenVpnUdp),
+                SettingChange::Obfuscate(true),
+            ]
+        );
+        assert_eq!(plan.required().len(), 2);
+        assert_eq!(
+            plan.steps[1].undo,
+            Some(SettingChange::Technology(Technology::NordLynx))
+        );
+        assert_eq!(plan.steps[2].reason, None);
+
+        let settings = TunnelSettings {
+            technology: Some(Technology::OpenVpnTcp),
+            obfuscate: Some(true),
+            post_quantum: Some(false),
+            meshnet: Some(true),
+        };
+        assert_eq!(
+            changes(&settings.plan(SettingChange::PostQuantum(true)).unwrap()),
+            [
+                SettingChange::Obfuscate(false),
+                SettingChange::Technology(Technology::NordLynx),
+                SettingChange::Meshnet(false),
+                SettingChange::PostQuantum(true),
+            ]
+        );
+        // Obfuscation already works with TCP
+        assert!(
+            settings
+                .plan(SettingChange::Obfuscate(true))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn refuses_unknown_settings() {
+        let settings = TunnelSettings {
+            technology: Some(Technology::OpenVpnUdp),
+            ..TunnelSettings::default()
+        };
+        assert!(settings.plan(SettingChange::PostQuantum(true)).is_err());
+        assert_eq!(
+            changes(
+                &settings
+                    .plan(SettingChange::Technology(Technology::NordLynx))
+                    .unwrap()
+            ),
+            [SettingChange::Technology(Technology::NordLynx)]
+        );
+    }
+
+    #[test]
+    fn warns_about_threat_protection_conflict() {
+        let custom = DnsSettings {
+            servers: vec!["1.1.1.1".parse().unwrap()],
+            threat_protection: Some(false),
+        };
+        assert!(
+            custom
+                .conflict(&DnsChange::ThreatProtection(true))
+                .unwrap()
+                .contains("1.1.1.1")
+        );
+        assert_eq!(custom.conflict(&DnsChange::Reset), None);
+
+        let protected = DnsSettings {
+            servers: vec![],
+            threat_protection: Some(true),
+        };
+        assert!(
+            protected
+                .conflict(&DnsChange::Servers(custom.servers))
+                .is_some()
+        );
+        assert_eq!(
+            protected.conflict(&DnsChange::ThreatProtection(false)),
+            None
+        );
+    }
+}
@@ -1,4 +1,11 @@
-use crate::app::App;
+use crate::action::KEY_BINDINGS;
+use crate::app::{
+    App, Button, DaemonState, HitAreas, LeakCheck, SettingsFileAction, StepOutcome,
+    TECHNOLOGY_PICKER_ROWS, View,
+};
+use crate::doctor::CheckStatus;
+use crate::state::{HistoryKind, format_timestamp};
+use crate::types::{self, ConnectionStatus, MAX_DNS_SERVERS, PortRule, Target, Technology};
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
@@ -6,12 +13,24 @@ use ratatui::{
     symbols::{border, scrollbar},
     text::{Line, Span},
     widgets::{
-        Block, BorderType, Borders, List, ListItem, Paragraph, Scrollbar, ScrollbarOrientation,
+        Block, BorderType, Borders, Clear, List, ListItem, ListState, Paragraph, Scrollbar,
+        ScrollbarOrientation, Sparkline,
     },
 };
+use std::time::Instant;
+
+/// Most lines of connect progress shown at once
+const CONNECT_LOG_LINES: usize = 4;
+/// Height of the hook log below the history
+const HOOK_LOG_LINES: usize = 8;
+
+/// Draw the whole UI; countdowns are measured from `now`
+pub fn render(app: &mut App, frame: &mut Frame, now: Instant) {
+    app.hit_areas = HitAreas::default();
 
-pub fn render(app: &mut App, frame: &mut Frame) {
     // Outer wrapper block
+    let tabs = tab_titles(app);
+    app.hit_areas.tabs = tab_areas(&tabs, frame.area());
     let outer_block = Block::default()
         .title(Span::styled(
             " NordVPN ",
@@ -19,6 +38,7 @@ pub fn render(app: &mut App, frame: &mut Frame) {
                 .fg(Color::Cyan)
                 .add_modifier(Modifier::BOLD),
         ))
+        .title(tabs.right_aligned())
         .borders(Borders::ALL)
         .border_set(border::ROUNDED)
         .border_type(BorderType::QuadrantInside)
@@ -27,38 +47,373 @@ pub fn render(app: &mut App, frame: &mut Frame) {
     let inner_area = outer_block.inner(frame.area());
     frame.render_widget(outer_block, frame.area());
 
+    render_body(app, frame, inner_area, now);
+
+    if app.show_help {
+        render_help_overlay(frame, inner_area);
+    }
+    if app.palette.is_some() {
+        render_palette(app, frame, inner_area);
+    }
+    if app.server_search.is_some() {
+        render_server_search(app, frame, inner_area);
+    }
+    if app.technology_picker.is_some() {
+        render_technology_picker(app, frame, inner_area);
+    }
+}
+
+/// Arrangement of the body, picked from the available space on every draw
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutMode {
+    /// Single-line status/filter and message rows around the view body
+    Compact,
+    /// Bordered status, filter and help bars stacked around the view body
+    Normal,
+    /// Normal bars with the country list beside a details pane
+    TwoColumn,
+    /// Two columns plus a connection activity graph
+    ThreeColumn,
+}
+
+impl LayoutMode {
+    /// Below this height the bordered bars leave no room for the list
+    const MIN_NORMAL_HEIGHT: u16 = 14;
+    const TWO_COLUMN_WIDTH: u16 = 100;
+    const THREE_COLUMN_WIDTH: u16 = 140;
+
+    pub fn for_area(area: Rect) -> Self {
+        if area.height < Self::MIN_NORMAL_HEIGHT {
+            LayoutMode::Compact
+        } else if area.width >= Self::THREE_COLUMN_WIDTH {
+            LayoutMode::ThreeColumn
+        } else if area.width >= Self::TWO_COLUMN_WIDTH {
+            LayoutMode::TwoColumn
+        } else {
+            LayoutMode::Normal
+        }
+    }
+}
+
+fn render_body(app: &mut App, frame: &mut Frame, inner_area: Rect, now: Instant) {
+    let mode = LayoutMode::for_area(inner_area);
+
+    let body = if mode == LayoutMode::Compact {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1), // Status and filter
+                Constraint::Min(0),    // View body
+                Constraint::Length(1), // Message line
+            ])
+            .split(inner_area);
+
+        render_compact_status(app, frame, chunks[0]);
+        frame.render_widget(Paragraph::new(help_line(app)), chunks[2]);
+        chunks[1]
+    } else {
+        let mut constraints = vec![Constraint::Length(3)]; // Status bar
+        if app.view == View::Countries {
+            constraints.push(Constraint::Length(3)); // Filter bar
+        }
+        constraints.push(Constraint::Min(0)); // View body
+        constraints.push(Constraint::Length(3)); // Help/message bar
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(constraints)
+            .split(inner_area);
+
+        render_status(app, frame, chunks[0]);
+        if app.view == View::Countries {
+            render_filter(app, frame, chunks[1]);
+        }
+        render_help(app, frame, chunks[chunks.len() - 1]);
+        chunks[chunks.len() - 2]
+    };
+
+    match app.view {
+        View::History => render_history(app, frame, body),
+        View::Dns => render_dns(app, frame, body),
+        View::Profiles => render_profiles(app, frame, body),
+        View::Settings => render_settings(app, frame, body),
+        View::Diagnostics => render_diagnostics(app, frame, body),
+        View::Countries => render_countries(app, frame, body, mode, now),
+    }
+}
+
+/// Country list, with details and activity panes beside it when wide enough
+fn render_countries(app: &mut App, frame: &mut Frame, area: Rect, mode: LayoutMode, now: Instant) {
+    let constraints = match mode {
+        LayoutMode::Compact | LayoutMode::Normal => vec![Constraint::Min(0)],
+        LayoutMode::TwoColumn => vec![Constraint::Percentage(50), Constraint::Percentage(50)],
+        LayoutMode::ThreeColumn => vec![
+            Constraint::Percentage(40),
+            Constraint::Percentage(30),
+            Constraint::Percentage(30),
+        ],
+    };
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(constraints)
+        .split(area);
+
+    // The connect log takes the bottom of the list column while connecting
+    let mut list_area = columns[0];
+    if mode != LayoutMode::Compact && app.status == ConnectionStatus::Connecting {
+        let lines = app.connect_log.len().clamp(1, CONNECT_LOG_LINES) as u16;
+        let [list, log] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(lines + 2)]).areas(list_area);
+        render_connect_log(app, frame, log);
+        list_area = list;
+    }
+
+    if matches!(app.daemon, DaemonState::Available) {
+        render_country_list(app, frame, list_area);
+    } else {
+        render_daemon_unavailable(app, frame, list_area, now);
+    }
+    if let Some(area) = columns.get(1) {
+        render_details(app, frame, *area);
+    }
+    if let Some(area) = columns.get(2) {
+        render_activity(app, frame, *area);
+    }
+}
+
+/// Latest lines printed by the running connect
+fn render_connect_log(app: &App, frame: &mut Frame, area: Rect) {
+    let lines: Vec<Line> = if app.connect_log.is_empty() {
+        vec![Line::styled(
+            "Waiting for nordvpn...",
+            Style::default().fg(Color::DarkGray),
+        )]
+    } else {
+        let skip = app.connect_log.len().saturating_sub(CONNECT_LOG_LINES);
+        app.connect_log[skip..]
+            .iter()
+            .map(|line| Line::styled(line.clone(), Style::default().fg(Color::Gray)))
+            .collect()
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(border::PLAIN)
+        .border_style(Style::default().fg(Color::Yellow))
+        .title(Span::styled(
+            " Progress ",
+            Style::default()
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        ));
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Status and filter squeezed into one unbordered line.
+///
+/// The filter goes first so it stays visible while typing, and is left out
+/// entirely while unused.
+fn render_compact_status(app: &App, frame: &mut Frame, area: Rect) {
+    let mut spans = vec![];
+    if app.view == View::Countries && (app.filter_mode || !app.filter.is_empty()) {
+        let (filter_text, text_style, _) = filter_text(app);
+        spans.push(Span::styled(filter_text, text_style));
+        spans.push(Span::styled(" │ ", Style::default().fg(Color::DarkGray)));
+    }
+    spans.extend(status_line(app).0.spans);
+    frame.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
+/// Current connection and the selected country
+fn render_details(app: &App, frame: &mut Frame, area: Rect) {
+    let label = Style::default().fg(Color::DarkGray);
+    let heading = Style::default()
+        .fg(Color::White)
+        .add_modifier(Modifier::BOLD);
+    let field = |name: &'static str, value: String| {
+        Line::from(vec![
+            Span::styled(format!("{name:<10}"), label),
+            Span::raw(value),
+        ])
+    };
+
+    let mut lines = vec![Line::from(Span::styled("Connection", heading))];
+    match &app.status {
+        ConnectionStatus::Connected {
+            country,
+            city,
+            server,
+            ip,
+        } => {
+            lines.push(field("Country", country.clone()));
+            if let Some(city) = city {
+                lines.push(field("City", city.clone()));
+            }
+            if let Some(server) = server {
+                lines.push(field("Server", server.clone()));
+            }
+            if let Some(ip) = ip {
+                lines.push(field("IP", ip.clone()));
+            }
+        }
+        status => lines.push(field("Status", status.to_string())),
+    }
+    let watchdog = if app.watchdog.is_active() {
+        "reconnecting"
+    } else if app.watchdog.enabled {
+        "armed"
+    } else {
+        "off"
+    };
+    lines.push(field("Watchdog", watchdog.to_string()));
+    if let Some(technology) = app.technology {
+        lines.push(field("Tech", technology.to_string()));
+    }
+
+    if let Some(country) = app.filtered_countries.get(app.selected_index) {
+        let target = Target::Country(country.name.clone());
+        lines.push(Line::raw(""));
+        lines.push(Line::from(Span::styled("Selected", heading)));
+        lines.push(field("Country", country.display_name.clone()));
+        let favorite = if app.state.is_favorite(&target) {
+            "yes"
+        } else {
+            "no"
+        };
+        lines.push(field("Favorite", favorite.to_string()));
+        let last_connected = app
+            .state
+            .history
+            .iter()
+            .rev()
+            .find(|entry| {
+                entry.kind == HistoryKind::Connected && entry.target.as_ref() == Some(&target)
+            })
+            .map(|entry| format_timestamp(entry.timestamp))
+            .unwrap_or_else(|| "never".to_string());
+        lines.push(field("Last used", last_connected));
+    }
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(border::PLAIN)
+        .border_style(Style::default().fg(Color::Blue))
+        .title(Span::styled(" Details ", heading));
+
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Connected/disconnected history of the background status polls
+fn render_activity(app: &App, frame: &mut Frame, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(border::PLAIN)
+        .border_style(Style::default().fg(Color::Blue))
+        .title(Span::styled(
+            " Activity ",
+            Style::default()
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        ));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length(3), // Status bar
-            Constraint::Length(3), // Filter bar
-            Constraint::Min(0),    // Country list
-            Constraint::Length(3), // Help/message bar
-        ])
-        .split(inner_area);
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(inner);
+
+    let samples = &app.status_samples;
+    let summary = if samples.is_empty() {
+        Span::styled("No status polls yet", Style::default().fg(Color::DarkGray))
+    } else {
+        let connected = samples.iter().filter(|connected| **connected).count();
+        Span::styled(
+            format!(
+                "Connected {}% of last {} polls",
+                connected * 100 / samples.len(),
+                samples.len()
+            ),
+            Style::default().fg(Color::Gray),
+        )
+    };
+    frame.render_widget(Paragraph::new(summary), chunks[0]);
+
+    // Show the most recent samples that fit
+    let width = chunks[1].width as usize;
+    let data: Vec<u64> = samples
+        .iter()
+        .skip(samples.len().saturating_sub(width))
+        .map(|connected| u64::from(*connected))
+        .collect();
+    let sparkline = Sparkline::default()
+        .data(&data)
+        .max(1)
+        .style(Style::default().fg(Color::Green));
+    frame.render_widget(sparkline, chunks[1]);
+}
+
+/// View names shown in the top border, with the active one highlighted
+fn tab_titles(app: &App) -> Line<'static> {
+    let mut spans = vec![Span::raw(" ")];
+    for (i, view) in View::ALL.iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::styled(" │ ", Style::default().fg(Color::DarkGray)));
+        }
+        let style = if *view == app.view {
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(Color::Gray)
+        };
+        spans.push(Span::styled(view.title(), style));
+    }
+    spans.push(Span::raw(" "));
+    Line::from(spans)
+}
 
-    render_status(app, frame, chunks[0]);
-    render_filter(app, frame, chunks[1]);
-    render_country_list(app, frame, chunks[2]);
-    render_help(app, frame, chunks[3]);
+/// Screen areas of the right-aligned view titles in the outer border
+fn tab_areas(tabs: &Line, area: Rect) -> Vec<(View, Rect)> {
+    // Right-aligned titles end just before the top-right corner
+    let mut x = (area.right().saturating_sub(1)).saturating_sub(tabs.width() as u16);
+    let mut areas = vec![];
+    for span in &tabs.spans {
+        let width = span.width() as u16;
+        if let Some(view) = View::ALL
+            .iter()
+            .find(|view| view.title() == span.content.as_ref())
+        {
+            areas.push((*view, Rect::new(x, area.y, width, 1)));
+        }
+        x += width;
+    }
+    areas
 }
 
-fn render_status(app: &App, frame: &mut Frame, area: Rect) {
-    let status_text = app.status.to_string();
+/// Connection summary with leak and watchdog indicators, plus its accent colour
+fn status_line(app: &App) -> (Line<'static>, Color) {
+    let status_text = match app.daemon {
+        DaemonState::Available => app.status.to_string(),
+        DaemonState::Unavailable { .. } => "Daemon unavailable".to_string(),
+    };
     let (status_color, status_symbol, text_style) = match &app.status {
-        crate::types::ConnectionStatus::Connected { .. } => (
+        _ if !matches!(app.daemon, DaemonState::Available) => (
+            Color::Red,
+            "✗",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        ),
+        ConnectionStatus::Connected { .. } => (
             Color::Green,
             "●",
             Style::default()
                 .fg(Color::Green)
                 .add_modifier(Modifier::BOLD),
         ),
-        crate::types::ConnectionStatus::Disconnected => (
+        ConnectionStatus::Disconnected => (
             Color::Red,
             "●",
             Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
         ),
-        crate::types::ConnectionStatus::Connecting => (
+        ConnectionStatus::Connecting => (
             Color::Yellow,
             "◐",
             Style::default()
@@ -68,11 +423,30 @@ fn render_status(app: &App, frame: &mut Frame, area: Rect) {
         ),
     };
 
-    let status_line = Line::from(vec![
+    let mut spans = vec![
         Span::styled(status_symbol, Style::default().fg(status_color)),
         Span::raw(" "),
         Span::styled(status_text, text_style),
-    ]);
+    ];
+    spans.extend(leak_spans(&app.leak_check));
+    if app.watchdog.is_active() {
+        spans.push(Span::styled(" · ", Style::default().fg(Color::DarkGray)));
+        spans.push(Span::styled(
+            "watchdog reconnecting",
+            Style::default().fg(Color::Yellow),
+        ));
+    } else if app.watchdog.enabled {
+        spans.push(Span::styled(" · ", Style::default().fg(Color::DarkGray)));
+        spans.push(Span::styled(
+            "watchdog",
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    (Line::from(spans), status_color)
+}
+
+fn render_status(app: &mut App, frame: &mut Frame, area: Rect) {
+    let (status_line, status_color) = status_line(app);
 
     let block = Block::default()
         .borders(Borders::ALL)
@@ -85,13 +459,92 @@ fn render_status(app: &App, frame: &mut Frame, area: Rect) {
                 .add_modifier(Modifier::BOLD),
         ));
 
-    let status_widget = Paragraph::new(status_line).block(block);
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    // Clickable buttons on the right, status text takes the rest
+    let mut buttons = vec![];
+    if matches!(app.status, ConnectionStatus::Connected { .. }) {
+        buttons.push((Button::Disconnect, " Disconnect ", Color::Red));
+    }
+    buttons.push((Button::Refresh, " Refresh ", Color::Yellow));
+    let buttons_width: u16 = buttons
+        .iter()
+        .map(|(_, label, _)| label.len() as u16 + 1)
+        .sum();
+
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(buttons_width)])
+        .split(inner);
+
+    frame.render_widget(Paragraph::new(status_line), chunks[0]);
 
-    frame.render_widget(status_widget, area);
+    let mut x = chunks[1].x;
+    for (button, label, color) in buttons {
+        let width = (label.len() as u16).min(chunks[1].right().saturating_sub(x + 1));
+        let button_area = Rect::new(x + 1, chunks[1].y, width, chunks[1].height.min(1));
+        frame.render_widget(
+            Paragraph::new(Span::styled(
+                label,
+                Style::default().fg(Color::Black).bg(color),
+            )),
+            button_area,
+        );
+        app.hit_areas.buttons.push((button, button_area));
+        x += label.len() as u16 + 1;
+    }
 }
 
-fn render_filter(app: &App, frame: &mut Frame, area: Rect) {
-    let filter_text = if app.filter_mode {
+/// Status bar suffix summarising the leak test
+fn leak_spans(leak_check: &LeakCheck) -> Vec<Span<'static>> {
+    let separator = Span::styled(" · ", Style::default().fg(Color::DarkGray));
+    let warning = Style::default().fg(Color::Red).add_modifier(Modifier::BOLD);
+    match leak_check {
+        LeakCheck::Idle => vec![],
+        LeakCheck::Running => vec![
+            separator,
+            Span::styled("verifying exit IP...", Style::default().fg(Color::DarkGray)),
+        ],
+        LeakCheck::Failed(_) => vec![
+            separator,
+            Span::styled("leak test failed", Style::default().fg(Color::Yellow)),
+        ],
+        LeakCheck::Done(report) => {
+            let mut spans = vec![];
+            if report.ip_mismatch() {
+                spans.push(separator.clone());
+                spans.push(Span::styled(
+                    format!(
+                        "⚠ exit IP {} ≠ {}",
+                        report.public_ip,
+                        report.expected_ip.as_deref().unwrap_or("?")
+                    ),
+                    warning,
+                ));
+            }
+            if report.dns_leak() {
+                spans.push(separator.clone());
+                spans.push(Span::styled(
+                    format!("⚠ DNS via {}", report.unexpected_resolvers.join(", ")),
+                    warning,
+                ));
+            }
+            if spans.is_empty() {
+                spans.push(separator);
+                spans.push(Span::styled(
+                    format!("✓ exit IP {} verified", report.public_ip),
+                    Style::default().fg(Color::Green),
+                ));
+            }
+            spans
+        }
+    }
+}
+
+/// Filter prompt text, its style and the accent colour for its border
+fn filter_text(app: &App) -> (String, Style, Color) {
+    let text = if app.filter_mode {
         format!("/{}_", app.filter)
     } else if app.filter.is_empty() {
         "Type to filter countries".to_string()
@@ -111,6 +564,11 @@ fn render_filter(app: &App, frame: &mut Frame, area: Rect) {
     } else {
         (Style::default().fg(Color::DarkGray), Color::DarkGray)
     };
+    (text, text_style, border_color)
+}
+
+fn render_filter(app: &App, frame: &mut Frame, area: Rect) {
+    let (filter_text, text_style, border_color) = filter_text(app);
 
     let block = Block::default()
         .borders(Borders::ALL)
@@ -131,25 +589,31 @@ fn render_filter(app: &App, frame: &mut Frame, area: Rect) {
 fn render_country_list(app: &mut App, frame: &mut Frame, area: Rect) {
     // Get connected country name if any
     let connected_country = match &app.status {
-        crate::types::ConnectionStatus::Connected { country, .. } => Some(country.to_lowercase()),
+        ConnectionStatus::Connected { country, .. } => Some(country.to_lowercase()),
         _ => None,
     };
 
     let items: Vec<ListItem> = app
         .filtered_countries
         .iter()
-        .enumerate()
-        .map(|(_i, country)| {
+        .map(|country| {
             let is_connected = connected_country
                 .as_ref()
                 .map(|c| country.display_name.to_lowercase() == *c)
                 .unwrap_or(false);
 
-            let content = if is_connected {
-                format!("{} ●", country.display_name)
+            let is_favorite = app
+                .state
+                .is_favorite(&Target::Country(country.name.clone()));
+
+            let mut content = if is_favorite {
+                format!("★ {}", country.display_name)
             } else {
                 country.display_name.clone()
             };
+            if is_connected {
+                content.push_str(" ●");
+            }
 
             let style = if is_connected {
                 Style::default()
@@ -190,6 +654,7 @@ fn render_country_list(app: &mut App, frame: &mut Frame, area: Rect) {
         .highlight_symbol("▶ ");
 
     frame.render_stateful_widget(list, area, &mut app.list_state);
+    app.hit_areas.country_list = Some(area);
 
     // Render scrollbar
     let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
@@ -206,15 +671,966 @@ fn render_country_list(app: &mut App, frame: &mut Frame, area: Rect) {
     );
 }
 
-fn render_help(app: &App, frame: &mut Frame, area: Rect) {
-    let help_text = if let Some(error) = &app.error_message {
+fn render_daemon_unavailable(app: &App, frame: &mut Frame, area: Rect, now: Instant) {
+    let DaemonState::Unavailable {
+        error,
+        attempts,
+        next_retry,
+    } = &app.daemon
+    else {
+        return;
+    };
+
+    let mut lines = vec![
+        Line::from(Span::styled(
+            "Cannot reach the NordVPN daemon",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        )),
+        Line::from(Span::styled(
+            error.to_string(),
+            Style::default().fg(Color::Gray),
+        )),
+        Line::raw(""),
+        Line::from(Span::styled(
+            "To fix this:",
+            Style::default()
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        )),
+    ];
+    lines.extend(error.advice().iter().map(|advice| {
         Line::from(vec![
+            Span::styled("  • ", Style::default().fg(Color::Yellow)),
+            Span::styled(*advice, Style::default().fg(Color::Yellow)),
+        ])
+    }));
+
+    let remaining = next_retry.saturating_duration_since(now);
+    lines.push(Line::raw(""));
+    lines.push(Line::from(Span::styled(
+        format!(
+            "Retrying in {}s (attempt {})",
+            remaining.as_millis().div_ceil(1000),
+            attempts + 1
+        ),
+        Style::default().fg(Color::DarkGray),
+    )));
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(border::PLAIN)
+        .border_style(Style::default().fg(Color::Red))
+        .title(Span::styled(
+            " Diagnostics ",
+            Style::default()
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        ));
+
+    let widget = Paragraph::new(lines)
+        .block(block)
+        .wrap(ratatui::widgets::Wrap { trim: false });
+
+    frame.render_widget(widget, area);
+}
+
+fn render_history(app: &App, frame: &mut Frame, area: Rect) {
+    // Hook runs take the bottom of the view once there are any
+    let mut area = area;
+    if !app.hook_log.is_empty() {
+        let [history, log] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Length(HOOK_LOG_LINES as u16 + 2),
+        ])
+        .areas(area);
+        render_hook_log(app, frame, log);
+        area = history;
+    }
+
+    let lines: Vec<Line> = if app.state.history.is_empty() {
+        vec![Line::from(Span::styled(
+            "No connections yet",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        app.state
+            .history
+            .iter()
+            .rev()
+            .map(|entry| {
+                let color = match entry.kind {
+                    HistoryKind::Connected | HistoryKind::Reconnected => Color::Green,
+                    HistoryKind::Disconnected => Color::Gray,
+                    HistoryKind::ReconnectAttempt => Color::Yellow,
+                    HistoryKind::ConnectFailed
+                    | HistoryKind::Dropped
+                    | HistoryKind::ReconnectFailed
+                    | HistoryKind::GaveUp => Color::Red,
+                };
+                let mut spans = vec![
+                    Span::styled(
+                        format_timestamp(entry.timestamp),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                    Span::raw("  "),
+                    Span::styled(
+                        format!("{:<16}", entry.kind.to_string()),
+                        Style::default().fg(color).add_modifier(Modifier::BOLD),
+                    ),
+                ];
+                if let Some(target) = &entry.target {
+                    spans.push(Span::styled(
+                        target.to_string(),
+                        Style::default().fg(Color::White),
+                    ));
+                }
+                if !entry.detail.is_empty() {
+                    spans.push(Span::styled(
+                        format!(" ({})", entry.detail),
+                        Style::default().fg(Color::Gray),
+                    ));
+                }
+                Line::from(spans)
+            })
+            .collect()
+    };
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(border::PLAIN)
+        .border_style(Style::default().fg(Color::Blue))
+        .title(Span::styled(
+            " History (UTC) ",
+            Style::default()
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        ));
+
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Latest hook runs with their output, newest at the bottom
+fn render_hook_log(app: &App, frame: &mut Frame, area: Rect) {
+    let mut lines = vec![];
+    for run in &app.hook_log {
+        let color = if run.succeeded() {
+            Color::Green
+        } else {
+            Color::Red
+        };
+        lines.push(Line::from(vec![
+            Span::styled(
+                format_timestamp(run.finished_at),
+                Style::default().fg(Color::DarkGray),
+            ),
+            Span::raw("  "),
+            Span::styled(
+                format!("{:<16}", run.event.to_string()),
+                Style::default().fg(color).add_modifier(Modifier::BOLD),
+            ),
+            // Outcome first, as long commands get cut off
+            Span::styled(format!("{}: ", run.outcome), Style::default().fg(color)),
+            Span::styled(run.command.clone(), Style::default().fg(Color::White)),
+        ]));
+        lines.extend(
+            run.output
+                .iter()
+                .map(|line| Line::styled(format!("  {}", line), Style::default().fg(Color::Gray))),
+        );
+    }
+    let skip = lines
+        .len()
+        .saturating_sub(area.height.saturating_sub(2) as usize);
+    lines.drain(..skip);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(border::PLAIN)
+        .border_style(Style::default().fg(Color::Blue))
+        .title(Span::styled(
+            " Hooks ",
+            Style::default()
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        ));
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn render_dns(app: &App, frame: &mut Frame, area: Rect) {
+    let label =
+        |name: &str| Span::styled(format!("{:<24}", name), Style::default().fg(Color::Gray));
+    let mut lines = match &app.dns {
+        None => vec![Line::styled(
+            "DNS settings unavailable, Ctrl+R to retry",
+            Style::default().fg(Color::DarkGray),
+        )],
+        Some(dns) => {
+            let servers = if dns.servers.is_empty() {
+                Span::styled("NordVPN default", Style::default().fg(Color::White))
+            } else {
+                Span::styled(
+                    types::join_addresses(&dns.servers),
+                    Style::default()
+                        .fg(Color::White)
+                        .add_modifier(Modifier::BOLD),
+                )
+            };
+            let threat_protection = match dns.threat_protection {
+                Some(true) => Span::styled("enabled", Style::default().fg(Color::Green)),
+                Some(false) => Span::styled("disabled", Style::default().fg(Color::Gray)),
+                None => Span::styled("not available", Style::default().fg(Color::DarkGray)),
+            };
+            vec![
+                Line::from(vec![label("DNS servers"), servers]),
+                Line::from(vec![label("Threat Protection Lite"), threat_protection]),
+            ]
+        }
+    };
+
+    if let Some(input) = &app.dns_input {
+        lines.push(Line::raw(""));
+        lines.push(Line::from(vec![
+            Span::styled("servers> ", Style::default().fg(Color::Yellow)),
+            Span::styled(
+                format!("{}_", input),
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            ),
+        ]));
+        lines.push(match types::parse_dns_servers(input) {
+            Ok(servers) => Line::styled(
+                format!(
+                    "✓ {} of {} servers, Enter saves",
+                    servers.len(),
+                    MAX_DNS_SERVERS
+                ),
+                Style::default().fg(Color::Green),
+            ),
+            Err(e) => Line::styled(format!("✗ {}", e), Style::default().fg(Color::Red)),
+        });
+    }
+
+    let warning = match (&app.dns_confirm, &app.dns) {
+        (Some(change), Some(dns)) => dns.conflict(change).map(|conflict| {
+            format!(
+                "⚠ {}. Press y to continue, any other key to cancel.",
+                conflict
+            )
+        }),
+        // Remind of the conflict before the user runs into it
+        (None, Some(dns)) if dns.threat_protection == Some(true) => Some(
+            "⚠ Custom DNS turns off Threat Protection Lite, which needs NordVPN's DNS".to_string(),
+        ),
+        (None, Some(dns)) if !dns.servers.is_empty() && dns.threat_protection.is_some() => {
+            Some("⚠ Turning on Threat Protection Lite drops the custom DNS servers".to_string())
+        }
+        _ => None,
+    };
+    if let Some(warning) = warning {
+        lines.push(Line::raw(""));
+        lines.push(Line::styled(warning, Style::default().fg(Color::Yellow)));
+    }
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(border::PLAIN)
+        .border_style(Style::default().fg(Color::Blue))
+        .title(Span::styled(
+            " DNS ",
+            Style::default()
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        ));
+
+    let widget = Paragraph::new(lines)
+        .block(block)
+        .wrap(ratatui::widgets::Wrap { trim: false });
+
+    frame.render_widget(widget, area);
+}
+
+/// Configured profiles beside the settings of the selected one and the
+/// outcome of the last run
+fn render_profiles(app: &App, frame: &mut Frame, area: Rect) {
+    let block = |title: &'static str| {
+        Block::default()
+            .borders(Borders::ALL)
+            .border_set(border::PLAIN)
+            .border_style(Style::default().fg(Color::Blue))
+            .title(Span::styled(
+                title,
+                Style::default()
+                    .fg(Color::White)
+                    .add_modifier(Modifier::BOLD),
+            ))
+    };
+
+    if app.config.profiles.is_empty() {
+        let lines = vec![
+            Line::styled("No profiles configured", Style::default().fg(Color::Gray)),
+            Line::raw(""),
+            Line::styled(
+                "Add [profiles.<name>] tables to the config file, e.g.",
+                Style::default().fg(Color::DarkGray),
+            ),
+            Line::styled(
+                "[profiles.work] country = \"Germany\", kill_switch = true",
+                Style::default().fg(Color::DarkGray),
+            ),
+        ];
+        frame.render_widget(Paragraph::new(lines).block(block(" Profiles ")), area);
+        return;
+    }
+
+    let [list_area, details_area] =
+        Layout::horizontal([Constraint::Percentage(30), Constraint::Percentage(70)]).areas(area);
+
+    let items: Vec<ListItem> = app
+        .config
+        .profiles
+        .keys()
+        .enumerate()
+        .map(|(i, name)| {
+            let selected = i == app.profile_selected;
+            let style = if selected {
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(Color::White)
+            };
+            ListItem::new(Span::styled(
+                format!("{}{}", if selected { "▶ " } else { "  " }, name),
+                style,
+            ))
+        })
+        .collect();
+    frame.render_widget(List::new(items).block(block(" Profiles ")), list_area);
+
+    let label =
+        |name: &str| Span::styled(format!("{:<14}", name), Style::default().fg(Color::Gray));
+    let value = |text: String| Span::styled(text, Style::default().fg(Color::White));
+    let mut lines = vec![];
+    if let Some(profile) = app
+        .selected_profile()
+        .and_then(|name| app.config.profiles.get(name))
+    {
+        if let Ok(target) = profile.target() {
+            lines.push(Line::from(vec![label("Target"), value(target.to_string())]));
+        }
+        if let Ok(Some(technology)) = profile.settings().technology() {
+            lines.push(Line::from(vec![
+                label("Technology"),
+                value(technology.to_string()),
+            ]));
+        }
+        if let Some(enabled) = profile.obfuscate {
+            lines.push(Line::from(vec![label("Obfuscate"), value(on_off(enabled))]));
+        }
+        if let Some(enabled) = profile.kill_switch {
+            lines.push(Line::from(vec![
+                label("Kill switch"),
+                value(on_off(enabled)),
+            ]));
+        }
+        if let Some(servers) = &profile.dns {
+            let dns = if servers.is_empty() {
+                "NordVPN default".to_string()
+            } else {
+                types::join_addresses(servers)
+            };
+            lines.push(Line::from(vec![label("DNS"), value(dns)]));
+        }
+        if let Some(allowlist) = &profile.allowlist {
+            let mut entries = allowlist.subnets.clone();
+            entries.extend(allowlist.ports.iter().map(PortRule::describe));
+            let entries = if entries.is_empty() {
+                "empty".to_string()
+            } else {
+                entries.join(", ")
+            };
+            lines.push(Line::from(vec![label("Allowlist"), value(entries)]));
+        }
+    }
+
+    if let Some(run) = &app.profile_run {
+        lines.push(Line::raw(""));
+        lines.push(Line::styled(
+            format!("Last applied: {}", run.name),
+            Style::default().fg(Color::Gray),
+        ));
+        for (step, outcome) in &run.steps {
+            let (mark, color, detail) = match outcome {
+                StepOutcome::Pending => ("…", Color::Yellow, String::new()),
+                StepOutcome::Done => ("✓", Color::Green, String::new()),
+                StepOutcome::Failed(e) => ("✗", Color::Red, format!(": {}", e)),
+                StepOutcome::Skipped => ("-", Color::DarkGray, " (skipped)".to_string()),
+            };
+            lines.push(Line::styled(
+                format!("{} {}{}", mark, step, detail),
+                Style::default().fg(color),
+            ));
+        }
+    }
+
+    let widget = Paragraph::new(lines)
+        .block(block(" Settings "))
+        .wrap(ratatui::widgets::Wrap { trim: false });
+    frame.render_widget(widget, details_area);
+}
+
+/// Current settings side by side with an imported settings file
+fn render_settings(app: &App, frame: &mut Frame, area: Rect) {
+    let mut lines = vec![];
+    match &app.settings {
+        None => lines.push(Line::styled(
+            "Settings unavailable, Ctrl+R to retry",
+            Style::default().fg(Color::DarkGray),
+        )),
+        Some(current) => {
+            let file = app.settings_file.as_ref().map(|(_, file)| file.rows());
+            let mut header = vec![
+                Span::styled(
+                    format!("{:<24}", "Setting"),
+                    Style::default().fg(Color::Gray),
+                ),
+                Span::styled(
+                    format!("{:<24}", "Current"),
+                    Style::default().fg(Color::Gray),
+                ),
+            ];
+            if let Some((path, _)) = &app.settings_file {
+                let name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                header.push(Span::styled(name, Style::default().fg(Color::Gray)));
+            }
+            lines.push(Line::from(header).style(Style::default().add_modifier(Modifier::BOLD)));
+
+            for (i, (setting, value)) in current.rows().into_iter().enumerate() {
+                let wanted = file.as_ref().and_then(|rows| rows[i].1.clone());
+                let differs = wanted.is_some() && wanted != value;
+                let color = if differs { Color::Yellow } else { Color::White };
+                let mut spans = vec![
+                    Span::styled(format!("{:<24}", setting), Style::default().fg(Color::Gray)),
+                    Span::styled(
+                        format!("{:<24}", value.unwrap_or_else(|| "-".to_string())),
+                        Style::default().fg(color),
+                    ),
+                ];
+                if file.is_some() {
+                    let wanted = match wanted {
+                        Some(wanted) if differs => format!("→ {}", wanted),
+                        Some(wanted) => wanted,
+                        None => "unchanged".to_string(),
+                    };
+                    spans.push(Span::styled(
+                        wanted,
+                        Style::default().fg(if differs {
+                            Color::Yellow
+                        } else {
+                            Color::DarkGray
+                        }),
+                    ));
+                }
+                lines.push(Line::from(spans));
+            }
+        }
+    }
+
+    if let Some((action, input)) = &app.settings_path_input {
+        let prompt = match action {
+            SettingsFileAction::Export => "export to> ",
+            SettingsFileAction::Import => "import from> ",
+        };
+        lines.push(Line::raw(""));
+        lines.push(Line::from(vec![
+            Span::styled(prompt, Style::default().fg(Color::Yellow)),
+            Span::styled(
+                format!("{}_", input),
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            ),
+        ]));
+        lines.push(Line::styled(
+            ".json files are JSON, anything else TOML",
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+
+    if let Some(steps) = &app.settings_plan {
+        lines.push(Line::raw(""));
+        lines.push(Line::styled(
+            format!(
+                "⚠ Apply {} changes? Press y to continue, any other key to cancel.",
+                steps.len()
+            ),
+            Style::default().fg(Color::Yellow),
+        ));
+        for step in steps {
+            lines.push(Line::styled(
+                format!("  {}", step),
+                Style::default().fg(Color::White),
+            ));
+        }
+    }
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(border::PLAIN)
+        .border_style(Style::default().fg(Color::Blue))
+        .title(Span::styled(
+            " Settings ",
+            Style::default()
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        ));
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn on_off(enabled: bool) -> String {
+    if enabled { "on" } else { "off" }.to_string()
+}
+
+fn render_diagnostics(app: &App, frame: &mut Frame, area: Rect) {
+    let lines: Vec<Line> = match &app.diagnostics {
+        None => vec![Line::from(Span::styled(
+            "Running checks...",
+            Style::default().fg(Color::Yellow),
+        ))],
+        Some(checks) => checks
+            .iter()
+            .map(|check| {
+                let color = match check.status {
+                    CheckStatus::Pass => Color::Green,
+                    CheckStatus::Warn => Color::Yellow,
+                    CheckStatus::Fail => Color::Red,
+                };
+                Line::from(vec![
+                    Span::styled(
+                        format!("[{}] ", check.status),
+                        Style::default().fg(color).add_modifier(Modifier::BOLD),
+                    ),
+                    Span::styled(
+                        format!("{}: ", check.name),
+                        Style::default().fg(Color::White),
+                    ),
+                    Span::styled(check.detail.clone(), Style::default().fg(Color::Gray)),
+                ])
+            })
+            .collect(),
+    };
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(border::PLAIN)
+        .border_style(Style::default().fg(Color::Blue))
+        .title(Span::styled(
+            " Diagnostics ",
+            Style::default()
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        ));
+
+    let widget = Paragraph::new(lines)
+        .block(block)
+        .wrap(ratatui::widgets::Wrap { trim: false });
+
+    frame.render_widget(widget, area);
+}
+
+/// A rectangle of at most `width` x `height`, centered in `area`
+fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+fn render_help_overlay(frame: &mut Frame, area: Rect) {
+    let mut lines = vec![];
+    for (group, bindings) in KEY_BINDINGS {
+        if !lines.is_empty() {
+            lines.push(Line::raw(""));
+        }
+        lines.push(Line::from(Span::styled(
+            *group,
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        )));
+        for (key, description) in *bindings {
+            lines.push(Line::from(vec![
+                Span::styled(format!("  {:<18}", key), Style::default().fg(Color::Yellow)),
+                Span::styled(*description, Style::default().fg(Color::Gray)),
+            ]));
+        }
+    }
+
+    let popup = centered_rect(60, lines.len() as u16 + 2, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(Color::Cyan))
+        .title(Span::styled(
+            " Key bindings (any key to close) ",
+            Style::default()
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        ));
+
+    frame.render_widget(Clear, popup);
+    frame.render_widget(Paragraph::new(lines).block(block), popup);
+}
+
+fn render_palette(app: &App, frame: &mut Frame, area: Rect) {
+    let Some(palette) = &app.palette else {
+        return;
+    };
+    let matches = app.palette_matches();
+
+    let popup = centered_rect(70, 16, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(Color::Yellow))
+        .title(Span::styled(
+            " Command palette ",
+            Style::default()
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        ));
+    let inner = block.inner(popup);
+    frame.render_widget(Clear, popup);
+    frame.render_widget(block, popup);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(inner);
+
+    let input = Line::from(vec![
+        Span::styled(": ", Style::default().fg(Color::Yellow)),
+        Span::styled(
+            format!("{}_", palette.query),
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ),
+    ]);
+    frame.render_widget(Paragraph::new(input), chunks[0]);
+
+    let items: Vec<ListItem> = if matches.is_empty() {
+        vec![ListItem::new(Span::styled(
+            "No matching actions",
+            Style::default().fg(Color::Red),
+        ))]
+    } else {
+        matches
+            .iter()
+            .map(|action| {
+                let mut spans = vec![Span::styled(
+                    action.to_string(),
+                    Style::default().fg(Color::Gray),
+                )];
+                if let Some(key) = action.key_hint() {
+                    spans.push(Span::styled(
+                        format!("  {}", key),
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect()
+    };
+
+    let list = List::new(items)
+        .highlight_style(
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("▶ ");
+    let mut state = ListState::default().with_selected(Some(palette.selected));
+    frame.render_stateful_widget(list, chunks[1], &mut state);
+}
+
+fn render_server_search(app: &App, frame: &mut Frame, area: Rect) {
+    let Some(search) = &app.server_search else {
+        return;
+    };
+    let completions = app.server_completions();
+
+    let popup = centered_rect(60, 16, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(Color::Yellow))
+        .title(Span::styled(
+            " Connect to server ",
+            Style::default()
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        ));
+    let inner = block.inner(popup);
+    frame.render_widget(Clear, popup);
+    frame.render_widget(block, popup);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1), // Input
+            Constraint::Length(1), // Validation
+            Constraint::Min(0),    // Completions
+            Constraint::Length(1), // Error or key hints
+        ])
+        .split(inner);
+
+    let input = Line::from(vec![
+        Span::styled("server> ", Style::default().fg(Color::Yellow)),
+        Span::styled(
+            format!("{}_", search.query),
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ),
+    ]);
+    frame.render_widget(Paragraph::new(input), chunks[0]);
+
+    let validation = if search.query.trim().is_empty() && completions.is_empty() {
+        Line::styled(
+            "Type a hostname like uk2345",
+            Style::default().fg(Color::DarkGray),
+        )
+    } else {
+        match app.server_search_target() {
+            Ok(target) => Line::styled(
+                format!("✓ Enter connects to {}", target),
+                Style::default().fg(Color::Green),
+            ),
+            Err(e) => Line::styled(format!("✗ {}", e), Style::default().fg(Color::Red)),
+        }
+    };
+    frame.render_widget(Paragraph::new(validation), chunks[1]);
+
+    let items: Vec<ListItem> = if completions.is_empty() {
+        let text = if app.servers_refreshing {
+            "Fetching recommended servers..."
+        } else {
+            "No known servers match"
+        };
+        vec![ListItem::new(Span::styled(
+            text,
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        completions
+            .iter()
+            .map(|server| {
+                let star = if app.state.is_favorite(&Target::Server(server.clone())) {
+                    "★ "
+                } else {
+                    "  "
+                };
+                let mut spans = vec![
+                    Span::styled(star, Style::default().fg(Color::Yellow)),
+                    Span::styled(format!("{:<10}", server), Style::default().fg(Color::Gray)),
+                ];
+                if let Some(recommendation) = app.servers.get(server) {
+                    spans.push(Span::styled(
+                        format!("  {} ({}% load)", recommendation.name, recommendation.load),
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect()
+    };
+    let list = List::new(items)
+        .highlight_style(
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("▶ ");
+    let selected = (!completions.is_empty()).then_some(search.selected);
+    let mut state = ListState::default().with_selected(selected);
+    frame.render_stateful_widget(list, chunks[2], &mut state);
+
+    let footer = match &app.error_message {
+        Some(error) => Line::styled(format!("✗ {}", error), Style::default().fg(Color::Red)),
+        None => Line::styled(
+            "Enter connect · Tab complete · Ctrl+F favorite · Esc close",
+            Style::default().fg(Color::DarkGray),
+        ),
+    };
+    frame.render_widget(Paragraph::new(footer), chunks[3]);
+}
+
+fn render_technology_picker(app: &App, frame: &mut Frame, area: Rect) {
+    let Some(picker) = &app.technology_picker else {
+        return;
+    };
+
+    let popup = centered_rect(64, TECHNOLOGY_PICKER_ROWS as u16 + 7, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(Color::Yellow))
+        .title(Span::styled(
+            " Technology ",
+            Style::default()
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        ));
+    let inner = block.inner(popup);
+    frame.render_widget(Clear, popup);
+    frame.render_widget(block, popup);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(2), // Current
+            Constraint::Min(0),    // Choices or pending change set
+            Constraint::Length(1), // Reconnect toggle
+            Constraint::Length(1), // Error or key hints
+        ])
+        .split(inner);
+
+    let current = app
+        .technology
+        .map(|technology| technology.to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    frame.render_widget(
+        Paragraph::new(Line::from(vec![
+            Span::styled("Current: ", Style::default().fg(Color::DarkGray)),
+            Span::styled(current, Style::default().fg(Color::White)),
+        ])),
+        chunks[0],
+    );
+
+    if let Some(plan) = &picker.pending {
+        let mut lines = vec![Line::styled(
+            format!(
+                "To {}, also:",
+                plan.steps
+                    .last()
+                    .map(|step| step.change.to_string())
+                    .unwrap_or_default()
+            ),
+            Style::default().fg(Color::Yellow),
+        )];
+        for step in plan.required() {
+            let mut spans = vec![Span::styled(
+                format!("• {}", step.change),
+                Style::default().fg(Color::White),
+            )];
+            if let Some(reason) = step.reason {
+                spans.push(Span::styled(
+                    format!(": {}", reason),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            lines.push(Line::from(spans));
+        }
+        frame.render_widget(
+            Paragraph::new(lines).wrap(ratatui::widgets::Wrap { trim: false }),
+            chunks[1],
+        );
+    } else {
+        let tunnel = app.tunnel.unwrap_or_default();
+        let mut items: Vec<ListItem> = Technology::ALL
+            .iter()
+            .map(|technology| {
+                let mut spans = vec![Span::styled(
+                    technology.to_string(),
+                    Style::default().fg(Color::Gray),
+                )];
+                if app.technology == Some(*technology) {
+                    spans.push(Span::styled(
+                        " (current)",
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        for (name, enabled) in [
+            ("Obfuscation", tunnel.obfuscate),
+            ("Post-quantum encryption", tunnel.post_quantum),
+        ] {
+            let line = match enabled {
+                Some(enabled) => Line::styled(
+                    format!("{}{}", if enabled { "[x] " } else { "[ ] " }, name),
+                    Style::default().fg(Color::Gray),
+                ),
+                None => Line::styled(
+                    format!("[-] {} (not available)", name),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            };
+            items.push(ListItem::new(line));
+        }
+        let list = List::new(items)
+            .highlight_style(
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol("▶ ");
+        let mut state = ListState::default().with_selected(Some(picker.selected));
+        frame.render_stateful_widget(list, chunks[1], &mut state);
+    }
+
+    let reconnect = match &app.last_target {
+        Some(target) => format!("reconnect to {} after switching", target),
+        None => "reconnect after switching (not connected)".to_string(),
+    };
+    let check = if picker.reconnect { "[x] " } else { "[ ] " };
+    frame.render_widget(
+        Paragraph::new(Line::styled(
+            format!("{}{}", check, reconnect),
+            Style::default().fg(Color::Gray),
+        )),
+        chunks[2],
+    );
+
+    let footer = match (&app.error_message, &picker.pending) {
+        (Some(error), _) => Line::styled(format!("✗ {}", error), Style::default().fg(Color::Red)),
+        (None, Some(_)) => Line::styled(
+            "Enter apply all · any other key go back",
+            Style::default().fg(Color::DarkGray),
+        ),
+        (None, None) => Line::styled(
+            "Enter apply or toggle · r toggle reconnect · Esc close",
+            Style::default().fg(Color::DarkGray),
+        ),
+    };
+    frame.render_widget(Paragraph::new(footer), chunks[3]);
+}
+
+/// Error or success message if there is one, otherwise key hints for the view
+fn help_line(app: &App) -> Line<'_> {
+    if let Some(error) = &app.error_message {
+        let mut spans = vec![
             Span::styled(
                 "✗ ",
                 Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
             ),
             Span::styled(error, Style::default().fg(Color::Red)),
-        ])
+        ];
+        if let Some(hint) = app.error_hint {
+            spans.push(Span::raw(" | "));
+            spans.push(Span::styled(hint, Style::default().fg(Color::Yellow)));
+        }
+        Line::from(spans)
     } else if let Some(success) = &app.success_message {
         Line::from(vec![
             Span::styled(
@@ -225,6 +1641,95 @@ fn render_help(app: &App, frame: &mut Frame, area: Rect) {
             ),
             Span::styled(success, Style::default().fg(Color::Green)),
         ])
+    } else if app.view == View::History {
+        Line::from(vec![
+            Span::styled("?", Style::default().fg(Color::Cyan)),
+            Span::raw(": help | "),
+            Span::styled("Tab", Style::default().fg(Color::Cyan)),
+            Span::raw(": switch view | "),
+            Span::styled("Ctrl+D", Style::default().fg(Color::Red)),
+            Span::raw(": disconnect | "),
+            Span::styled("Ctrl+W", Style::default().fg(Color::Yellow)),
+            Span::raw(": watchdog | "),
+            Span::styled("Esc/q", Style::default().fg(Color::Magenta)),
+            Span::raw(": quit"),
+        ])
+    } else if app.view == View::Dns && app.dns_input.is_some() {
+        Line::from(vec![
+            Span::styled("Type", Style::default().fg(Color::Yellow)),
+            Span::raw(" up to 3 addresses | "),
+            Span::styled("Enter", Style::default().fg(Color::Green)),
+            Span::raw(": save | "),
+            Span::styled("Esc", Style::default().fg(Color::Magenta)),
+            Span::raw(": cancel"),
+        ])
+    } else if app.view == View::Settings && app.settings_path_input.is_some() {
+        Line::from(vec![
+            Span::styled("Type", Style::default().fg(Color::Yellow)),
+            Span::raw(" a file path | "),
+            Span::styled("Enter", Style::default().fg(Color::Green)),
+            Span::raw(": ok | "),
+            Span::styled("Esc", Style::default().fg(Color::Magenta)),
+            Span::raw(": cancel"),
+        ])
+    } else if app.view == View::Settings {
+        Line::from(vec![
+            Span::styled("?", Style::default().fg(Color::Cyan)),
+            Span::raw(": help | "),
+            Span::styled("Tab", Style::default().fg(Color::Cyan)),
+            Span::raw(": switch view | "),
+            Span::styled("e", Style::default().fg(Color::Green)),
+            Span::raw(": export | "),
+            Span::styled("i", Style::default().fg(Color::Yellow)),
+            Span::raw(": import | "),
+            Span::styled("a", Style::default().fg(Color::Red)),
+            Span::raw(": apply | "),
+            Span::styled("Esc/q", Style::default().fg(Color::Magenta)),
+            Span::raw(": quit"),
+        ])
+    } else if app.view == View::Profiles {
+        Line::from(vec![
+            Span::styled("?", Style::default().fg(Color::Cyan)),
+            Span::raw(": help | "),
+            Span::styled("Tab", Style::default().fg(Color::Cyan)),
+            Span::raw(": switch view | "),
+            Span::styled("↑/↓", Style::default().fg(Color::Yellow)),
+            Span::raw(": select | "),
+            Span::styled("Enter", Style::default().fg(Color::Green)),
+            Span::raw(": apply | "),
+            Span::styled("Ctrl+D", Style::default().fg(Color::Red)),
+            Span::raw(": disconnect | "),
+            Span::styled("Esc/q", Style::default().fg(Color::Magenta)),
+            Span::raw(": quit"),
+        ])
+    } else if app.view == View::Dns {
+        Line::from(vec![
+            Span::styled("?", Style::default().fg(Color::Cyan)),
+            Span::raw(": help | "),
+            Span::styled("Tab", Style::default().fg(Color::Cyan)),
+            Span::raw(": switch view | "),
+            Span::styled("e", Style::default().fg(Color::Green)),
+            Span::raw(": edit | "),
+            Span::styled("x", Style::default().fg(Color::Red)),
+            Span::raw(": reset | "),
+            Span::styled("t", Style::default().fg(Color::Yellow)),
+            Span::raw(": threat protection | "),
+            Span::styled("Esc/q", Style::default().fg(Color::Magenta)),
+            Span::raw(": quit"),
+        ])
+    } else if app.view == View::Diagnostics {
+        Line::from(vec![
+            Span::styled("?", Style::default().fg(Color::Cyan)),
+            Span::raw(": help | "),
+            Span::styled("Tab", Style::default().fg(Color::Cyan)),
+            Span::raw(": switch view | "),
+            Span::styled("Ctrl+R", Style::default().fg(Color::Yellow)),
+            Span::raw(": re-run checks | "),
+            Span::styled("c", Style::default().fg(Color::Green)),
+            Span::raw(": copy report | "),
+            Span::styled("Esc/q", Style::default().fg(Color::Magenta)),
+            Span::raw(": quit"),
+        ])
     } else if app.filter_mode {
         Line::from(vec![
             Span::styled("Type", Style::default().fg(Color::Yellow)),
@@ -236,6 +1741,8 @@ fn render_help(app: &App, frame: &mut Frame, area: Rect) {
         ])
     } else {
         Line::from(vec![
+            Span::styled("?", Style::default().fg(Color::Cyan)),
+            Span::raw(": help | "),
             Span::styled("↑/↓/j/k", Style::default().fg(Color::Cyan)),
             Span::raw(": navigate | "),
             Span::styled("Enter", Style::default().fg(Color::Green)),
@@ -244,10 +1751,20 @@ fn render_help(app: &App, frame: &mut Frame, area: Rect) {
             Span::raw(": disconnect | "),
             Span::styled("Ctrl+R", Style::default().fg(Color::Yellow)),
             Span::raw(": refresh | "),
+            Span::styled("Ctrl+F", Style::default().fg(Color::Yellow)),
+            Span::raw(": favorite | "),
+            Span::styled("Ctrl+W", Style::default().fg(Color::Yellow)),
+            Span::raw(": watchdog | "),
+            Span::styled("Tab", Style::default().fg(Color::Cyan)),
+            Span::raw(": views | "),
             Span::styled("Esc/q", Style::default().fg(Color::Magenta)),
             Span::raw(": quit"),
         ])
-    };
+    }
+}
+
+fn render_help(app: &App, frame: &mut Frame, area: Rect) {
+    let help_text = help_line(app);
 
     let block = Block::default()
         .borders(Borders::ALL)
@@ -264,3 +1781,464 @@ fn render_help(app: &App, frame: &mut Frame, area: Rect) {
 
     frame.render_widget(help_widget, area);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{Palette, ProfileRun, ServerSearch, TechnologyPicker};
+    use crate::config::Config;
+    use crate::doctor::Check;
+    use crate::error::BackendError;
+    use crate::hooks::{HookEvent, HookOutcome, HookRun};
+    use crate::profile::Step;
+    use crate::servers::Recommendation;
+    use crate::snapshot::SettingsSnapshot;
+    use crate::state::HistoryEntry;
+    use crate::state::State;
+    use crate::types::{
+        ConnectionStatus, Country, DnsChange, DnsSettings, SettingChange, Toggle, TunnelSettings,
+    };
+    use ratatui::{Terminal, backend::TestBackend};
+    use std::path::PathBuf;
+
+    const COUNTRIES: &[&str] = &[
+        "Australia",
+        "Canada",
+        "Germany",
+        "Japan",
+        "Netherlands",
+        "Sweden",
+        "Switzerland",
+        "United_Kingdom",
+        "United_States",
+    ];
+
+    fn connected() -> ConnectionStatus {
+        ConnectionStatus::Connected {
+            country: "Germany".to_string(),
+            city: Some("Frankfurt".to_string()),
+            server: Some("de1234.nordvpn.com".to_string()),
+            ip: Some("185.0.0.1".to_string()),
+        }
+    }
+
+    /// An app with a fixed country list, favorites and history
+    fn fixture(status: ConnectionStatus) -> App {
+        let countries = COUNTRIES
+            .iter()
+            .map(|name| Country::new(name.to_string()))
+            .collect();
+        let config = Config {
+            status_poll_secs: 0,
+            ..Config::default()
+        };
+        let germany = Target::Country("Germany".to_string());
+        let mut state = State::default();
+        state.favorites = vec![Target::Country("Sweden".to_string()), germany.clone()];
+        state.history = vec![
+            HistoryEntry {
+                timestamp: 1_760_000_000,
+                kind: HistoryKind::Connected,
+                target: Some(germany.clone()),
+                detail: String::new(),
+            },
+            HistoryEntry {
+                timestamp: 1_760_000_600,
+                kind: HistoryKind::Dropped,
+                target: Some(germany.clone()),
+                detail: String::new(),
+            },
+            HistoryEntry {
+                timestamp: 1_760_000_660,
+                kind: HistoryKind::Connected,
+                target: Some(germany),
+                detail: String::new(),
+            },
+        ];
+        let mut app = App::with_data(config, countries, status, state);
+        app.select_index(2);
+        app
+    }
+
+    fn app() -> App {
+        fixture(connected())
+    }
+
+    fn draw(app: &mut App, width: u16, height: u16) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal
+            .draw(|frame| render(app, frame, Instant::now()))
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        buffer
+            .content
+            .chunks(buffer.area.width as usize)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn layout_mode_follows_area() {
+        let mode = |width, height| LayoutMode::for_area(Rect::new(0, 0, width, height));
+        assert_eq!(mode(200, 10), LayoutMode::Compact);
+        assert_eq!(mode(80, 24), LayoutMode::Normal);
+        assert_eq!(mode(120, 24), LayoutMode::TwoColumn);
+        assert_eq!(mode(160, 24), LayoutMode::ThreeColumn);
+    }
+
+    #[test]
+    fn compact_layout_keeps_list_visible() {
+        let mut app = app();
+        let screen = draw(&mut app, 60, 10);
+        assert!(screen.contains("● Connected to Frankfurt"));
+        assert!(screen.contains("Japan"));
+        assert!(!screen.contains(" Status "));
+
+        app.filter = "neth".to_string();
+        app.update_filter();
+        let screen = draw(&mut app, 60, 10);
+        assert!(screen.contains("Filter: neth │ ● Connected"));
+        assert!(!screen.contains("Germany ●"));
+    }
+
+    #[test]
+    fn normal_layout_has_no_side_panes() {
+        let screen = draw(&mut app(), 80, 24);
+        assert!(screen.contains(" Status "));
+        assert!(screen.contains("Netherlands"));
+        assert!(!screen.contains(" Details "));
+    }
+
+    #[test]
+    fn wide_layout_shows_details() {
+        let screen = draw(&mut app(), 120, 24);
+        assert!(screen.contains(" Details "));
+        assert!(screen.contains("de1234.nordvpn.com"));
+        assert!(!screen.contains(" Activity "));
+    }
+
+    #[test]
+    fn widest_layout_shows_activity() {
+        let mut app = app();
+        app.status_samples.extend([true, true, false, true]);
+        let screen = draw(&mut app, 160, 24);
+        assert!(screen.contains(" Details "));
+        assert!(screen.contains(" Activity "));
+        assert!(screen.contains("Connected 75% of last 4 polls"));
+    }
+
+    /// Render `app` at the given size and compare against the stored snapshot
+    fn assert_screen(name: &str, app: &mut App, width: u16, height: u16) {
+        assert_screen_at(name, app, width, height, Instant::now());
+    }
+
+    /// Like [`assert_screen`], rendering as if it were `now`
+    fn assert_screen_at(name: &str, app: &mut App, width: u16, height: u16, now: Instant) {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|frame| render(app, frame, now)).unwrap();
+        insta::assert_snapshot!(name, terminal.backend());
+    }
+
+    #[test]
+    fn snapshot_countries_connected() {
+        let mut app = app();
+        app.status_samples
+            .extend([false, false, true, true, true, false, true, true]);
+        assert_screen("countries_connected_compact", &mut app, 60, 12);
+        assert_screen("countries_connected_normal", &mut app, 80, 24);
+        assert_screen("countries_connected_two_column", &mut app, 110, 24);
+        assert_screen("countries_connected_three_column", &mut app, 150, 24);
+    }
+
+    #[test]
+    fn snapshot_countries_disconnected() {
+        let mut app = fixture(ConnectionStatus::Disconnected);
+        assert_screen("countries_disconnected_compact", &mut app, 60, 12);
+        assert_screen("countries_disconnected_normal", &mut app, 80, 24);
+    }
+
+    #[test]
+    fn snapshot_countries_connecting() {
+        let mut app = fixture(ConnectionStatus::Connecting);
+        assert_screen("countries_connecting_normal", &mut app, 80, 24);
+        assert_screen("countries_connecting_two_column", &mut app, 110, 24);
+
+        app.connect_log = vec![
+            "Connecting to Germany #1045 (de1045.nordvpn.com)".to_string(),
+            "You are connected to Germany #1045 (de1045.nordvpn.com)!".to_string(),
+        ];
+        assert_screen("countries_connecting_log", &mut app, 80, 24);
+    }
+
+    #[test]
+    fn snapshot_countries_filter() {
+        let mut app = app();
+        app.filter_mode = true;
+        app.filter = "united".to_string();
+        app.update_filter();
+        assert_screen("countries_filter_mode_normal", &mut app, 80, 24);
+
+        app.filter = "zz".to_string();
+        app.update_filter();
+        assert_screen("countries_empty_filter_normal", &mut app, 80, 24);
+        assert_screen("countries_empty_filter_compact", &mut app, 60, 12);
+    }
+
+    #[test]
+    fn snapshot_error_message() {
+        let mut app = fixture(ConnectionStatus::Disconnected);
+        let err = BackendError::InvalidTarget("Atlantis".to_string());
+        app.error_message = Some(format!("Failed to connect: {}", err));
+        app.error_hint = err.hint();
+        assert_screen("countries_error_normal", &mut app, 80, 24);
+        assert_screen("countries_error_compact", &mut app, 60, 12);
+    }
+
+    #[test]
+    fn snapshot_success_message() {
+        let mut app = app();
+        app.success_message = Some("Connected to Germany".to_string());
+        assert_screen("countries_success_normal", &mut app, 80, 24);
+    }
+
+    #[test]
+    fn snapshot_daemon_unavailable() {
+        let mut app = fixture(ConnectionStatus::Disconnected);
+        // A fixed render time keeps the countdown the same however slow the
+        // test runs
+        let now = Instant::now();
+        app.daemon = DaemonState::Unavailable {
+            error: BackendError::DaemonUnavailable("cannot reach system daemon".to_string()),
+            attempts: 2,
+            next_retry: now + std::time::Duration::from_secs(30),
+        };
+        assert_screen_at("countries_daemon_unavailable_normal", &mut app, 80, 24, now);
+    }
+
+    #[test]
+    fn snapshot_history() {
+        let mut app = app();
+        app.view = View::History;
+        assert_screen("history_normal", &mut app, 80, 24);
+        assert_screen("history_compact", &mut app, 60, 12);
+    }
+
+    #[test]
+    fn snapshot_history_hooks() {
+        let mut app = app();
+        app.view = View::History;
+        let run = |event, command: &str, outcome, output: &[&str]| HookRun {
+            event,
+            command: command.to_string(),
+            outcome,
+            output: output.iter().map(|line| line.to_string()).collect(),
+            finished_at: 1_760_000_700,
+        };
+        app.hook_log = [
+            run(
+                HookEvent::UnexpectedDrop,
+                "systemctl --user stop syncthing",
+                HookOutcome::Exited(0),
+                &[],
+            ),
+            run(
+                HookEvent::PostConnect,
+                "/etc/nordvpn-tui/hosts.sh",
+                HookOutcome::Exited(1),
+                &["updating /etc/hosts for de1045", "permission denied"],
+            ),
+            run(
+                HookEvent::ServerChange,
+                "notify-send moved",
+                HookOutcome::TimedOut(std::time::Duration::from_secs(30)),
+                &[],
+            ),
+        ]
+        .into();
+        assert_screen("history_hooks", &mut app, 80, 24);
+    }
+
+    #[test]
+    fn snapshot_diagnostics() {
+        let mut app = app();
+        app.view = View::Diagnostics;
+        app.diagnostics = Some(vec![
+            Check {
+                name: "nordvpn binary",
+                status: CheckStatus::Pass,
+                detail: "NordVPN Version 3.19.0".to_string(),
+            },
+            Check {
+                name: "daemon socket",
+                status: CheckStatus::Pass,
+                detail: "/run/nordvpn/nordvpnd.sock".to_string(),
+            },
+            Check {
+                name: "IPv6 leak",
+                status: CheckStatus::Warn,
+                detail: "IPv6 is enabled and routed outside the tunnel".to_string(),
+            },
+            Check {
+                name: "login",
+                status: CheckStatus::Fail,
+                detail: "not logged in".to_string(),
+            },
+        ]);
+        assert_screen("diagnostics_normal", &mut app, 80, 24);
+    }
+
+    #[test]
+    fn snapshot_dns() {
+        let mut app = app();
+        app.view = View::Dns;
+        app.dns = Some(DnsSettings {
+            servers: vec!["1.1.1.1".parse().unwrap()],
+            threat_protection: Some(false),
+        });
+        assert_screen("dns_normal", &mut app, 80, 24);
+
+        app.dns_input = Some("1.1.1.1, 9.9.9".to_string());
+        assert_screen("dns_editing", &mut app, 80, 24);
+
+        app.dns_input = None;
+        app.dns_confirm = Some(DnsChange::ThreatProtection(true));
+        assert_screen("dns_confirm", &mut app, 80, 24);
+    }
+
+    #[test]
+    fn snapshot_profiles() {
+        let mut app = app();
+        app.view = View::Profiles;
+        assert_screen("profiles_empty", &mut app, 80, 24);
+
+        app.config = Config::parse(
+            r#"
+            [profiles.streaming]
+            country = "United_States"
+            [profiles.work]
+            country = "Germany"
+            city = "Frankfurt"
+            technology = "openvpn"
+            kill_switch = true
+            dns = ["1.1.1.1"]
+            allowlist = { subnets = ["192.168.1.0/24"], ports = [22] }
+        "#,
+        )
+        .unwrap();
+        app.profile_selected = 1;
+        app.profile_run = Some(ProfileRun {
+            name: "work".to_string(),
+            steps: vec![
+                (
+                    Step::Setting {
+                        change: SettingChange::Technology(Technology::OpenVpnUdp),
+                        reason: None,
+                    },
+                    StepOutcome::Done,
+                ),
+                (
+                    Step::Toggle(Toggle::KillSwitch, true),
+                    StepOutcome::Failed("Kill Switch is not available".to_string()),
+                ),
+                (
+                    Step::Connect(Target::City {
+                        country: "Germany".to_string(),
+                        city: "Frankfurt".to_string(),
+                    }),
+                    StepOutcome::Skipped,
+                ),
+            ],
+        });
+        assert_screen("profiles", &mut app, 100, 24);
+    }
+
+    #[test]
+    fn snapshot_settings() {
+        let mut app = app();
+        app.view = View::Settings;
+        let settings = "Technology: NORDLYNX\nFirewall: enabled\nKill Switch: disabled\n\
+            Threat Protection Lite: disabled\nMeshnet: disabled\nDNS: disabled\n";
+        app.settings = Some(SettingsSnapshot::from_settings(&crate::parse::settings(
+            settings,
+        )));
+        assert_screen("settings_normal", &mut app, 100, 28);
+
+        let file = SettingsSnapshot {
+            technology: Some("openvpn".to_string()),
+            kill_switch: Some(true),
+            firewall: Some(true),
+            dns: Some(vec!["9.9.9.9".parse().unwrap()]),
+            ..SettingsSnapshot::default()
+        };
+        app.settings_file = Some((PathBuf::from("/home/user/laptop.toml"), file));
+        assert_screen("settings_compare", &mut app, 100, 28);
+
+        app.settings_plan = Some(vec![
+            Step::Setting {
+                change: SettingChange::Technology(Technology::OpenVpnUdp),
+                reason: None,
+            },
+            Step::Toggle(Toggle::KillSwitch, true),
+            Step::Dns(DnsChange::Servers(vec!["9.9.9.9".parse().unwrap()])),
+        ]);
+        assert_screen("settings_confirm", &mut app, 100, 32);
+    }
+
+    #[test]
+    fn snapshot_overlays() {
+        let mut app = app();
+        app.show_help = true;
+        assert_screen("help_overlay", &mut app, 100, 72);
+
+        app.show_help = false;
+        app.palette = Some(Palette {
+            query: "conn swe".to_string(),
+            selected: 0,
+        });
+        assert_screen("palette", &mut app, 100, 30);
+
+        app.palette = None;
+        let recommendation = |server: &str, name: &str, load| Recommendation {
+            hostname: format!("{}.nordvpn.com", server),
+            name: name.to_string(),
+            load,
+        };
+        app.servers.update(vec![
+            recommendation("uk2345", "United Kingdom #2345", 12),
+            recommendation("de1045", "Germany #1045", 30),
+            recommendation("uk1780", "United Kingdom #1780", 41),
+        ]);
+        app.state
+            .favorites
+            .push(Target::Server("uk812".to_string()));
+        app.server_search = Some(ServerSearch {
+            query: "uk".to_string(),
+            selected: 1,
+        });
+        assert_screen("server_search", &mut app, 100, 30);
+
+        app.server_search = None;
+        app.technology = Some(Technology::NordLynx);
+        let tunnel = TunnelSettings {
+            technology: Some(Technology::NordLynx),
+            obfuscate: Some(false),
+            post_quantum: Some(true),
+            meshnet: Some(false),
+        };
+        app.tunnel = Some(tunnel);
+        app.technology_picker = Some(TechnologyPicker {
+            selected: 2,
+            reconnect: true,
+            pending: None,
+        });
+        assert_screen("technology_picker", &mut app, 100, 30);
+
+        app.technology_picker = Some(TechnologyPicker {
+            selected: 4,
+            reconnect: true,
+            pending: tunnel.plan(SettingChange::Obfuscate(true)).ok(),
+        });
+        assert_screen("technology_picker_confirm", &mut app, 100, 30);
+    }
+}
@@ -0,0 +1,429 @@
+//! `nordvpn-tui watch`: poll the status headless and print a line, or a
+//! JSON object, each time it changes, for status bars such as waybar,
+//! polybar or tmux.
+
+use crate::cli::WatchOptions;
+use crate::config::WatchConfig;
+use crate::error::BackendError;
+use crate::nordvpn::NordVPN;
+use crate::types::{ConnectionStatus, StatusDetails};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Placeholders a template can use
+pub const FIELDS: &[&str] = &[
+    "status",
+    "state",
+    "country",
+    "country_code",
+    "city",
+    "server",
+    "ip",
+    "technology",
+    "uptime",
+    "rx",
+    "tx",
+    "rx_rate",
+    "tx_rate",
+    "error",
+];
+
+/// A format string with `{field}` placeholders; `{{` and `}}` stand for
+/// literal braces
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template(Vec<Part>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Field(&'static str),
+}
+
+impl Template {
+    pub fn parse(format: &str) -> Result<Self, String> {
+        let mut parts = vec![];
+        let mut text = String::new();
+        let mut chars = format.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("unclosed {{{}", name)),
+                        }
+                    }
+                    let Some(field) = FIELDS.iter().find(|field| **field == name) else {
+                        return Err(format!(
+                            "unknown field {{{}}}; available: {}",
+                            name,
+                            FIELDS.join(", ")
+                        ));
+                    };
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Field(field));
+                }
+                '}' => return Err("unmatched '}'; write '}}' for a literal brace".to_string()),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Self(parts))
+    }
+
+    /// Fill in the placeholders; unknown values are left empty and so is
+    /// surrounding whitespace
+    pub fn render(&self, values: &BTreeMap<&'static str, String>) -> String {
+        let mut line = String::new();
+        for part in &self.0 {
+            match part {
+                Part::Text(text) => line.push_str(text),
+                Part::Field(field) => line.push_str(values.get(field).map_or("", String::as_str)),
+            }
+        }
+        line.trim().to_string()
+    }
+}
+
+/// Turns status polls into output lines, remembering the last one so that
+/// only changes are printed
+#[derive(Debug)]
+pub struct Watcher {
+    format: Template,
+    /// Used instead of `format` while not connected
+    disconnected_format: Option<Template>,
+    json: bool,
+    /// What the previous line showed: its text, plus the class and tooltip
+    /// in JSON mode
+    last: Option<Vec<String>>,
+    /// Time and byte counters of the previous connected sample
+    transfer: Option<(Instant, u64, u64)>,
+}
+
+impl Watcher {
+    pub fn new(format: Template, disconnected_format: Option<Template>, json: bool) -> Self {
+        Self {
+            format,
+            disconnected_format,
+            json,
+            last: None,
+            transfer: None,
+        }
+    }
+
+    /// The line to print for a poll taken at `now`, or `None` if it would
+    /// repeat the previous one
+    pub fn sample(
+        &mut self,
+        result: Result<StatusDetails, BackendError>,
+        now: Instant,
+    ) -> Option<String> {
+        let values = self.values(result, now);
+        let connected = values
+            .get("state")
+            .is_some_and(|state| state == "connected");
+        let template = match &self.disconnected_format {
+            Some(template) if !connected => template,
+            _ => &self.format,
+        };
+        let text = template.render(&values);
+        // The other JSON fields, like uptime and traffic, change with every
+        // poll and only matter once something shown changes
+        let shown = if self.json {
+            vec![
+                text.clone(),
+                values["state"].clone(),
+                values["status"].clone(),
+            ]
+        } else {
+            vec![text.clone()]
+        };
+        if self.last.as_ref() == Some(&shown) {
+            return None;
+        }
+        self.last = Some(shown);
+
+        let line = if self.json {
+            // Shaped like a waybar custom module, with every field added
+            let mut object: Map<String, Value> = values
+                .iter()
+                .map(|(key, value)| (key.to_string(), Value::from(value.as_str())))
+                .collect();
+            object.insert("text".to_string(), Value::from(text));
+            object.insert(
+                "tooltip".to_string(),
+                Value::from(values["status"].as_str()),
+            );
+            object.insert("class".to_string(), Value::from(values["state"].as_str()));
+            Value::Object(object).to_string()
+        } else {
+            text
+        };
+        Some(line)
+    }
+
+    fn values(
+        &mut self,
+        result: Result<StatusDetails, BackendError>,
+        now: Instant,
+    ) -> BTreeMap<&'static str, String> {
+        let mut values = BTreeMap::new();
+        let details = match result {
+            Ok(details) => details,
+            Err(e) => {
+                self.transfer = None;
+                values.insert("state", "unavailable".to_string());
+                values.insert("status", "NordVPN unavailable".to_string());
+                values.insert("error", e.to_string());
+                return values;
+            }
+        };
+
+        values.insert("status", details.status.to_string());
+        let ConnectionStatus::Connected {
+            country,
+            city,
+            server,
+            ip,
+        } = details.status
+        else {
+            self.transfer = None;
+            let state = match details.status {
+                ConnectionStatus::Connecting => "connecting",
+                _ => "disconnected",
+            };
+            values.insert("state", state.to_string());
+            return values;
+        };
+
+        values.insert("state", "connected".to_string());
+        values.insert("country", country);
+        if let Some(server) = server {
+            // Hostnames start with the country code, e.g. "de1045.nordvpn.com"
+            let code: String = server
+                .chars()
+                .take_while(char::is_ascii_alphabetic)
+                .collect();
+            if !code.is_empty() {
+                values.insert("country_code", code.to_uppercase());
+            }
+            values.insert("server", server);
+        }
+        values.extend(city.map(|city| ("city", city)));
+        values.extend(ip.map(|ip| ("ip", ip)));
+        values.extend(
+            details
+                .technology
+                .map(|technology| ("technology", technology.to_string())),
+        );
+        values.extend(details.uptime.map(|uptime| ("uptime", uptime)));
+
+        let Some((rx, tx)) = details.transfer else {
+            self.transfer = None;
+            return values;
+        };
+        values.insert("rx", size(rx as f64));
+        values.insert("tx", size(tx as f64));
+        if let Some((then, last_rx, last_tx)) = self.transfer
+            && rx >= last_rx
+            && tx >= last_tx
+        {
+            let secs = now.duration_since(then).as_secs_f64();
+            if secs > 0.0 {
+                values.insert(
+                    "rx_rate",
+                    format!("{}/s", size((rx - last_rx) as f64 / secs)),
+                );
+                values.insert(
+                    "tx_rate",
+                    format!("{}/s", size((tx - last_tx) as f64 / secs)),
+                );
+            }
+        }
+        self.transfer = Some((now, rx, tx));
+        values
+    }
+}
+
+/// Bytes in binary units, e.g. "512 B" or "1.2 MiB"
+fn size(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{:.0} {}", value, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Poll until the reader of stdout goes away, or print one line with
+/// `once`. Returns the exit code.
+pub fn run(options: &WatchOptions, config: &WatchConfig) -> i32 {
+    let format = options.format.as_deref().unwrap_or(&config.format);
+    let templates = Template::parse(format).and_then(|format| {
+        // A format given on the command line is used in every state
+        let disconnected = match (&options.format, &config.disconnected_format) {
+            (None, Some(disconnected)) => Some(Template::parse(disconnected)?),
+            _ => None,
+        };
+        Ok((format, disconnected))
+    });
+    let (format, disconnected) = match templates {
+        Ok(templates) => templates,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return 2;
+        }
+    };
+    let mut watcher = Watcher::new(format, disconnected, options.json);
+    let interval = Duration::from_secs(options.interval.unwrap_or(config.interval_secs).max(1));
+
+    let mut stdout = std::io::stdout();
+    loop {
+        if let Some(line) = watcher.sample(NordVPN::get_status_details(), Instant::now())
+            && writeln!(stdout, "{}", line)
+                .and_then(|()| stdout.flush())
+                .is_err()
+        {
+            return 0;
+        }
+        if options.once {
+            return 0;
+        }
+        thread::sleep(interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Technology;
+
+    fn connected(transfer: Option<(u64, u64)>) -> Result<StatusDetails, BackendError> {
+        Ok(StatusDetails {
+            status: ConnectionStatus::Connected {
+                country: "Germany".to_string(),
+                city: Some("Frankfurt".to_string()),
+                server: Some("de1045.nordvpn.com".to_string()),
+                ip: Some("185.130.184.86".to_string()),
+            },
+            technology: Some(Technology::NordLynx),
+            uptime: Some("12 seconds".to_string()),
+            transfer,
+        })
+    }
+
+    fn disconnected() -> Result<StatusDetails, BackendError> {
+        Ok(StatusDetails {
+            status: ConnectionStatus::Disconnected,
+            technology: None,
+            uptime: None,
+            transfer: None,
+        })
+    }
+
+    #[test]
+    fn parses_templates() {
+        assert!(Template::parse("{country_code} {city} {{x}}").is_ok());
+        let e = Template::parse("{county}").unwrap_err();
+        assert!(e.contains("unknown field {county}"), "{e}");
+        assert!(Template::parse("a}b").is_err());
+        assert!(Template::parse("{city").is_err());
+    }
+
+    #[test]
+    fn prints_only_changes_with_rates() {
+        let format = Template::parse("{country_code} {city} ↓{rx_rate} {{{technology}}}").unwrap();
+        let off = Template::parse("VPN {state}").unwrap();
+        let mut watcher = Watcher::new(format, Some(off), false);
+        let start = Instant::now();
+        let later = |secs| start + Duration::from_secs(secs);
+
+        assert_eq!(
+            watcher.sample(disconnected(), start).as_deref(),
+            Some("VPN disconnected")
+        );
+        assert_eq!(watcher.sample(disconnected(), later(5)), None);
+        assert_eq!(
+            watcher
+                .sample(connected(Some((0, 0))), later(10))
+                .as_deref(),
+            Some("DE Frankfurt ↓ {NordLynx}")
+        );
+        assert_eq!(
+            watcher
+                .sample(connected(Some((10 * 1024 * 1024, 0))), later(20))
+                .as_deref(),
+            Some("DE Frankfurt ↓1.0 MiB/s {NordLynx}")
+        );
+        assert_eq!(
+            watcher.sample(connected(Some((20 * 1024 * 1024, 0))), later(30)),
+            None
+        );
+        assert_eq!(
+            watcher
+                .sample(Err(BackendError::BinaryNotFound), later(40))
+                .as_deref(),
+            Some("VPN unavailable")
+        );
+    }
+
+    #[test]
+    fn json_is_waybar_compatible() {
+        let format = Template::parse("{country}").unwrap();
+        let mut watcher = Watcher::new(format, None, true);
+        let line = watcher.sample(connected(Some((1536, 512))), Instant::now());
+        let value: Value = serde_json::from_str(&line.unwrap()).unwrap();
+        assert_eq!(value["text"], "Germany");
+        assert_eq!(value["class"], "connected");
+        assert_eq!(
+            value["tooltip"],
+            "Connected to Frankfurt, Germany (de1045.nordvpn.com)"
+        );
+        assert_eq!(value["rx"], "1.5 KiB");
+        assert_eq!(value["tx"], "512 B");
+        assert_eq!(value["country_code"], "DE");
+        assert!(value.get("rx_rate").is_none());
+    }
+
+    #[test]
+    fn json_ignores_changes_outside_template() {
+        let format = Template::parse("{country_code}").unwrap();
+        let mut watcher = Watcher::new(format, None, true);
+        let start = Instant::now();
+        assert!(watcher.sample(connected(Some((0, 0))), start).is_some());
+
+        let mut later = connected(Some((4096, 1024)));
+        if let Ok(details) = &mut later {
+            details.uptime = Some("1 minute 12 seconds".to_string());
+        }
+        assert_eq!(watcher.sample(later, start + Duration::from_secs(60)), None);
+        assert!(
+            watcher
+                .sample(disconnected(), start + Duration::from_secs(70))
+                .is_some()
+        );
+    }
+}
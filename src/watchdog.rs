@@ -0,0 +1,311 @@
+use crate::config::WatchdogConfig;
+use crate::types::Target;
+use std::time::{Duration, Instant};
+
+/// Reconnects to the last target after an unexpected disconnect.
+///
+/// This only tracks timing and attempts; the app performs the actual
+/// reconnects and reports their outcome back.
+#[derive(Debug)]
+pub struct Watchdog {
+    config: WatchdogConfig,
+    /// Whether drops should trigger reconnects
+    pub enabled: bool,
+    pub state: WatchdogState,
+    /// Id of the last attempt put in flight
+    last_id: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchdogState {
+    Idle,
+    /// Waiting for the next attempt on `target` to become due
+    Waiting {
+        target: Target,
+        attempt: u32,
+        due: Instant,
+        tried: Vec<Target>,
+    },
+    /// An attempt is running in the background
+    InFlight {
+        /// Tells the outcome of this attempt from one cancelled earlier
+        id: u64,
+        target: Target,
+        attempt: u32,
+        tried: Vec<Target>,
+    },
+}
+
+/// What happens after a failed attempt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Failure {
+    /// Try the same target again after a delay
+    Retry { attempt: u32, delay: Duration },
+    /// The target is exhausted, move on to a favorite
+    Fallback(Target),
+    /// Nothing left to try
+    GaveUp,
+}
+
+impl Watchdog {
+    pub fn new(config: WatchdogConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            config,
+            state: WatchdogState::Idle,
+            last_id: 0,
+        }
+    }
+
+    /// Whether a reconnect sequence is under way
+    pub fn is_active(&self) -> bool {
+        self.state != WatchdogState::Idle
+    }
+
+    /// The connection to `target` dropped; schedule the first attempt.
+    pub fn on_drop(&mut self, target: Target, now: Instant) {
+        if !self.enabled || self.is_active() {
+            return;
+        }
+        self.state = WatchdogState::Waiting {
+            tried: vec![target.clone()],
+            target,
+            attempt: 1,
+            due: now + self.delay(1),
+        };
+    }
+
+    /// Return the target, attempt number and attempt id to try if an
+    /// attempt is due, marking it in flight.
+    pub fn poll(&mut self, now: Instant) -> Option<(Target, u32, u64)> {
+        let WatchdogState::Waiting {
+            target,
+            attempt,
+            due,
+            tried,
+        } = &self.state
+        else {
+            return None;
+        };
+        if now < *due {
+            return None;
+        }
+        let (target, attempt) = (target.clone(), *attempt);
+        self.last_id += 1;
+        self.state = WatchdogState::InFlight {
+            id: self.last_id,
+            target: target.clone(),
+            attempt,
+            tried: tried.clone(),
+        };
+        Some((target, attempt, self.last_id))
+    }
+
+    /// Whether attempt `id` is still in flight, rather than cancelled
+    pub fn is_in_flight(&self, id: u64) -> bool {
+        matches!(self.state, WatchdogState::InFlight { id: current, .. } if current == id)
+    }
+
+    /// Target of the attempt in flight, if any
+    pub fn reconnecting(&self) -> Option<&Target> {
+        match &self.state {
+            WatchdogState::InFlight { target, .. } => Some(target),
+            _ => None,
+        }
+    }
+
+    /// The in-flight attempt succeeded
+    pub fn on_success(&mut self) {
+        self.state = WatchdogState::Idle;
+    }
+
+    /// The in-flight attempt failed; decide what to do next. Returns `None`
+    /// if no attempt is in flight, e.g. because it was cancelled.
+    pub fn on_failure(&mut self, now: Instant, favorites: &[Target]) -> Option<Failure> {
+        let WatchdogState::InFlight {
+            target,
+            attempt,
+            tried,
+            ..
+        } = std::mem::replace(&mut self.state, WatchdogState::Idle)
+        else {
+            return None;
+        };
+
+        if attempt < self.config.max_attempts {
+            let next = attempt + 1;
+            let delay = self.delay(next);
+            self.state = WatchdogState::Waiting {
+                target,
+                attempt: next,
+                due: now + delay,
+                tried,
+            };
+            return Some(Failure::Retry {
+                attempt: next,
+                delay,
+            });
+        }
+
+        let fallback = self
+            .config
+            .fallback_to_favorites
+            .then(|| favorites.iter().find(|f| !tried.contains(f)))
+            .flatten();
+        Some(match fallback {
+            Some(next) => {
+                let mut tried = tried;
+                tried.push(next.clone());
+                self.state = WatchdogState::Waiting {
+                    target: next.clone(),
+                    attempt: 1,
+                    due: now + self.delay(1),
+                    tried,
+                };
+                Failure::Fallback(next.clone())
+            }
+            None => Failure::GaveUp,
+        })
+    }
+
+    /// Stop any reconnect sequence, e.g. because the user took over
+    pub fn cancel(&mut self) {
+        self.state = WatchdogState::Idle;
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.config.max_attempts
+    }
+
+    /// Exponential backoff for the given attempt number (starting at 1)
+    fn delay(&self, attempt: u32) -> Duration {
+        let secs = self
+            .config
+            .backoff_initial_secs
+            .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+            .min(self.config.backoff_max_secs);
+        Duration::from_secs(secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn watchdog(max_attempts: u32, fallback_to_favorites: bool) -> Watchdog {
+        Watchdog::new(WatchdogConfig {
+            enabled: true,
+            max_attempts,
+            backoff_initial_secs: 2,
+            backoff_max_secs: 5,
+            fallback_to_favorites,
+        })
+    }
+
+    fn country(name: &str) -> Target {
+        Target::Country(name.to_string())
+    }
+
+    #[test]
+    fn retries_with_capped_backoff() {
+        let mut watchdog = watchdog(3, false);
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        watchdog.on_drop(country("Germany"), start);
+        assert_eq!(watchdog.poll(at(1)), None);
+        let (target, attempt, id) = watchdog.poll(at(2)).unwrap();
+        assert_eq!((target, attempt), (country("Germany"), 1));
+        assert!(watchdog.is_in_flight(id));
+        // A second drop while reconnecting changes nothing
+        watchdog.on_drop(country("Sweden"), at(2));
+        assert!(watchdog.is_in_flight(id));
+
+        assert_eq!(
+            watchdog.on_failure(at(3), &[]),
+            Some(Failure::Retry {
+                attempt: 2,
+                delay: Duration::from_secs(4)
+            })
+        );
+        assert_eq!(watchdog.poll(at(6)), None);
+        let (_, attempt, next_id) = watchdog.poll(at(7)).unwrap();
+        assert_eq!(attempt, 2);
+        assert_ne!(next_id, id);
+        assert!(!watchdog.is_in_flight(id));
+        assert_eq!(
+            watchdog.on_failure(at(8), &[]),
+            Some(Failure::Retry {
+                attempt: 3,
+                delay: Duration::from_secs(5)
+            })
+        );
+
+        watchdog.poll(at(13)).unwrap();
+        watchdog.on_success();
+        assert!(!watchdog.is_active());
+    }
+
+    #[test]
+    fn falls_back_to_untried_favorites_then_gives_up() {
+        let mut watchdog = watchdog(1, true);
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let favorites = [country("Germany"), country("Sweden")];
+
+        watchdog.on_drop(country("Germany"), start);
+        watchdog.poll(at(2)).unwrap();
+        // Germany was tried already, so Sweden is next
+        assert_eq!(
+            watchdog.on_failure(at(3), &favorites),
+            Some(Failure::Fallback(country("Sweden")))
+        );
+        let (target, attempt, _) = watchdog.poll(at(5)).unwrap();
+        assert_eq!((target, attempt), (country("Sweden"), 1));
+        assert_eq!(
+            watchdog.on_failure(at(6), &favorites),
+            Some(Failure::GaveUp)
+        );
+        assert!(!watchdog.is_active());
+    }
+
+    #[test]
+    fn gives_up_without_fallback() {
+        let mut watchdog = watchdog(1, false);
+        let start = Instant::now();
+        watchdog.on_drop(country("Germany"), start);
+        watchdog.poll(start + Duration::from_secs(2)).unwrap();
+        assert_eq!(
+            watchdog.on_failure(start, &[country("Sweden")]),
+            Some(Failure::GaveUp)
+        );
+    }
+
+    #[test]
+    fn ignores_outcome_after_cancel() {
+        let mut watchdog = watchdog(3, true);
+        let start = Instant::now();
+        watchdog.on_drop(country("Germany"), start);
+        let (_, _, id) = watchdog.poll(start + Duration::from_secs(2)).unwrap();
+
+        watchdog.cancel();
+        assert!(!watchdog.is_in_flight(id));
+        assert_eq!(watchdog.on_failure(start, &[]), None);
+        assert!(!watchdog.is_active());
+
+        // A new sequence does not accept the old attempt's outcome either
+        watchdog.on_drop(country("Germany"), start);
+        let (_, _, new_id) = watchdog.poll(start + Duration::from_secs(2)).unwrap();
+        assert!(!watchdog.is_in_flight(id));
+        assert!(watchdog.is_in_flight(new_id));
+    }
+
+    #[test]
+    fn does_nothing_while_disabled() {
+        let mut watchdog = watchdog(3, false);
+        watchdog.enabled = false;
+        let start = Instant::now();
+        watchdog.on_drop(country("Germany"), start);
+        assert_eq!(watchdog.poll(start + Duration::from_secs(60)), None);
+    }
+}
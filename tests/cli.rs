@@ -0,0 +1,229 @@
+//! End-to-end tests of the headless commands against the fake `nordvpn`
+//! CLI from `examples/fake-nordvpn.rs`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+/// A temporary home with the fake CLI installed as `nordvpn`
+struct Sandbox {
+    dir: TempDir,
+}
+
+impl Sandbox {
+    fn new(state: &str) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        // The example is built next to the binary under test
+        let fake = Path::new(env!("CARGO_BIN_EXE_nordvpn-tui"))
+            .with_file_name("examples")
+            .join("fake-nordvpn");
+        std::os::unix::fs::symlink(fake, dir.path().join("nordvpn")).unwrap();
+        fs::write(dir.path().join("nordvpn-state.toml"), state).unwrap();
+        Self { dir }
+    }
+
+    fn binary(&self) -> PathBuf {
+        self.dir.path().join("nordvpn")
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_nordvpn-tui"))
+            .args(args)
+            .env("NORDVPN_BINARY", self.binary())
+            .env("XDG_CONFIG_HOME", self.dir.path())
+            .env("XDG_STATE_HOME", self.dir.path())
+            .output()
+            .unwrap()
+    }
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn doctor_reports_fake_cli() {
+    let sandbox = Sandbox::new("");
+    let report = stdout(&sandbox.run(&["doctor"]));
+
+    assert!(report.contains("[PASS] nordvpn binary: NordVPN Version 3.19.0"));
+    assert!(report.contains("[PASS] login: logged in as user@example.com"));
+    assert!(report.contains("[PASS] settings: technology NORDLYNX, kill switch disabled"));
+    assert!(report.contains("[PASS] meshnet: disabled"));
+}
+
+#[test]
+fn doctor_flags_logged_out_account() {
+    let sandbox = Sandbox::new("logged_in = false");
+    let output = sandbox.run(&["doctor"]);
+
+    assert!(stdout(&output).contains("[FAIL] login: not logged in; run: nordvpn login"));
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn doctor_without_daemon() {
+    let sandbox = Sandbox::new("daemon = false");
+    let report = stdout(&sandbox.run(&["doctor"]));
+
+    assert!(report.contains("[WARN] login: could not check: NordVPN daemon is not reachable"));
+}
+
+#[test]
+fn profile_applies_settings_and_connects() {
+    let sandbox = Sandbox::new("[cities]\nGermany = [\"Berlin\", \"Frankfurt\"]");
+    let config_dir = sandbox.dir.path().join("nordvpn-tui");
+    fs::create_dir_all(&config_dir).unwrap();
+    fs::write(
+        config_dir.join("config.toml"),
+        r#"
+        [profiles.work]
+        country = "Germany"
+        city = "Frankfurt"
+        kill_switch = true
+        allowlist = { subnets = ["192.168.1.0/24"], ports = [{ start = 8080, end = 8090, protocol = "tcp" }] }
+    "#,
+    )
+    .unwrap();
+
+    let output = sandbox.run(&["profile", "work"]);
+    let out = stdout(&output);
+    assert!(out.contains("Applying profile work"));
+    assert!(out.contains("✓ turn on kill switch"));
+    assert!(out.contains("✓ allowlist subnet 192.168.1.0/24"));
+    assert!(out.contains("✓ allowlist ports 8080 - 8090 (TCP)"));
+    assert!(out.contains("✓ connect to Frankfurt, Germany"));
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = sandbox.run(&["profile", "home"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("available: work"));
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn settings_copy_between_machines() {
+    let old =
+        Sandbox::new("settings = { Technology = \"OPENVPN\", \"Kill Switch\" = \"enabled\" }");
+    let new = Sandbox::new("");
+    let file = old.dir.path().join("settings.toml");
+    let file = file.to_str().unwrap();
+
+    let output = old.run(&["settings", "export", file]);
+    assert_eq!(output.status.code(), Some(0));
+    let exported = fs::read_to_string(file).unwrap();
+    assert!(exported.contains("technology = \"openvpn\""));
+    assert!(exported.contains("kill_switch = true"));
+
+    let output = new.run(&["settings", "diff", file]);
+    assert!(stdout(&output).contains("Kill Switch: off → on"));
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = new.run(&["settings", "import", "--yes", file]);
+    let out = stdout(&output);
+    assert!(out.contains("✓ switch technology to OpenVPN (UDP)"));
+    assert!(out.contains("✓ turn on kill switch"));
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = new.run(&["settings", "diff", file]);
+    assert!(stdout(&output).contains("Settings match"));
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn settings_import_lists_required_steps() {
+    let sandbox = Sandbox::new("settings = { Technology = \"OPENVPN\", Obfuscate = \"enabled\" }");
+    let file = sandbox.dir.path().join("settings.toml");
+    fs::write(&file, "technology = \"nordlynx\"\n").unwrap();
+
+    let output = sandbox.run(&["settings", "import", "--yes", file.to_str().unwrap()]);
+    let out = stdout(&output);
+    assert!(out.contains("• turn off obfuscation: obfuscation only works with OpenVPN"));
+    assert!(out.contains("• switch technology to NordLynx\n"));
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn config_file_selects_binary() {
+    let sandbox = Sandbox::new("");
+    let config_dir = sandbox.dir.path().join("nordvpn-tui");
+    fs::create_dir_all(&config_dir).unwrap();
+    fs::write(
+        config_dir.join("config.toml"),
+        format!("nordvpn_binary = {:?}\n", sandbox.binary()),
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_nordvpn-tui"))
+        .arg("doctor")
+        .env_remove("NORDVPN_BINARY")
+        .env("XDG_CONFIG_HOME", sandbox.dir.path())
+        .output()
+        .unwrap();
+    assert!(stdout(&output).contains("[PASS] nordvpn binary: NordVPN Version 3.19.0"));
+}
+
+#[test]
+fn agent_applies_first_matching_rule() {
+    let sandbox = Sandbox::new("status = \"connected\"\ncountry = \"Germany\"");
+    let config_dir = sandbox.dir.path().join("nordvpn-tui");
+    fs::create_dir_all(&config_dir).unwrap();
+
+    let output = sandbox.run(&["agent", "--once"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no [[agent.rules]]"));
+    assert_eq!(output.status.code(), Some(2));
+
+    fs::write(
+        config_dir.join("config.toml"),
+        r#"
+        [agent]
+        trusted_ssids = ["Home"]
+        ssid_command = ["echo", "Home"]
+
+        [[agent.rules]]
+        network = "untrusted-wifi"
+        action = "connect"
+
+        [[agent.rules]]
+        network = "trusted-wifi"
+        action = "disconnect"
+    "#,
+    )
+    .unwrap();
+
+    let output = sandbox.run(&["agent", "--once"]);
+    let out = stdout(&output);
+    assert_eq!(output.status.code(), Some(0));
+    // Without a default route there is no network for the rules to match
+    if out.starts_with("Network: offline") {
+        return;
+    }
+    assert!(out.contains("Rule 2: on trusted Wi-Fi, disconnect"));
+    assert!(out.contains("✓ disconnect"));
+}
+
+#[test]
+fn watch_prints_status_for_status_bars() {
+    let sandbox = Sandbox::new(
+        r#"
+        status = "connected"
+        country = "Germany"
+        city = "Frankfurt"
+        hostname = "de1045.nordvpn.com"
+        transfer = "1.21 MiB received, 370.82 KiB sent"
+    "#,
+    );
+
+    let output = sandbox.run(&["watch", "--once", "--format", "{country_code} {city} ↓{rx}"]);
+    assert_eq!(stdout(&output), "DE Frankfurt ↓1.2 MiB\n");
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = sandbox.run(&["watch", "--once", "--json"]);
+    let out = stdout(&output);
+    assert!(out.contains(r#""class":"connected""#), "{out}");
+    assert!(out.contains(r#""text":"Connected to Frankfurt, Germany (de1045.nordvpn.com)""#));
+
+    let output = sandbox.run(&["watch", "--format", "{county}"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown field {county}"));
+    assert_eq!(output.status.code(), Some(2));
+}